@@ -0,0 +1,187 @@
+// st_wba_ble/src/addr.rs
+//! Bluetooth device addresses and identity-address configuration.
+
+use crate::Ble;
+use crate::security::{self, IdentityAddress};
+use crate::status::{self, BleStatus, Result};
+use st_wba_ble_sys::ffi;
+
+// `aci_hal_write_config_data` offsets (ble_defs.h).
+const CONFIG_DATA_PUBLIC_ADDRESS_OFFSET: u8 = 0x00;
+const CONFIG_DATA_RANDOM_ADDRESS_OFFSET: u8 = 0x2E;
+
+/// Address type as used by HCI/ACI commands.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum AddressType {
+    Public = 0x00,
+    Random = 0x01,
+}
+
+impl From<u8> for AddressType {
+    fn from(v: u8) -> Self {
+        if v & 0x01 == 0 {
+            AddressType::Public
+        } else {
+            AddressType::Random
+        }
+    }
+}
+
+/// A 48-bit device address; `bytes` are little-endian (LSB first), as on air.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Address {
+    pub kind: AddressType,
+    pub bytes: [u8; 6],
+}
+
+impl Address {
+    pub const fn public(bytes: [u8; 6]) -> Self {
+        Self {
+            kind: AddressType::Public,
+            bytes,
+        }
+    }
+
+    pub const fn random(bytes: [u8; 6]) -> Self {
+        Self {
+            kind: AddressType::Random,
+            bytes,
+        }
+    }
+
+    /// Static random address: the two most significant bits are forced to `0b11`.
+    pub const fn static_random(mut bytes: [u8; 6]) -> Self {
+        bytes[5] |= 0xC0;
+        Self::random(bytes)
+    }
+
+    /// Derive a stable static random address from a per-device seed
+    /// (e.g. the MCU's 96-bit unique ID).
+    ///
+    /// The seed is folded with 64-bit FNV-1a; the result is never all-zero
+    /// or all-one in its random part.
+    pub fn static_random_from_seed(seed: &[u8]) -> Self {
+        let mut h: u64 = 0xCBF2_9CE4_8422_2325;
+        for b in seed {
+            h ^= *b as u64;
+            h = h.wrapping_mul(0x0000_0100_0000_01B3);
+        }
+        let mut bytes = [0u8; 6];
+        bytes.copy_from_slice(&h.to_le_bytes()[..6]);
+        // The 46 random bits must contain at least one 0 and one 1.
+        if bytes[..5].iter().all(|b| *b == 0) && bytes[5] & 0x3F == 0 {
+            bytes[0] = 0x01;
+        } else if bytes[..5].iter().all(|b| *b == 0xFF) && bytes[5] & 0x3F == 0x3F {
+            bytes[0] = 0xFE;
+        }
+        Self::static_random(bytes)
+    }
+
+    /// Whether this is a valid static random address: random type, top two
+    /// bits `0b11`, and the other 46 bits neither all zero nor all one.
+    pub fn is_static_random(&self) -> bool {
+        let b = &self.bytes;
+        let low_zero = b[..5].iter().all(|x| *x == 0) && b[5] & 0x3F == 0;
+        let low_ones = b[..5].iter().all(|x| *x == 0xFF) && b[5] & 0x3F == 0x3F;
+        self.kind == AddressType::Random && b[5] & 0xC0 == 0xC0 && !low_zero && !low_ones
+    }
+}
+
+/// Own address type used when advertising, scanning or initiating.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(u8)]
+pub enum OwnAddress {
+    /// Public device address.
+    #[default]
+    Public = 0x00,
+    /// Static random address.
+    Random = 0x01,
+    /// Resolvable private address, falling back to the public address
+    /// when the peer is not in the resolving list.
+    ResolvableOrPublic = 0x02,
+    /// Resolvable private address, falling back to the static random address.
+    ResolvableOrRandom = 0x03,
+}
+
+impl Ble {
+    /// Program the public device address (little-endian, LSB first).
+    ///
+    /// Call before [`Ble::init_peripheral`] so GAP picks it up as identity address.
+    pub fn set_public_address(bytes: [u8; 6]) -> Result<()> {
+        let rc = unsafe {
+            ffi::aci_hal_write_config_data(CONFIG_DATA_PUBLIC_ADDRESS_OFFSET, 6, bytes.as_ptr())
+        } as i32;
+        if BleStatus::from(rc) != BleStatus::Ok {
+            return Err(BleStatus::from(rc));
+        }
+        Ok(())
+    }
+
+    /// Program a static random identity address.
+    ///
+    /// `addr` must pass [`Address::is_static_random`] (build it with
+    /// [`Address::static_random`]). Call before [`Ble::init_peripheral`],
+    /// and pair with [`IdentityAddress::StaticRandom`] when bonding.
+    pub fn set_static_random_address(addr: Address) -> Result<()> {
+        if !addr.is_static_random() {
+            return Err(BleStatus::Other(status::INVALID_PARAMS));
+        }
+        let rc = unsafe {
+            ffi::aci_hal_write_config_data(
+                CONFIG_DATA_RANDOM_ADDRESS_OFFSET,
+                6,
+                addr.bytes.as_ptr(),
+            )
+        } as i32;
+        if BleStatus::from(rc) != BleStatus::Ok {
+            return Err(BleStatus::from(rc));
+        }
+        Ok(())
+    }
+
+    /// Program a static random identity address derived from `seed`
+    /// (see [`Address::static_random_from_seed`]).
+    pub fn set_static_random_address_from_seed(seed: &[u8]) -> Result<Address> {
+        let addr = Address::static_random_from_seed(seed);
+        Self::set_static_random_address(addr)?;
+        Ok(addr)
+    }
+
+    /// Read back the identity address currently configured in the controller,
+    /// as selected by [`AuthRequirements::identity_address`](crate::security::AuthRequirements::identity_address).
+    pub fn address(&self) -> Result<Address> {
+        let (kind, offset) = match security::identity_address() {
+            IdentityAddress::Public => (AddressType::Public, CONFIG_DATA_PUBLIC_ADDRESS_OFFSET),
+            IdentityAddress::StaticRandom => {
+                (AddressType::Random, CONFIG_DATA_RANDOM_ADDRESS_OFFSET)
+            }
+        };
+        let mut len: u8 = 0;
+        let mut buf = [0u8; 6];
+        let rc =
+            unsafe { ffi::aci_hal_read_config_data(offset, &mut len, buf.as_mut_ptr()) } as i32;
+        if BleStatus::from(rc) != BleStatus::Ok {
+            return Err(BleStatus::from(rc));
+        }
+        if len as usize != buf.len() {
+            return Err(BleStatus::Other(status::FAILED));
+        }
+        Ok(Address { kind, bytes: buf })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn static_random_validation() {
+        assert!(Address::static_random([1, 2, 3, 4, 5, 6]).is_static_random());
+        assert!(!Address::random([1, 2, 3, 4, 5, 0x06]).is_static_random());
+        assert!(!Address::public([1, 2, 3, 4, 5, 0xC6]).is_static_random());
+        assert!(!Address::random([0, 0, 0, 0, 0, 0xC0]).is_static_random());
+        assert!(!Address::random([0xFF; 6]).is_static_random());
+        assert!(Address::static_random_from_seed(b"uid").is_static_random());
+    }
+}
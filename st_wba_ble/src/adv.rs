@@ -1,15 +1,57 @@
+use crate::addr::OwnAddress;
 use crate::status::{BleStatus, Result};
 use st_wba_ble_sys::ffi;
 
+/// Which peers may scan or connect while advertising.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(u8)]
+pub enum AdvFilterPolicy {
+    /// Anyone may scan and connect.
+    #[default]
+    AllowAll = 0x00,
+    /// Scan requests only from the Filter Accept List.
+    Scan = 0x01,
+    /// Connection requests only from the Filter Accept List.
+    Connect = 0x02,
+    /// Scan and connection requests only from the Filter Accept List.
+    ScanAndConnect = 0x03,
+}
+
+/// Advertising parameters for [`start_name`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct AdvParams {
+    /// Minimum advertising interval, in units of 0.625 ms.
+    pub interval_min: u16,
+    /// Maximum advertising interval, in units of 0.625 ms.
+    pub interval_max: u16,
+    /// Address to advertise with; use a `Resolvable*` variant when privacy is enabled.
+    pub own_address: OwnAddress,
+    /// Filter Accept List usage (see [`crate::filter::FilterAcceptList`]).
+    pub filter_policy: AdvFilterPolicy,
+}
+
+impl Default for AdvParams {
+    /// 20–40 ms interval, public address.
+    fn default() -> Self {
+        Self {
+            interval_min: 0x0020,
+            interval_max: 0x0040,
+            own_address: OwnAddress::Public,
+            filter_policy: AdvFilterPolicy::AllowAll,
+        }
+    }
+}
+
 /// Start undirected connectable advertising quickly with a given local name.
 ///
 /// Uses 20–40 ms advertising interval, public address, no filter policy.
 pub fn start_fast_name(name: &str) -> Result<()> {
+    start_name(name, &AdvParams::default())
+}
+
+/// Start undirected connectable advertising with a given local name and parameters.
+pub fn start_name(name: &str, params: &AdvParams) -> Result<()> {
     let adv_type_undirected: u8 = 0x00; // ADV_IND
-    let own_addr_public: u8 = 0x00;
-    let filter_allow_all: u8 = 0x00;
-    let min: u16 = 0x0020; // 20 ms
-    let max: u16 = 0x0040; // 40 ms
     let name_len: u8 = name.len() as u8;
     let name_ptr: *const u8 = name.as_bytes().as_ptr();
     let uuid_len: u8 = 0;
@@ -20,10 +62,10 @@ pub fn start_fast_name(name: &str) -> Result<()> {
     let rc = unsafe {
         ffi::aci_gap_set_discoverable(
             adv_type_undirected,
-            min,
-            max,
-            own_addr_public,
-            filter_allow_all,
+            params.interval_min,
+            params.interval_max,
+            params.own_address as u8,
+            params.filter_policy as u8,
             name_len,
             name_ptr,
             uuid_len,
@@ -0,0 +1,90 @@
+// st_wba_ble/src/bond.rs
+//! Bonding database management (list, query, forget, clear).
+
+use crate::addr::{Address, AddressType};
+use crate::status::{BleStatus, Result};
+use core::sync::atomic::{AtomicBool, Ordering};
+use st_wba_ble_sys::ffi;
+
+/// Most entries `aci_gap_get_bonded_devices` can return in one response.
+pub const MAX_LISTED_BONDS: usize = 36;
+
+static PERSIST: AtomicBool = AtomicBool::new(true);
+
+/// Whether new pairings are stored as bonds (see [`BondStore::set_persistent`]).
+pub(crate) fn is_persistent() -> bool {
+    PERSIST.load(Ordering::Relaxed)
+}
+
+/// Handle to the stack's security database, obtained from [`crate::Ble::bonds`].
+pub struct BondStore {
+    _priv: (),
+}
+
+impl BondStore {
+    pub(crate) const fn new() -> Self {
+        BondStore { _priv: () }
+    }
+
+    /// Copy the identity addresses of bonded peers into `out`.
+    /// Returns the number of entries written.
+    pub fn list(&self, out: &mut [Address]) -> Result<usize> {
+        let mut n: u8 = 0;
+        let mut entries: [ffi::Bonded_Device_Entry_t; MAX_LISTED_BONDS] =
+            unsafe { core::mem::zeroed() };
+        let rc = unsafe { ffi::aci_gap_get_bonded_devices(&mut n, entries.as_mut_ptr()) } as i32;
+        if BleStatus::from(rc) != BleStatus::Ok {
+            return Err(BleStatus::from(rc));
+        }
+        let count = core::cmp::min(n as usize, core::cmp::min(out.len(), MAX_LISTED_BONDS));
+        for (dst, e) in out.iter_mut().zip(entries.iter()).take(count) {
+            *dst = Address {
+                kind: AddressType::from(e.Address_Type),
+                bytes: e.Address,
+            };
+        }
+        Ok(count)
+    }
+
+    /// Whether `peer` is in the security database.
+    pub fn is_bonded(&self, peer: &Address) -> bool {
+        let rc =
+            unsafe { ffi::aci_gap_is_device_bonded(peer.kind as u8, peer.bytes.as_ptr()) } as i32;
+        BleStatus::from(rc) == BleStatus::Ok
+    }
+
+    /// Forget a single peer ("forget device").
+    pub fn remove(&self, peer: &Address) -> Result<()> {
+        let rc = unsafe { ffi::aci_gap_remove_bonded_device(peer.kind as u8, peer.bytes.as_ptr()) }
+            as i32;
+        if BleStatus::from(rc) == BleStatus::Ok {
+            Ok(())
+        } else {
+            Err(BleStatus::from(rc))
+        }
+    }
+
+    /// Erase every bond (factory reset).
+    pub fn clear(&self) -> Result<()> {
+        let rc = unsafe { ffi::aci_gap_clear_security_db() } as i32;
+        if BleStatus::from(rc) == BleStatus::Ok {
+            Ok(())
+        } else {
+            Err(BleStatus::from(rc))
+        }
+    }
+
+    /// Choose whether pairings are kept as bonds.
+    ///
+    /// When disabled, subsequent calls to
+    /// [`Security::set_auth_requirements`](crate::security::Security::set_auth_requirements)
+    /// request pairing without bonding, so keys are used for the current link
+    /// only and nothing is written to the security database.
+    pub fn set_persistent(&self, persistent: bool) {
+        PERSIST.store(persistent, Ordering::Relaxed);
+    }
+
+    pub fn is_persistent(&self) -> bool {
+        is_persistent()
+    }
+}
@@ -13,22 +13,5 @@ pub extern "C" fn HAL_GetTick() -> u32 {
     (embassy_time::Instant::now().as_millis() as u64 % (u32::MAX as u64 + 1)) as u32
 }
 
-// --- Optional placeholders for RNG/AES/PKA ---------------------------------
-// Only add these if you decide not to compile ST’s C "Interfaces" drivers and
-// your chosen stack libs reference them. Leave commented until needed.
-// #[unsafe(no_mangle)]
-// pub extern "C" fn HW_RNG_Init() {}
-//
-// #[unsafe(no_mangle)]
-// pub extern "C" fn HW_RNG_GetRandom32(pval: *mut u32) -> i32 {
-//     unsafe { if !pval.is_null() { *pval = 0xDEADBEEF; } }
-//     0 // return 0 as “OK” by convention
-// }
-//
-// #[unsafe(no_mangle)]
-// pub extern "C" fn HW_AES_Init() {}
-//
-// #[unsafe(no_mangle)]
-// pub extern "C" fn HW_PKA_Init() {}
-//
-// // …add more exact signatures as needed (match ST headers in your repo)
+// RNG/AES/PKA: enable `rust-rng` / `rust-crypto` instead of stubbing the
+// `HW_RNG_*` / `HW_AES_*` / `HW_PKA_*` drivers here.
@@ -0,0 +1,249 @@
+// st_wba_ble/src/config.rs
+//! `BleConfig`: builder for GATT/GAP initialisation.
+
+use crate::Ble;
+use crate::privacy::Privacy;
+use crate::security::{AuthRequirements, IoCapability, Security};
+use crate::stack::StackConfig;
+use crate::status::{self, BleStatus, Result};
+use st_wba_ble_sys::ffi;
+
+/// GAP roles passed to `aci_gap_init`; combine with `|`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Roles(u8);
+
+impl Roles {
+    pub const PERIPHERAL: Roles = Roles(0x01);
+    pub const BROADCASTER: Roles = Roles(0x02);
+    pub const CENTRAL: Roles = Roles(0x04);
+    pub const OBSERVER: Roles = Roles(0x08);
+
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+}
+
+impl core::ops::BitOr for Roles {
+    type Output = Roles;
+    fn bitor(self, rhs: Roles) -> Roles {
+        Roles(self.0 | rhs.0)
+    }
+}
+
+/// Default ATT MTU (the minimum allowed by the spec).
+pub const DEFAULT_ATT_MTU: u16 = 23;
+
+/// Attribute records the stack adds for the GAP and GATT services
+/// (ST: "9 plus the number of user attributes").
+const BUILTIN_ATTRIBUTE_RECORDS: u16 = 9;
+
+/// Configuration for [`BleConfig::init`].
+///
+/// ```ignore
+/// let ble = BleConfig::new()
+///     .roles(Roles::PERIPHERAL | Roles::CENTRAL)
+///     .device_name("RustWBA", 16)
+///     .appearance(0x03C1) // keyboard
+///     .io_capability(IoCapability::DisplayYesNo)
+///     .bonding(AuthRequirements::authenticated())
+///     .init()?;
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct BleConfig<'a> {
+    roles: Roles,
+    privacy: Privacy,
+    device_name: &'a str,
+    device_name_max_len: u8,
+    appearance: Option<u16>,
+    tx_power: Option<u8>,
+    io_capability: Option<IoCapability>,
+    auth: Option<AuthRequirements>,
+    mtu: u16,
+    attribute_table_size: u16,
+}
+
+impl Default for BleConfig<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> BleConfig<'a> {
+    /// Peripheral role, no privacy, no name, stack defaults for everything else.
+    pub const fn new() -> Self {
+        Self {
+            roles: Roles::PERIPHERAL,
+            privacy: Privacy::Disabled,
+            device_name: "",
+            device_name_max_len: 0,
+            appearance: None,
+            tx_power: None,
+            io_capability: None,
+            auth: None,
+            mtu: DEFAULT_ATT_MTU,
+            attribute_table_size: 0,
+        }
+    }
+
+    pub const fn roles(mut self, roles: Roles) -> Self {
+        self.roles = roles;
+        self
+    }
+
+    pub const fn privacy(mut self, privacy: Privacy) -> Self {
+        self.privacy = privacy;
+        self
+    }
+
+    /// Device Name characteristic value; `max_len` reserves room for renaming
+    /// later (the larger of `max_len` and `name.len()` is used).
+    pub const fn device_name(mut self, name: &'a str, max_len: u8) -> Self {
+        self.device_name = name;
+        self.device_name_max_len = max_len;
+        self
+    }
+
+    /// Appearance characteristic value (Bluetooth Assigned Numbers, e.g. 0x03C1 = keyboard).
+    pub const fn appearance(mut self, appearance: u16) -> Self {
+        self.appearance = Some(appearance);
+        self
+    }
+
+    /// Radio output level as a PA level index for `aci_hal_set_tx_power_level`.
+    pub const fn tx_power(mut self, pa_level: u8) -> Self {
+        self.tx_power = Some(pa_level);
+        self
+    }
+
+    pub const fn io_capability(mut self, cap: IoCapability) -> Self {
+        self.io_capability = Some(cap);
+        self
+    }
+
+    /// Authentication/bonding requirements applied right after GAP init.
+    pub const fn bonding(mut self, auth: AuthRequirements) -> Self {
+        self.auth = Some(auth);
+        self
+    }
+
+    /// Largest ATT MTU the application wants to negotiate.
+    ///
+    /// The stack's MTU is fixed by [`StackConfig::init`], so this only sizes
+    /// it through [`BleConfig::stack_config`]; [`BleConfig::init`] rejects a
+    /// value above the MTU the stack was started with.
+    pub const fn mtu(mut self, mtu: u16) -> Self {
+        self.mtu = mtu;
+        self
+    }
+
+    /// Number of GATT attribute records reserved for application services.
+    ///
+    /// The records the stack adds itself for the GAP and GATT services come
+    /// on top. Like [`BleConfig::mtu`], this feeds [`BleConfig::stack_config`]
+    /// and is checked by [`BleConfig::init`] against the started stack.
+    pub const fn attribute_table_size(mut self, records: u16) -> Self {
+        self.attribute_table_size = records;
+        self
+    }
+
+    /// Stack capacity derived from this configuration: the requested MTU and,
+    /// when set, the attribute table size; everything else from [`StackConfig::new`].
+    pub const fn stack_config(&self) -> StackConfig {
+        let cfg = StackConfig::new().att_mtu(self.mtu);
+        if self.attribute_table_size == 0 {
+            cfg
+        } else {
+            cfg.attributes(
+                self.attribute_table_size
+                    .saturating_add(BUILTIN_ATTRIBUTE_RECORDS),
+            )
+        }
+    }
+
+    /// Initialise GATT and GAP, set TX power, name, appearance and security.
+    ///
+    /// Fails with `INVALID_PARAMS` if the MTU or attribute table size
+    /// exceed what the stack was started with by [`StackConfig::init`].
+    pub fn init(self) -> Result<Ble> {
+        let name_len = core::cmp::max(self.device_name.len(), self.device_name_max_len as usize);
+        if name_len > u8::MAX as usize || self.mtu < DEFAULT_ATT_MTU {
+            return Err(BleStatus::Other(status::INVALID_PARAMS));
+        }
+        if let Some(stack) = crate::stack::active()
+            && (self.mtu > stack.att_mtu
+                || self
+                    .attribute_table_size
+                    .saturating_add(BUILTIN_ATTRIBUTE_RECORDS)
+                    > stack.num_gatt_attributes)
+        {
+            return Err(BleStatus::Other(status::INVALID_PARAMS));
+        }
+
+        if let Some(pa_level) = self.tx_power {
+            let rc = unsafe { ffi::aci_hal_set_tx_power_level(0, pa_level) } as i32;
+            if BleStatus::from(rc) != BleStatus::Ok {
+                return Err(BleStatus::from(rc));
+            }
+        }
+
+        let rc = unsafe { ffi::aci_gatt_init() } as i32;
+        if BleStatus::from(rc) != BleStatus::Ok {
+            return Err(BleStatus::from(rc));
+        }
+
+        let (mut svc, mut name_h, mut app_h) = (0u16, 0u16, 0u16);
+        let rc = unsafe {
+            ffi::aci_gap_init(
+                self.roles.bits(),
+                self.privacy as u8,
+                name_len as u8,
+                &mut svc,
+                &mut name_h,
+                &mut app_h,
+            )
+        } as i32;
+        if BleStatus::from(rc) != BleStatus::Ok {
+            return Err(BleStatus::from(rc));
+        }
+
+        if !self.device_name.is_empty() {
+            let rc = unsafe {
+                ffi::aci_gatt_update_char_value(
+                    svc,
+                    name_h,
+                    0,
+                    self.device_name.len() as u8,
+                    self.device_name.as_ptr(),
+                )
+            } as i32;
+            if BleStatus::from(rc) != BleStatus::Ok {
+                return Err(BleStatus::from(rc));
+            }
+        }
+
+        if let Some(appearance) = self.appearance {
+            let val = appearance.to_le_bytes();
+            let rc =
+                unsafe { ffi::aci_gatt_update_char_value(svc, app_h, 0, 2, val.as_ptr()) } as i32;
+            if BleStatus::from(rc) != BleStatus::Ok {
+                return Err(BleStatus::from(rc));
+            }
+        }
+
+        if let Some(cap) = self.io_capability {
+            Security::set_io_capability(cap)?;
+        }
+        if let Some(auth) = &self.auth {
+            Security::set_auth_requirements(auth)?;
+        }
+
+        Ok(Ble {
+            gap_service: svc,
+            device_name_handle: name_h,
+            appearance_handle: app_h,
+            device_name_max_len: name_len as u8,
+            mtu: self.mtu,
+            attribute_table_size: self.attribute_table_size,
+        })
+    }
+}
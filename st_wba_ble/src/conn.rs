@@ -0,0 +1,519 @@
+// st_wba_ble/src/conn.rs
+//! Connection tracking and connection-parameter negotiation.
+//!
+//! The stack reports link events through C callbacks (`hci_le_*_event`,
+//! `aci_l2cap_*_event`); this module implements them, keeps a small table of
+//! live connections and hands out [`Connection`] values to the application.
+
+use crate::addr::{Address, OwnAddress};
+use crate::status::{self, BleStatus, Result};
+use core::cell::{Cell, RefCell};
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_sync::signal::Signal;
+use st_wba_ble_sys::ffi;
+
+/// Maximum number of simultaneous links tracked by this crate.
+pub const MAX_CONNECTIONS: usize = 4;
+
+/// HCI reason used when rejecting a parameter request (Unacceptable Connection Parameters).
+const REASON_UNACCEPTABLE_PARAMS: u8 = 0x3B;
+
+/// Local role on a link.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Role {
+    Central,
+    Peripheral,
+}
+
+/// Connection parameters currently in use on a link.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ConnParams {
+    /// Connection interval, in units of 1.25 ms.
+    pub interval: u16,
+    /// Peripheral latency, in connection events.
+    pub latency: u16,
+    /// Supervision timeout, in units of 10 ms.
+    pub supervision_timeout: u16,
+}
+
+/// Parameters proposed by the remote device, passed to the [`ParamPolicy`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ParamRequest {
+    pub handle: u16,
+    /// Minimum connection interval, in units of 1.25 ms.
+    pub interval_min: u16,
+    /// Maximum connection interval, in units of 1.25 ms.
+    pub interval_max: u16,
+    pub latency: u16,
+    /// Supervision timeout, in units of 10 ms.
+    pub timeout: u16,
+}
+
+/// Application policy deciding whether a remote parameter request is accepted.
+pub type ParamPolicy = fn(&ParamRequest) -> bool;
+
+fn accept_all(_req: &ParamRequest) -> bool {
+    true
+}
+
+#[derive(Copy, Clone)]
+struct Slot {
+    handle: u16,
+    role: Role,
+    params: ConnParams,
+}
+
+static LINKS: Mutex<CriticalSectionRawMutex, RefCell<[Option<Slot>; MAX_CONNECTIONS]>> =
+    Mutex::new(RefCell::new([None; MAX_CONNECTIONS]));
+static POLICY: Mutex<CriticalSectionRawMutex, Cell<ParamPolicy>> =
+    Mutex::new(Cell::new(accept_all));
+static CONNECTED: Channel<CriticalSectionRawMutex, Connection, MAX_CONNECTIONS> = Channel::new();
+static UPDATED: [Signal<CriticalSectionRawMutex, Result<ConnParams>>; MAX_CONNECTIONS] =
+    [const { Signal::new() }; MAX_CONNECTIONS];
+
+fn slot_index(handle: u16) -> Option<usize> {
+    LINKS.lock(|l| {
+        l.borrow()
+            .iter()
+            .position(|s| s.is_some_and(|s| s.handle == handle))
+    })
+}
+
+/// An established link, identified by its HCI connection handle.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Connection {
+    handle: u16,
+    role: Role,
+}
+
+impl Connection {
+    /// Look up a live connection by handle.
+    pub fn from_handle(handle: u16) -> Option<Self> {
+        LINKS.lock(|l| {
+            l.borrow()
+                .iter()
+                .flatten()
+                .find(|s| s.handle == handle)
+                .map(|s| Connection {
+                    handle: s.handle,
+                    role: s.role,
+                })
+        })
+    }
+
+    pub fn handle(&self) -> u16 {
+        self.handle
+    }
+
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    /// Whether the link is still up.
+    pub fn is_connected(&self) -> bool {
+        slot_index(self.handle).is_some()
+    }
+
+    /// Parameters last reported by the controller for this link.
+    pub fn params(&self) -> Option<ConnParams> {
+        LINKS.lock(|l| {
+            l.borrow()
+                .iter()
+                .flatten()
+                .find(|s| s.handle == self.handle)
+                .map(|s| s.params)
+        })
+    }
+
+    /// Ask for new connection parameters.
+    ///
+    /// As Peripheral this sends an L2CAP Connection Parameter Update Request;
+    /// as Central it starts the LL connection update procedure directly.
+    /// Intervals are in units of 1.25 ms, `timeout` in units of 10 ms.
+    /// The outcome is reported through [`Connection::params_updated`].
+    pub fn request_params(
+        &self,
+        interval_min: u16,
+        interval_max: u16,
+        latency: u16,
+        timeout: u16,
+    ) -> Result<()> {
+        let Some(idx) = slot_index(self.handle) else {
+            return Err(BleStatus::Other(status::UNKNOWN_CONNECTION));
+        };
+        UPDATED[idx].reset();
+
+        let rc = unsafe {
+            match self.role {
+                Role::Peripheral => ffi::aci_l2cap_connection_parameter_update_req(
+                    self.handle,
+                    interval_min,
+                    interval_max,
+                    latency,
+                    timeout,
+                ),
+                Role::Central => ffi::aci_gap_start_connection_update(
+                    self.handle,
+                    interval_min,
+                    interval_max,
+                    latency,
+                    timeout,
+                    0, // Minimum_CE_Length
+                    0, // Maximum_CE_Length
+                ),
+            }
+        } as i32;
+        if BleStatus::from(rc) == BleStatus::Ok {
+            Ok(())
+        } else {
+            Err(BleStatus::from(rc))
+        }
+    }
+
+    /// Wait for the next connection-update-complete on this link.
+    ///
+    /// Resolves with the parameters now in use, or with an error if the
+    /// update was rejected or the link dropped.
+    pub async fn params_updated(&self) -> Result<ConnParams> {
+        let Some(idx) = slot_index(self.handle) else {
+            return Err(BleStatus::Other(status::UNKNOWN_CONNECTION));
+        };
+        UPDATED[idx].wait().await
+    }
+}
+
+/// Wait for the next incoming or outgoing connection.
+pub async fn accept() -> Connection {
+    CONNECTED.receive().await
+}
+
+/// Parameters for initiating a connection with [`connect`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ConnectParams {
+    /// Scan interval while initiating, in units of 0.625 ms.
+    pub scan_interval: u16,
+    /// Scan window while initiating, in units of 0.625 ms.
+    pub scan_window: u16,
+    /// Address to initiate with; use a `Resolvable*` variant when privacy is enabled.
+    pub own_address: OwnAddress,
+    /// Connection interval bounds, in units of 1.25 ms.
+    pub interval_min: u16,
+    pub interval_max: u16,
+    pub latency: u16,
+    /// Supervision timeout, in units of 10 ms.
+    pub supervision_timeout: u16,
+    /// Connect to any device in the Filter Accept List instead of the given peer.
+    pub use_accept_list: bool,
+}
+
+impl Default for ConnectParams {
+    /// 60 ms scan interval/window, 30–50 ms connection interval, 4 s timeout.
+    fn default() -> Self {
+        Self {
+            scan_interval: 0x0060,
+            scan_window: 0x0060,
+            own_address: OwnAddress::Public,
+            interval_min: 0x0018,
+            interval_max: 0x0028,
+            latency: 0,
+            supervision_timeout: 400,
+            use_accept_list: false,
+        }
+    }
+}
+
+/// Initiate a connection to `peer` as Central.
+/// `peer` is ignored when [`ConnectParams::use_accept_list`] is set.
+/// The new link is delivered through [`accept`].
+pub fn connect(peer: &Address, params: &ConnectParams) -> Result<()> {
+    let rc = unsafe {
+        ffi::hci_le_create_connection(
+            params.scan_interval,
+            params.scan_window,
+            params.use_accept_list as u8, // Initiator_Filter_Policy
+            peer.kind as u8,
+            peer.bytes.as_ptr(),
+            params.own_address as u8,
+            params.interval_min,
+            params.interval_max,
+            params.latency,
+            params.supervision_timeout,
+            0, // Minimum_CE_Length
+            0, // Maximum_CE_Length
+        )
+    } as i32;
+    if BleStatus::from(rc) == BleStatus::Ok {
+        Ok(())
+    } else {
+        Err(BleStatus::from(rc))
+    }
+}
+
+/// Install the policy used to answer remote parameter requests (default: accept all).
+pub fn set_param_policy(policy: ParamPolicy) {
+    POLICY.lock(|p| p.set(policy));
+}
+
+fn on_connected(status: u8, handle: u16, role: u8, params: ConnParams) {
+    if status != 0 {
+        return;
+    }
+    let role = if role == 0x00 {
+        Role::Central
+    } else {
+        Role::Peripheral
+    };
+    let stored = LINKS.lock(|l| {
+        let mut l = l.borrow_mut();
+        match l.iter().position(|s| s.is_none()) {
+            Some(i) => {
+                l[i] = Some(Slot {
+                    handle,
+                    role,
+                    params,
+                });
+                UPDATED[i].reset();
+                true
+            }
+            None => false,
+        }
+    });
+    if stored {
+        let _ = CONNECTED.try_send(Connection { handle, role });
+    }
+}
+
+// ===== Disconnection listeners =====
+/// A link went down (`hci_disconnection_complete_event`).
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Disconnection {
+    pub handle: u16,
+    /// HCI reason code.
+    pub reason: u8,
+}
+
+/// Called from the stack's event context after a link and its channels and
+/// GATT procedures have been torn down; drop per-link state here.
+pub type DisconnectListener = fn(&Disconnection);
+
+/// Disconnection listeners that can be registered.
+pub const MAX_DISCONNECT_LISTENERS: usize = 8;
+
+static DISCONNECT_LISTENERS: Mutex<
+    CriticalSectionRawMutex,
+    RefCell<[Option<DisconnectListener>; MAX_DISCONNECT_LISTENERS]>,
+> = Mutex::new(RefCell::new([None; MAX_DISCONNECT_LISTENERS]));
+
+/// Register `f` for every disconnection.
+pub fn add_disconnect_listener(f: DisconnectListener) -> Result<()> {
+    DISCONNECT_LISTENERS.lock(|l| {
+        let mut l = l.borrow_mut();
+        if l.iter().flatten().any(|g| core::ptr::fn_addr_eq(*g, f)) {
+            return Ok(());
+        }
+        let slot = l
+            .iter_mut()
+            .find(|s| s.is_none())
+            .ok_or(BleStatus::Other(status::INSUFFICIENT_RESOURCES))?;
+        *slot = Some(f);
+        Ok(())
+    })
+}
+
+// ===== Stack event callbacks =====
+
+#[unsafe(no_mangle)]
+#[allow(clippy::too_many_arguments)]
+pub extern "C" fn hci_le_connection_complete_event(
+    status: u8,
+    connection_handle: u16,
+    role: u8,
+    _peer_address_type: u8,
+    _peer_address: *const u8,
+    conn_interval: u16,
+    conn_latency: u16,
+    supervision_timeout: u16,
+    _central_clock_accuracy: u8,
+) {
+    on_connected(
+        status,
+        connection_handle,
+        role,
+        ConnParams {
+            interval: conn_interval,
+            latency: conn_latency,
+            supervision_timeout,
+        },
+    );
+}
+
+#[unsafe(no_mangle)]
+#[allow(clippy::too_many_arguments)]
+pub extern "C" fn hci_le_enhanced_connection_complete_event(
+    status: u8,
+    connection_handle: u16,
+    role: u8,
+    _peer_address_type: u8,
+    _peer_address: *const u8,
+    _local_resolvable_private_address: *const u8,
+    _peer_resolvable_private_address: *const u8,
+    conn_interval: u16,
+    conn_latency: u16,
+    supervision_timeout: u16,
+    _central_clock_accuracy: u8,
+) {
+    on_connected(
+        status,
+        connection_handle,
+        role,
+        ConnParams {
+            interval: conn_interval,
+            latency: conn_latency,
+            supervision_timeout,
+        },
+    );
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn hci_disconnection_complete_event(status: u8, connection_handle: u16, reason: u8) {
+    if status != 0 {
+        return;
+    }
+    let idx = LINKS.lock(|l| {
+        let mut l = l.borrow_mut();
+        let i = l
+            .iter()
+            .position(|s| s.is_some_and(|s| s.handle == connection_handle));
+        if let Some(i) = i {
+            l[i] = None;
+        }
+        i
+    });
+    if let Some(i) = idx {
+        UPDATED[i].signal(Err(BleStatus::from(reason as i32)));
+    }
+    crate::l2cap::on_link_closed(connection_handle);
+    crate::gatt::on_link_closed(connection_handle);
+    let closed = Disconnection {
+        handle: connection_handle,
+        reason,
+    };
+    // Copy the table out so listeners may register others.
+    let listeners = DISCONNECT_LISTENERS.lock(|l| *l.borrow());
+    for f in listeners.into_iter().flatten() {
+        f(&closed);
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn hci_le_connection_update_complete_event(
+    status: u8,
+    connection_handle: u16,
+    conn_interval: u16,
+    conn_latency: u16,
+    supervision_timeout: u16,
+) {
+    let Some(idx) = slot_index(connection_handle) else {
+        return;
+    };
+    if status != 0 {
+        UPDATED[idx].signal(Err(BleStatus::from(status as i32)));
+        return;
+    }
+    let params = ConnParams {
+        interval: conn_interval,
+        latency: conn_latency,
+        supervision_timeout,
+    };
+    LINKS.lock(|l| {
+        if let Some(s) = l.borrow_mut()[idx].as_mut() {
+            s.params = params;
+        }
+    });
+    UPDATED[idx].signal(Ok(params));
+}
+
+/// LL Connection Parameters Request from the peer (either role).
+#[unsafe(no_mangle)]
+pub extern "C" fn hci_le_remote_connection_parameter_request_event(
+    connection_handle: u16,
+    interval_min: u16,
+    interval_max: u16,
+    max_latency: u16,
+    timeout: u16,
+) {
+    let req = ParamRequest {
+        handle: connection_handle,
+        interval_min,
+        interval_max,
+        latency: max_latency,
+        timeout,
+    };
+    let accept = POLICY.lock(|p| p.get())(&req);
+    unsafe {
+        if accept {
+            ffi::hci_le_remote_connection_parameter_request_reply(
+                connection_handle,
+                interval_min,
+                interval_max,
+                max_latency,
+                timeout,
+                0, // Min_CE_Length
+                0, // Max_CE_Length
+            );
+        } else {
+            ffi::hci_le_remote_connection_parameter_request_negative_reply(
+                connection_handle,
+                REASON_UNACCEPTABLE_PARAMS,
+            );
+        }
+    }
+}
+
+/// L2CAP Connection Parameter Update Request received while Central.
+#[unsafe(no_mangle)]
+pub extern "C" fn aci_l2cap_connection_update_req_event(
+    connection_handle: u16,
+    identifier: u8,
+    _l2cap_length: u16,
+    interval_min: u16,
+    interval_max: u16,
+    latency: u16,
+    timeout_multiplier: u16,
+) {
+    let req = ParamRequest {
+        handle: connection_handle,
+        interval_min,
+        interval_max,
+        latency,
+        timeout: timeout_multiplier,
+    };
+    let accept = POLICY.lock(|p| p.get())(&req);
+    unsafe {
+        ffi::aci_l2cap_connection_parameter_update_resp(
+            connection_handle,
+            interval_min,
+            interval_max,
+            latency,
+            timeout_multiplier,
+            0, // Minimum_CE_Length
+            0, // Maximum_CE_Length
+            identifier,
+            accept as u8,
+        );
+    }
+}
+
+/// Central's answer to our L2CAP update request (Peripheral side).
+/// On acceptance the controller follows up with a connection-update-complete.
+#[unsafe(no_mangle)]
+pub extern "C" fn aci_l2cap_connection_update_resp_event(connection_handle: u16, result: u16) {
+    if result == 0 {
+        return;
+    }
+    if let Some(idx) = slot_index(connection_handle) {
+        UPDATED[idx].signal(Err(BleStatus::from(result as i32)));
+    }
+}
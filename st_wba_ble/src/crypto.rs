@@ -0,0 +1,715 @@
+// st_wba_ble/src/crypto.rs
+//! AES-128 and P-256 for the host stack (enabled with the `rust-crypto` feature).
+//!
+//! Backs `BLEPLAT_AesEcbEncrypt`, `BLEPLAT_AesCmac*` and `BLEPLAT_Pka*` in
+//! [`crate::platform`], replacing ST's `hw_aes.c`, `hw_pka.c`, `pka_p256.c`
+//! and the `baes`/`bpka` modules. The default provider drives the AES and
+//! PKA peripherals ([`Stm32WbaCrypto`]); with `soft-crypto` a pure-software
+//! [`SoftCrypto`] is available for host testing.
+//!
+//! P-256 operations are slow, so the stack's start requests are queued and
+//! computed by [`crate::StackRunner`], which then signals completion with
+//! `BLEPLATCB_PkaComplete`.
+
+use core::cell::{Cell, RefCell};
+use core::sync::atomic::{AtomicBool, Ordering};
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+
+/// A crypto operation was rejected.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CryptoError {
+    /// Private key out of range or public key not on the curve.
+    InvalidKey,
+    /// The peripheral reported an error.
+    Hardware,
+    /// The provider is already running an operation.
+    Busy,
+    /// The peripheral did not become ready in time.
+    Timeout,
+}
+
+/// Cryptographic primitives needed by the stack. All values are big-endian
+/// byte strings (FIPS-197 / SEC1 order); public keys are `X || Y`.
+///
+/// AES may be requested while a P-256 operation is running, so
+/// implementations serialise their own state (the methods take `&self`).
+pub trait CryptoProvider: Sync {
+    /// Encrypt one block with AES-128.
+    fn aes_ecb_encrypt(&self, key: &[u8; 16], block: &[u8; 16]) -> [u8; 16];
+
+    /// Public key for `private` (scalar multiplication of the generator).
+    fn p256_public_key(&self, private: &[u8; 32]) -> Result<[u8; 64], CryptoError>;
+
+    /// X coordinate of `private * remote_public`; must reject points off the curve.
+    fn p256_dh_key(
+        &self,
+        private: &[u8; 32],
+        remote_public: &[u8; 64],
+    ) -> Result<[u8; 32], CryptoError>;
+}
+
+static HARDWARE: Stm32WbaCrypto = Stm32WbaCrypto::new();
+static PROVIDER: Mutex<CriticalSectionRawMutex, Cell<Option<&'static dyn CryptoProvider>>> =
+    Mutex::new(Cell::new(None));
+
+/// Use `provider` instead of the AES/PKA peripherals; call before the stack starts.
+pub fn set_provider(provider: &'static dyn CryptoProvider) {
+    PROVIDER.lock(|p| p.set(Some(provider)));
+}
+
+/// The installed provider. It is not locked while in use, so slow P-256
+/// work runs with interrupts enabled.
+fn provider() -> &'static dyn CryptoProvider {
+    PROVIDER.lock(|p| p.get()).unwrap_or(&HARDWARE)
+}
+
+// ===== AES / CMAC =====
+
+static CMAC_KEY: Mutex<CriticalSectionRawMutex, Cell<[u8; 16]>> = Mutex::new(Cell::new([0; 16]));
+
+pub(crate) fn aes_ecb_encrypt(key: &[u8; 16], block: &[u8; 16]) -> [u8; 16] {
+    provider().aes_ecb_encrypt(key, block)
+}
+
+pub(crate) fn cmac_set_key(key: &[u8; 16]) {
+    CMAC_KEY.lock(|k| k.set(*key));
+}
+
+/// AES-CMAC (RFC 4493) with the key from [`cmac_set_key`].
+pub(crate) fn cmac_compute(msg: &[u8]) -> [u8; 16] {
+    let key = CMAC_KEY.lock(|k| k.get());
+    cmac(&key, msg)
+}
+
+/// AES-CMAC (RFC 4493) on top of the provider's block cipher.
+pub fn cmac(key: &[u8; 16], msg: &[u8]) -> [u8; 16] {
+    fn dbl(b: [u8; 16]) -> [u8; 16] {
+        let v = u128::from_be_bytes(b);
+        let r = (v << 1) ^ if v >> 127 == 1 { 0x87 } else { 0 };
+        r.to_be_bytes()
+    }
+
+    let k1 = dbl(aes_ecb_encrypt(key, &[0; 16]));
+    let k2 = dbl(k1);
+    let blocks = msg.len().div_ceil(16).max(1);
+    let complete = !msg.is_empty() && msg.len().is_multiple_of(16);
+
+    let mut x = [0u8; 16];
+    for chunk in msg.chunks(16).take(blocks - 1) {
+        for (xi, ci) in x.iter_mut().zip(chunk) {
+            *xi ^= ci;
+        }
+        x = aes_ecb_encrypt(key, &x);
+    }
+
+    let tail = &msg[(blocks - 1) * 16..];
+    let mut last = [0u8; 16];
+    last[..tail.len()].copy_from_slice(tail);
+    let sub = if complete {
+        k1
+    } else {
+        last[tail.len()] = 0x80;
+        k2
+    };
+    for i in 0..16 {
+        x[i] ^= last[i] ^ sub[i];
+    }
+    aes_ecb_encrypt(key, &x)
+}
+
+// ===== Deferred P-256 (BLEPLAT_Pka*) =====
+
+#[derive(Copy, Clone)]
+enum PkaRequest {
+    PublicKey([u8; 32]),
+    DhKey([u8; 32], [u8; 64]),
+}
+
+struct PkaState {
+    request: Option<PkaRequest>,
+    busy: bool,
+    public: Result<[u8; 64], CryptoError>,
+    dh: Result<[u8; 32], CryptoError>,
+}
+
+static PKA: Mutex<CriticalSectionRawMutex, RefCell<PkaState>> =
+    Mutex::new(RefCell::new(PkaState {
+        request: None,
+        busy: false,
+        public: Err(CryptoError::InvalidKey),
+        dh: Err(CryptoError::InvalidKey),
+    }));
+
+/// Queue a P-256 operation; false if one is still running.
+fn start(req: PkaRequest) -> bool {
+    let queued = PKA.lock(|s| {
+        let mut s = s.borrow_mut();
+        if s.busy {
+            return false;
+        }
+        s.busy = true;
+        s.request = Some(req);
+        true
+    });
+    if queued {
+        crate::runner::schedule();
+    }
+    queued
+}
+
+pub(crate) fn start_p256_key(private_le: &[u32; 8]) -> Result<bool, CryptoError> {
+    if !scalar_in_range(private_le) {
+        return Err(CryptoError::InvalidKey);
+    }
+    Ok(start(PkaRequest::PublicKey(le_words_to_be(private_le))))
+}
+
+pub(crate) fn start_dh_key(
+    private_le: &[u32; 8],
+    remote_le: &[u32; 16],
+) -> Result<bool, CryptoError> {
+    if !scalar_in_range(private_le) {
+        return Err(CryptoError::InvalidKey);
+    }
+    Ok(start(PkaRequest::DhKey(
+        le_words_to_be(private_le),
+        point_le_words_to_be(remote_le),
+    )))
+}
+
+pub(crate) fn read_p256_key(out_le: &mut [u32; 16]) -> Result<(), CryptoError> {
+    let public = PKA.lock(|s| s.borrow().public)?;
+    *out_le = point_be_to_le_words(&public);
+    Ok(())
+}
+
+pub(crate) fn read_dh_key(out_le: &mut [u32; 8]) -> Result<(), CryptoError> {
+    let dh = PKA.lock(|s| s.borrow().dh)?;
+    *out_le = be_to_le_words(&dh);
+    Ok(())
+}
+
+/// Run a queued P-256 operation; true when one completed.
+pub(crate) fn process() -> bool {
+    let Some(req) = PKA.lock(|s| s.borrow_mut().request.take()) else {
+        return false;
+    };
+    match req {
+        PkaRequest::PublicKey(private) => {
+            let public = provider().p256_public_key(&private);
+            PKA.lock(|s| {
+                let mut s = s.borrow_mut();
+                s.public = public;
+                s.busy = false;
+            });
+        }
+        PkaRequest::DhKey(private, remote) => {
+            let dh = provider().p256_dh_key(&private, &remote);
+            PKA.lock(|s| {
+                let mut s = s.borrow_mut();
+                s.dh = dh;
+                s.busy = false;
+            });
+        }
+    }
+    true
+}
+
+// The stack passes P-256 values as little-endian 32-bit words.
+
+fn le_words_to_be(w: &[u32; 8]) -> [u8; 32] {
+    let mut b = [0u8; 32];
+    for (i, word) in w.iter().enumerate() {
+        b[28 - 4 * i..32 - 4 * i].copy_from_slice(&word.to_be_bytes());
+    }
+    b
+}
+
+fn be_to_le_words(b: &[u8; 32]) -> [u32; 8] {
+    let mut w = [0u32; 8];
+    for (i, word) in w.iter_mut().enumerate() {
+        let mut be = [0u8; 4];
+        be.copy_from_slice(&b[28 - 4 * i..32 - 4 * i]);
+        *word = u32::from_be_bytes(be);
+    }
+    w
+}
+
+fn point_le_words_to_be(w: &[u32; 16]) -> [u8; 64] {
+    let (x, y) = w.split_at(8);
+    let mut b = [0u8; 64];
+    b[..32].copy_from_slice(&le_words_to_be(x.try_into().unwrap()));
+    b[32..].copy_from_slice(&le_words_to_be(y.try_into().unwrap()));
+    b
+}
+
+fn point_be_to_le_words(b: &[u8; 64]) -> [u32; 16] {
+    let (x, y) = b.split_at(32);
+    let mut w = [0u32; 16];
+    w[..8].copy_from_slice(&be_to_le_words(x.try_into().unwrap()));
+    w[8..].copy_from_slice(&be_to_le_words(y.try_into().unwrap()));
+    w
+}
+
+// ===== STM32WBA AES / PKA peripherals (RM0493) =====
+
+mod regs {
+    pub const AES_BASE: usize = 0x420C_0000;
+    pub const AES_CR: usize = AES_BASE;
+    pub const AES_SR: usize = AES_BASE + 0x04;
+    pub const AES_DINR: usize = AES_BASE + 0x08;
+    pub const AES_DOUTR: usize = AES_BASE + 0x0C;
+    pub const AES_KEYR0: usize = AES_BASE + 0x10;
+    pub const AES_ICR: usize = AES_BASE + 0x308;
+    pub const AES_CR_EN: u32 = 1 << 0;
+    pub const AES_SR_CCF: u32 = 1 << 0;
+    pub const AES_ICR_CCF: u32 = 1 << 0;
+
+    pub const PKA_BASE: usize = 0x420C_2000;
+    pub const PKA_CR: usize = PKA_BASE;
+    pub const PKA_SR: usize = PKA_BASE + 0x04;
+    pub const PKA_CLRFR: usize = PKA_BASE + 0x08;
+    pub const PKA_CR_EN: u32 = 1 << 0;
+    pub const PKA_CR_START: u32 = 1 << 1;
+    pub const PKA_CR_MODE_SHIFT: u32 = 8;
+    pub const PKA_SR_INITOK: u32 = 1 << 0;
+    pub const PKA_SR_BUSY: u32 = 1 << 16;
+    pub const PKA_SR_PROCENDF: u32 = 1 << 17;
+    pub const PKA_SR_ERRORS: u32 = 0b111 << 19; // RAMERRF | ADDRERRF | OPERRF
+    pub const PKA_CLRFR_ALL: u32 = 0b1111 << 17;
+    pub const PKA_MODE_ECC_MUL: u32 = 0x20;
+
+    // ECC scalar multiplication operands (byte offsets from PKA_BASE).
+    pub const ECC_IN_EXP_NB_BITS: usize = 0x400;
+    pub const ECC_IN_OP_NB_BITS: usize = 0x408;
+    pub const ECC_IN_A_COEFF_SIGN: usize = 0x410;
+    pub const ECC_IN_A_COEFF: usize = 0x418;
+    pub const ECC_IN_MOD_GF: usize = 0x470;
+    pub const ECC_IN_B_COEFF: usize = 0x520;
+    pub const ECC_IN_POINT_X: usize = 0x578;
+    pub const ECC_IN_POINT_Y: usize = 0x5D0;
+    pub const ECC_IN_N_ORDER: usize = 0xE94;
+    pub const ECC_IN_K: usize = 0x12A0;
+    pub const ECC_OUT_X: usize = 0x578;
+    pub const ECC_OUT_Y: usize = 0x5D0;
+    pub const ECC_OUT_ERROR: usize = 0x680;
+    pub const ECC_NO_ERROR: u32 = 0xD60D;
+
+    pub const RCC_AHB2ENR: usize = 0x4602_0C00 + 0x08C;
+    pub const RCC_AHB2ENR_AESEN: u32 = 1 << 16;
+    /// The PKA runs from the RNG clock (`HW_RNG_EnableClock` in ST's `hw_pka.c`).
+    pub const RCC_AHB2ENR_RNGEN: u32 = 1 << 18;
+    pub const RCC_AHB2ENR_PKAEN: u32 = 1 << 19;
+
+    #[inline]
+    pub fn read(addr: usize) -> u32 {
+        unsafe { core::ptr::read_volatile(addr as *const u32) }
+    }
+
+    #[inline]
+    pub fn write(addr: usize, v: u32) {
+        unsafe { core::ptr::write_volatile(addr as *mut u32, v) }
+    }
+}
+
+// NIST P-256 domain parameters as little-endian words (PKA operand order).
+const P256_P: [u32; 8] = [
+    0xFFFF_FFFF,
+    0xFFFF_FFFF,
+    0xFFFF_FFFF,
+    0x0000_0000,
+    0x0000_0000,
+    0x0000_0000,
+    0x0000_0001,
+    0xFFFF_FFFF,
+];
+const P256_B: [u32; 8] = [
+    0x27D2_604B,
+    0x3BCE_3C3E,
+    0xCC53_B0F6,
+    0x651D_06B0,
+    0x7698_86BC,
+    0xB3EB_BD55,
+    0xAA3A_93E7,
+    0x5AC6_35D8,
+];
+const P256_N: [u32; 8] = [
+    0xFC63_2551,
+    0xF3B9_CAC2,
+    0xA717_9E84,
+    0xBCE6_FAAD,
+    0xFFFF_FFFF,
+    0xFFFF_FFFF,
+    0x0000_0000,
+    0xFFFF_FFFF,
+];
+const P256_GX: [u32; 8] = [
+    0xD898_C296,
+    0xF4A1_3945,
+    0x2DEB_33A0,
+    0x7703_7D81,
+    0x63A4_40F2,
+    0xF8BC_E6E5,
+    0xE12C_4247,
+    0x6B17_D1F2,
+];
+const P256_GY: [u32; 8] = [
+    0x37BF_51F5,
+    0xCBB6_4068,
+    0x6B31_5ECE,
+    0x2BCE_3357,
+    0x7C0F_9E16,
+    0x8EE7_EB4A,
+    0xFE1A_7F9B,
+    0x4FE3_42E2,
+];
+
+/// Polls of `PKA_SR` before giving up on initialisation or the busy flag.
+const PKA_READY_TIMEOUT: u32 = 100_000;
+/// Polls of `PKA_SR` before giving up on a scalar multiplication.
+const PKA_OP_TIMEOUT: u32 = 50_000_000;
+
+/// Driver for the on-chip AES and PKA peripherals (polled). AES blocks run
+/// inside a critical section; the PKA is claimed for a whole operation.
+pub struct Stm32WbaCrypto {
+    pka_busy: AtomicBool,
+}
+
+impl Default for Stm32WbaCrypto {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Stm32WbaCrypto {
+    pub const fn new() -> Self {
+        Stm32WbaCrypto {
+            pka_busy: AtomicBool::new(false),
+        }
+    }
+
+    fn enable_clocks(bits: u32) {
+        let en = regs::read(regs::RCC_AHB2ENR);
+        if en & bits != bits {
+            regs::write(regs::RCC_AHB2ENR, en | bits);
+        }
+    }
+
+    /// Poll `PKA_SR` until `done` holds, at most `polls` times.
+    fn pka_wait(polls: u32, done: impl Fn(u32) -> bool) -> Result<u32, CryptoError> {
+        for _ in 0..polls {
+            let sr = regs::read(regs::PKA_SR);
+            if done(sr) {
+                return Ok(sr);
+            }
+        }
+        Err(CryptoError::Timeout)
+    }
+
+    /// Write an operand followed by the two terminating zero words the PKA
+    /// expects (`__PKA_RAM_PARAM_END`).
+    fn pka_write(offset: usize, words: &[u32]) {
+        for (i, w) in words.iter().enumerate() {
+            regs::write(regs::PKA_BASE + offset + 4 * i, *w);
+        }
+        regs::write(regs::PKA_BASE + offset + 4 * words.len(), 0);
+        regs::write(regs::PKA_BASE + offset + 4 * (words.len() + 1), 0);
+    }
+
+    fn pka_read(offset: usize, words: &mut [u32]) {
+        for (i, w) in words.iter_mut().enumerate() {
+            *w = regs::read(regs::PKA_BASE + offset + 4 * i);
+        }
+    }
+
+    /// `k * (x, y)` on P-256, all values little-endian words. The PKA is
+    /// disabled again on every path.
+    fn ecc_mul(&self, k: &[u32; 8], x: &[u32; 8], y: &[u32; 8]) -> Result<[u32; 16], CryptoError> {
+        if self.pka_busy.swap(true, Ordering::Acquire) {
+            return Err(CryptoError::Busy);
+        }
+        Self::enable_clocks(regs::RCC_AHB2ENR_RNGEN | regs::RCC_AHB2ENR_PKAEN);
+        regs::write(regs::PKA_CR, regs::PKA_CR_EN);
+        let result = Self::ecc_mul_enabled(k, x, y);
+        regs::write(regs::PKA_CR, 0);
+        self.pka_busy.store(false, Ordering::Release);
+        result
+    }
+
+    fn ecc_mul_enabled(k: &[u32; 8], x: &[u32; 8], y: &[u32; 8]) -> Result<[u32; 16], CryptoError> {
+        Self::pka_wait(PKA_READY_TIMEOUT, |sr| sr & regs::PKA_SR_INITOK != 0)?;
+        Self::pka_wait(PKA_READY_TIMEOUT, |sr| sr & regs::PKA_SR_BUSY == 0)?;
+
+        Self::pka_write(regs::ECC_IN_EXP_NB_BITS, &[256]);
+        Self::pka_write(regs::ECC_IN_OP_NB_BITS, &[256]);
+        Self::pka_write(regs::ECC_IN_A_COEFF_SIGN, &[1]); // a = -3
+        Self::pka_write(regs::ECC_IN_A_COEFF, &[3, 0, 0, 0, 0, 0, 0, 0]);
+        Self::pka_write(regs::ECC_IN_B_COEFF, &P256_B);
+        Self::pka_write(regs::ECC_IN_MOD_GF, &P256_P);
+        Self::pka_write(regs::ECC_IN_N_ORDER, &P256_N);
+        Self::pka_write(regs::ECC_IN_K, k);
+        Self::pka_write(regs::ECC_IN_POINT_X, x);
+        Self::pka_write(regs::ECC_IN_POINT_Y, y);
+
+        regs::write(regs::PKA_CLRFR, regs::PKA_CLRFR_ALL);
+        regs::write(
+            regs::PKA_CR,
+            regs::PKA_CR_EN
+                | (regs::PKA_MODE_ECC_MUL << regs::PKA_CR_MODE_SHIFT)
+                | regs::PKA_CR_START,
+        );
+        let sr = Self::pka_wait(PKA_OP_TIMEOUT, |sr| {
+            sr & (regs::PKA_SR_PROCENDF | regs::PKA_SR_ERRORS) != 0
+        });
+        regs::write(regs::PKA_CLRFR, regs::PKA_CLRFR_ALL);
+        let sr = sr?;
+        if sr & regs::PKA_SR_ERRORS != 0 {
+            return Err(CryptoError::Hardware);
+        }
+        // The PKA checks that the input point lies on the curve.
+        if regs::read(regs::PKA_BASE + regs::ECC_OUT_ERROR) != regs::ECC_NO_ERROR {
+            return Err(CryptoError::InvalidKey);
+        }
+        let mut out = [0u32; 16];
+        Self::pka_read(regs::ECC_OUT_X, &mut out[..8]);
+        Self::pka_read(regs::ECC_OUT_Y, &mut out[8..]);
+        Ok(out)
+    }
+
+    /// Encrypt one block on the AES peripheral.
+    fn aes_block(key: &[u8; 16], block: &[u8; 16]) -> [u8; 16] {
+        Self::enable_clocks(regs::RCC_AHB2ENR_AESEN);
+        // ECB encryption, 128-bit key, no data swapping: MODE=0, CHMOD=0, DATATYPE=0.
+        regs::write(regs::AES_CR, 0);
+        for i in 0..4 {
+            // KEYR3 holds key[0..4], KEYR0 holds key[12..16].
+            let w = u32::from_be_bytes(key[12 - 4 * i..16 - 4 * i].try_into().unwrap());
+            regs::write(regs::AES_KEYR0 + 4 * i, w);
+        }
+        regs::write(regs::AES_CR, regs::AES_CR_EN);
+        for chunk in block.chunks(4) {
+            regs::write(
+                regs::AES_DINR,
+                u32::from_be_bytes(chunk.try_into().unwrap()),
+            );
+        }
+        while regs::read(regs::AES_SR) & regs::AES_SR_CCF == 0 {}
+        let mut out = [0u8; 16];
+        for chunk in out.chunks_mut(4) {
+            chunk.copy_from_slice(&regs::read(regs::AES_DOUTR).to_be_bytes());
+        }
+        regs::write(regs::AES_ICR, regs::AES_ICR_CCF);
+        regs::write(regs::AES_CR, 0);
+        out
+    }
+}
+
+/// `0 < k < n`, comparing little-endian words from the top.
+fn scalar_in_range(k: &[u32; 8]) -> bool {
+    if k.iter().all(|w| *w == 0) {
+        return false;
+    }
+    for i in (0..8).rev() {
+        if k[i] != P256_N[i] {
+            return k[i] < P256_N[i];
+        }
+    }
+    false
+}
+
+impl CryptoProvider for Stm32WbaCrypto {
+    fn aes_ecb_encrypt(&self, key: &[u8; 16], block: &[u8; 16]) -> [u8; 16] {
+        // The critical section keeps an interrupt from reprogramming the key
+        // halfway through a block.
+        critical_section::with(|_| Self::aes_block(key, block))
+    }
+
+    fn p256_public_key(&self, private: &[u8; 32]) -> Result<[u8; 64], CryptoError> {
+        let k = be_to_le_words(private);
+        if !scalar_in_range(&k) {
+            return Err(CryptoError::InvalidKey);
+        }
+        let q = self.ecc_mul(&k, &P256_GX, &P256_GY)?;
+        Ok(point_le_words_to_be(&q))
+    }
+
+    fn p256_dh_key(
+        &self,
+        private: &[u8; 32],
+        remote_public: &[u8; 64],
+    ) -> Result<[u8; 32], CryptoError> {
+        let k = be_to_le_words(private);
+        if !scalar_in_range(&k) {
+            return Err(CryptoError::InvalidKey);
+        }
+        let p = point_be_to_le_words(remote_public);
+        let (x, y) = p.split_at(8);
+        let q = self.ecc_mul(&k, x.try_into().unwrap(), y.try_into().unwrap())?;
+        Ok(le_words_to_be(q[..8].try_into().unwrap()))
+    }
+}
+
+/// Pure-software provider (RustCrypto `aes` + `p256`), mainly for host tests.
+#[cfg(feature = "soft-crypto")]
+#[derive(Default)]
+pub struct SoftCrypto;
+
+#[cfg(feature = "soft-crypto")]
+impl CryptoProvider for SoftCrypto {
+    fn aes_ecb_encrypt(&self, key: &[u8; 16], block: &[u8; 16]) -> [u8; 16] {
+        use aes::cipher::{BlockEncrypt, KeyInit};
+        let cipher = aes::Aes128::new(key.into());
+        let mut b = (*block).into();
+        cipher.encrypt_block(&mut b);
+        b.into()
+    }
+
+    fn p256_public_key(&self, private: &[u8; 32]) -> Result<[u8; 64], CryptoError> {
+        use p256::elliptic_curve::sec1::ToEncodedPoint;
+        let sk =
+            p256::SecretKey::from_bytes(private.into()).map_err(|_| CryptoError::InvalidKey)?;
+        let point = sk.public_key().to_encoded_point(false);
+        let mut out = [0u8; 64];
+        out.copy_from_slice(&point.as_bytes()[1..]);
+        Ok(out)
+    }
+
+    fn p256_dh_key(
+        &self,
+        private: &[u8; 32],
+        remote_public: &[u8; 64],
+    ) -> Result<[u8; 32], CryptoError> {
+        use p256::elliptic_curve::sec1::ToEncodedPoint;
+        let sk =
+            p256::SecretKey::from_bytes(private.into()).map_err(|_| CryptoError::InvalidKey)?;
+        let mut sec1 = [0u8; 65];
+        sec1[0] = 0x04;
+        sec1[1..].copy_from_slice(remote_public);
+        // Decoding validates that the point is on the curve.
+        let pk = p256::PublicKey::from_sec1_bytes(&sec1).map_err(|_| CryptoError::InvalidKey)?;
+        let shared = (pk.to_projective() * *sk.to_nonzero_scalar()).to_encoded_point(false);
+        let mut out = [0u8; 32];
+        out.copy_from_slice(shared.x().ok_or(CryptoError::InvalidKey)?);
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex<const N: usize>(s: &str) -> [u8; N] {
+        let mut out = [0u8; N];
+        for (o, i) in out.iter_mut().zip((0..s.len()).step_by(2)) {
+            *o = u8::from_str_radix(&s[i..i + 2], 16).unwrap();
+        }
+        out
+    }
+
+    #[test]
+    fn word_byte_conversions() {
+        let mut words = [0u32; 8];
+        words[0] = 0x0403_0201;
+        words[7] = 0xA0B0_C0D0;
+        let be = le_words_to_be(&words);
+        assert_eq!(be[..4], [0xA0, 0xB0, 0xC0, 0xD0]);
+        assert_eq!(be[28..], [0x04, 0x03, 0x02, 0x01]);
+        assert_eq!(be_to_le_words(&be), words);
+
+        let mut point = [0u32; 16];
+        point[..8].copy_from_slice(&P256_GX);
+        point[8..].copy_from_slice(&P256_GY);
+        let be = point_le_words_to_be(&point);
+        assert_eq!(
+            be[..32],
+            hex::<32>("6B17D1F2E12C4247F8BCE6E563A440F277037D812DEB33A0F4A13945D898C296")
+        );
+        assert_eq!(point_be_to_le_words(&be), point);
+    }
+
+    #[test]
+    fn scalar_range() {
+        let mut k = [0u32; 8];
+        assert!(!scalar_in_range(&k));
+        k[0] = 1;
+        assert!(scalar_in_range(&k));
+        assert!(!scalar_in_range(&P256_N));
+        let mut below = P256_N;
+        below[0] -= 1;
+        assert!(scalar_in_range(&below));
+    }
+
+    #[cfg(feature = "soft-crypto")]
+    static SOFT: SoftCrypto = SoftCrypto;
+
+    #[cfg(feature = "soft-crypto")]
+    #[test]
+    fn aes_fips197_vector() {
+        set_provider(&SOFT);
+        let key = hex::<16>("000102030405060708090a0b0c0d0e0f");
+        let pt = hex::<16>("00112233445566778899aabbccddeeff");
+        assert_eq!(
+            aes_ecb_encrypt(&key, &pt),
+            hex::<16>("69c4e0d86a7b0430d8cdb78070b4c55a")
+        );
+    }
+
+    #[cfg(feature = "soft-crypto")]
+    #[test]
+    fn cmac_rfc4493_vectors() {
+        set_provider(&SOFT);
+        let key = hex::<16>("2b7e151628aed2a6abf7158809cf4f3c");
+        let msg = hex::<64>(
+            "6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51\
+             30c81c46a35ce411e5fbc1191a0a52eff69f2445df4f9b17ad2b417be66c3710",
+        );
+        let cases = [
+            (0, "bb1d6929e95937287fa37d129b756746"),
+            (16, "070a16b46b4d4144f79bdd9dd04a287c"),
+            (40, "dfa66747de9ae63030ca32611497c827"),
+            (64, "51f0bebf7e3b9d92fc49741779363cfe"),
+        ];
+        for (len, tag) in cases {
+            assert_eq!(cmac(&key, &msg[..len]), hex::<16>(tag), "length {len}");
+        }
+        cmac_set_key(&key);
+        assert_eq!(cmac_compute(&msg[..16]), hex::<16>(cases[1].1));
+    }
+
+    #[cfg(feature = "soft-crypto")]
+    #[test]
+    fn p256_dh_round_trip() {
+        let a = hex::<32>("c88f01f510d9ac3f70a292daa2316de544e9aab8afe84049c62a9c57862d1433");
+        let b = hex::<32>("0f89b11d2e8a3a2ad2eb6b6e6b3ac3d8a8e1d8f3a4b6d2c1e0f9a8b7c6d5e4f3");
+        let pub_a = SOFT.p256_public_key(&a).unwrap();
+        let pub_b = SOFT.p256_public_key(&b).unwrap();
+        assert_eq!(
+            SOFT.p256_dh_key(&a, &pub_b).unwrap(),
+            SOFT.p256_dh_key(&b, &pub_a).unwrap()
+        );
+
+        let mut off_curve = pub_b;
+        off_curve[63] ^= 1;
+        assert_eq!(
+            SOFT.p256_dh_key(&a, &off_curve),
+            Err(CryptoError::InvalidKey)
+        );
+        assert_eq!(SOFT.p256_public_key(&[0; 32]), Err(CryptoError::InvalidKey));
+    }
+
+    #[cfg(feature = "soft-crypto")]
+    #[test]
+    fn deferred_public_key_uses_stack_word_order() {
+        set_provider(&SOFT);
+        let mut one = [0u32; 8];
+        one[0] = 1;
+        assert_eq!(start_p256_key(&one), Ok(true));
+        assert!(process());
+        let mut public = [0u32; 16];
+        read_p256_key(&mut public).unwrap();
+        assert_eq!(public[..8], P256_GX);
+        assert_eq!(public[8..], P256_GY);
+        assert_eq!(start_p256_key(&[0; 8]), Err(CryptoError::InvalidKey));
+    }
+}
@@ -0,0 +1,77 @@
+// st_wba_ble/src/filter.rs
+//! Filter Accept List (formerly "whitelist") management.
+//!
+//! The list only takes effect when a filter policy selects it:
+//! [`AdvFilterPolicy`](crate::adv::AdvFilterPolicy) for advertising,
+//! [`ScanFilterPolicy`](crate::scan::ScanFilterPolicy) for scanning and
+//! [`ConnectParams::use_accept_list`](crate::conn::ConnectParams::use_accept_list)
+//! when initiating. The controller rejects changes while a procedure using
+//! the list is running, so update it with advertising/scanning stopped.
+
+use crate::addr::Address;
+use crate::bond::{BondStore, MAX_LISTED_BONDS};
+use crate::status::{BleStatus, Result};
+use st_wba_ble_sys::ffi;
+
+/// Namespace for the controller's Filter Accept List.
+pub struct FilterAcceptList;
+
+impl FilterAcceptList {
+    pub fn add(peer: &Address) -> Result<()> {
+        let rc = unsafe {
+            ffi::hci_le_add_device_to_filter_accept_list(peer.kind as u8, peer.bytes.as_ptr())
+        } as i32;
+        if BleStatus::from(rc) == BleStatus::Ok {
+            Ok(())
+        } else {
+            Err(BleStatus::from(rc))
+        }
+    }
+
+    pub fn remove(peer: &Address) -> Result<()> {
+        let rc = unsafe {
+            ffi::hci_le_remove_device_from_filter_accept_list(peer.kind as u8, peer.bytes.as_ptr())
+        } as i32;
+        if BleStatus::from(rc) == BleStatus::Ok {
+            Ok(())
+        } else {
+            Err(BleStatus::from(rc))
+        }
+    }
+
+    pub fn clear() -> Result<()> {
+        let rc = unsafe { ffi::hci_le_clear_filter_accept_list() } as i32;
+        if BleStatus::from(rc) == BleStatus::Ok {
+            Ok(())
+        } else {
+            Err(BleStatus::from(rc))
+        }
+    }
+
+    /// Total number of entries the controller can hold.
+    pub fn size() -> Result<u8> {
+        let mut size: u8 = 0;
+        let rc = unsafe { ffi::hci_le_read_filter_accept_list_size(&mut size) } as i32;
+        if BleStatus::from(rc) == BleStatus::Ok {
+            Ok(size)
+        } else {
+            Err(BleStatus::from(rc))
+        }
+    }
+
+    /// "Only reconnect to bonded devices": replace the list with every bonded
+    /// identity address. Returns the number of entries added.
+    ///
+    /// Combine with `AdvFilterPolicy::ScanAndConnect` when advertising, or
+    /// `use_accept_list` when connecting. With privacy enabled, also load the
+    /// resolving list so the controller can match the peers' private addresses.
+    pub fn load_bonds(bonds: &BondStore) -> Result<usize> {
+        let mut peers = [Address::public([0; 6]); MAX_LISTED_BONDS];
+        let n = bonds.list(&mut peers)?;
+        Self::clear()?;
+        for p in &peers[..n] {
+            Self::add(p)?;
+        }
+        Ok(n)
+    }
+}
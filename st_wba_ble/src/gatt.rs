@@ -1,5 +1,11 @@
 // st_wba_ble/src/gatt.rs
-use crate::status::{BleStatus, Result};
+use crate::conn::{Connection, MAX_CONNECTIONS};
+use crate::status::{self, BleStatus, Result};
+use core::cell::RefCell;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_sync::signal::Signal;
 use st_wba_ble_sys::ffi;
 
 pub struct Service {
@@ -13,6 +19,18 @@ pub struct Char {
     pub handle: u16,
 }
 
+impl Char {
+    /// Handle of the value attribute.
+    pub fn value_handle(&self) -> u16 {
+        self.handle + 1
+    }
+}
+
+/// A characteristic descriptor added with [`add_descriptor`].
+pub struct Descriptor {
+    pub handle: u16,
+}
+
 // ===== Named constants (avoid magic numbers) =====
 // Values are documented in ST's headers (e.g., ble_gatt_aci.h):
 // UUID type: 0x01 = 16-bit, 0x02 = 128-bit. Primary service = 0x01.
@@ -22,13 +40,41 @@ pub const PRIMARY_SERVICE: u8 = 0x01;
 
 // Common characteristic properties & permissions (convenience re-exports)
 // These match CubeMX templates / ST examples.
+pub const CHAR_PROP_BROADCAST: u8 = 0x01;
 pub const CHAR_PROP_READ: u8 = 0x02;
+pub const CHAR_PROP_WRITE_WITHOUT_RESP: u8 = 0x04;
+pub const CHAR_PROP_WRITE: u8 = 0x08;
 pub const CHAR_PROP_NOTIFY: u8 = 0x10;
+pub const CHAR_PROP_INDICATE: u8 = 0x20;
+pub const CHAR_PROP_SIGNED_WRITE: u8 = 0x40;
+pub const CHAR_PROP_EXT: u8 = 0x80;
+
+// Security permissions (`Security_Permissions`), combinable.
 pub const ATTR_PERMISSION_NONE: u8 = 0x00;
+pub const ATTR_PERMISSION_AUTHEN_READ: u8 = 0x01;
+pub const ATTR_PERMISSION_AUTHOR_READ: u8 = 0x02;
+pub const ATTR_PERMISSION_ENCRY_READ: u8 = 0x04;
+pub const ATTR_PERMISSION_AUTHEN_WRITE: u8 = 0x08;
+pub const ATTR_PERMISSION_AUTHOR_WRITE: u8 = 0x10;
+pub const ATTR_PERMISSION_ENCRY_WRITE: u8 = 0x20;
+
+// Descriptor access (`Access_Permissions` of `aci_gatt_add_char_desc`).
+pub const ATTR_NO_ACCESS: u8 = 0x00;
+pub const ATTR_ACCESS_READ_ONLY: u8 = 0x01;
+pub const ATTR_ACCESS_WRITE_REQ_ONLY: u8 = 0x02;
+pub const ATTR_ACCESS_READ_WRITE: u8 = 0x03;
+pub const ATTR_ACCESS_WRITE_WITHOUT_RESPONSE: u8 = 0x04;
+pub const ATTR_ACCESS_SIGNED_WRITE_ALLOWED: u8 = 0x08;
 
 // Event mask examples for `aci_gatt_add_char` (8-bit).
 // Pick the one(s) you need; default to 0 if unsure.
+/// Report writes through `aci_gatt_attribute_modified_event` (see [`add_write_listener`]).
 pub const GATT_NOTIFY_ATTRIBUTE_WRITE: u8 = 0x01;
+pub const GATT_NOTIFY_WRITE_REQ_AND_WAIT_FOR_APPL_RESP: u8 = 0x02;
+pub const GATT_NOTIFY_READ_REQ_AND_WAIT_FOR_APPL_RESP: u8 = 0x04;
+
+/// Maximum attribute value length allowed by ATT.
+pub const MAX_VALUE_LEN: u16 = 512;
 
 // ===== Internal helpers for UUID packing =====
 #[inline]
@@ -54,6 +100,17 @@ fn make_char_uuid16(uuid16: u16) -> ffi::Char_UUID_t {
     cuuid
 }
 
+#[inline]
+fn make_desc_uuid16(uuid16: u16) -> ffi::Char_Desc_Uuid_t {
+    let mut duuid: ffi::Char_Desc_Uuid_t = unsafe { core::mem::zeroed() };
+    let bytes = uuid16.to_le_bytes();
+    unsafe {
+        let p = (&mut duuid) as *mut _ as *mut u8;
+        core::ptr::copy_nonoverlapping(bytes.as_ptr(), p, 2);
+    }
+    duuid
+}
+
 #[inline]
 fn make_service_uuid128(uuid128: &[u8; 16]) -> ffi::Service_UUID_t {
     let mut suuid: ffi::Service_UUID_t = unsafe { core::mem::zeroed() };
@@ -195,11 +252,164 @@ pub fn add_char_uuid128_with_mask(
     }
 }
 
+/// Add a variable-length 16-bit UUID characteristic holding up to `max_len`
+/// bytes, optionally requiring an encryption key of `enc_key_size` bytes
+/// (7..=16, 0 for no constraint).
+pub fn add_char_variable(
+    svc: &Service,
+    uuid16: u16,
+    props: u8,
+    perm: u8,
+    max_len: u16,
+    evt_mask: u8,
+    enc_key_size: u8,
+) -> Result<Char> {
+    if max_len > MAX_VALUE_LEN {
+        return Err(BleStatus::Other(status::INVALID_PARAMS));
+    }
+    let mut ch_decl_handle: u16 = 0;
+    let cuuid = make_char_uuid16(uuid16);
+    let rc = unsafe {
+        ffi::aci_gatt_add_char(
+            svc.handle,
+            UUID_TYPE_16,
+            &cuuid as *const _,
+            max_len,
+            props,
+            perm,
+            evt_mask,
+            enc_key_size,
+            1, // Is_Variable
+            &mut ch_decl_handle as *mut u16,
+        )
+    } as i32;
+    if BleStatus::from(rc) == BleStatus::Ok {
+        Ok(Char {
+            handle: ch_decl_handle,
+        })
+    } else {
+        Err(BleStatus::from(rc))
+    }
+}
+
+/// [`add_char_variable`] for a 128-bit UUID characteristic.
+pub fn add_char_uuid128_variable(
+    svc: &Service,
+    uuid128: [u8; 16],
+    props: u8,
+    perm: u8,
+    max_len: u16,
+    evt_mask: u8,
+    enc_key_size: u8,
+) -> Result<Char> {
+    if max_len > MAX_VALUE_LEN {
+        return Err(BleStatus::Other(status::INVALID_PARAMS));
+    }
+    let mut ch_decl_handle: u16 = 0;
+    let cuuid = make_char_uuid128(&uuid128);
+    let rc = unsafe {
+        ffi::aci_gatt_add_char(
+            svc.handle,
+            UUID_TYPE_128,
+            &cuuid as *const _,
+            max_len,
+            props,
+            perm,
+            evt_mask,
+            enc_key_size,
+            1, // Is_Variable
+            &mut ch_decl_handle as *mut u16,
+        )
+    } as i32;
+    if BleStatus::from(rc) == BleStatus::Ok {
+        Ok(Char {
+            handle: ch_decl_handle,
+        })
+    } else {
+        Err(BleStatus::from(rc))
+    }
+}
+
+// ===== Descriptors =====
+/// Add a 16-bit UUID descriptor to `ch` with initial `value`. `access` is
+/// one of the `ATTR_ACCESS_*` values, `perm` the security permissions.
+pub fn add_descriptor(
+    svc: &Service,
+    ch: &Char,
+    uuid16: u16,
+    perm: u8,
+    access: u8,
+    value: &[u8],
+    max_len: u8,
+) -> Result<Descriptor> {
+    if value.len() > max_len as usize {
+        return Err(BleStatus::Other(status::INVALID_PARAMS));
+    }
+    let mut desc_handle: u16 = 0;
+    let duuid = make_desc_uuid16(uuid16);
+    let rc = unsafe {
+        ffi::aci_gatt_add_char_desc(
+            svc.handle,
+            ch.handle,
+            UUID_TYPE_16,
+            &duuid as *const _,
+            max_len,
+            value.len() as u8,
+            value.as_ptr(),
+            perm,
+            access,
+            GATT_NOTIFY_ATTRIBUTE_WRITE,
+            0,                                       // Enc_Key_Size
+            (value.len() != max_len as usize) as u8, // Is_Variable
+            &mut desc_handle as *mut u16,
+        )
+    } as i32;
+    if BleStatus::from(rc) == BleStatus::Ok {
+        Ok(Descriptor {
+            handle: desc_handle,
+        })
+    } else {
+        Err(BleStatus::from(rc))
+    }
+}
+
 // ===== Updates / Notifications =====
+/// Set the local value (up to [`MAX_VALUE_LEN`]) without notifying anyone,
+/// e.g. a HID Report Map. Longer than 255 bytes is written in parts.
+pub fn set_char_value(svc: &Service, ch: &Char, val: &[u8]) -> Result<()> {
+    if val.len() > MAX_VALUE_LEN as usize {
+        return Err(BleStatus::Other(status::INVALID_PARAMS));
+    }
+    const GATT_CHAR_UPDATE_LOCAL_ONLY: u8 = 0x00;
+    let mut off = 0;
+    loop {
+        let chunk = (val.len() - off).min(u8::MAX as usize);
+        let rc = unsafe {
+            ffi::aci_gatt_update_char_value_ext(
+                0, // Conn_Handle_To_Notify: unused for local updates
+                svc.handle,
+                ch.value_handle(),
+                GATT_CHAR_UPDATE_LOCAL_ONLY,
+                val.len() as u16,
+                off as u16,
+                chunk as u8,
+                val[off..].as_ptr(),
+            )
+        } as i32;
+        if BleStatus::from(rc) != BleStatus::Ok {
+            return Err(BleStatus::from(rc));
+        }
+        off += chunk;
+        if off >= val.len() {
+            return Ok(());
+        }
+    }
+}
+
 /// Update the characteristic value (<=255 bytes) and let the stack notify if CCCD is enabled.
 /// For longer values, prefer `update_char_value_chunked_notify` (uses the EXT API when enabled).
 pub fn update_char_value(svc: &Service, ch: &Char, val: &[u8]) -> Result<()> {
-    let value_handle = ch.handle + 1; // value attribute lives at decl+1
+    let value_handle = ch.value_handle();
     let rc = unsafe {
         ffi::aci_gatt_update_char_value(
             svc.handle,
@@ -222,7 +432,7 @@ pub fn update_char_value(svc: &Service, ch: &Char, val: &[u8]) -> Result<()> {
 /// in one go; otherwise it falls back to multiple basic updates.
 #[cfg(feature = "use_update_ext")]
 pub fn update_char_value_chunked_notify(svc: &Service, ch: &Char, val: &[u8]) -> Result<()> {
-    let value_handle = ch.handle + 1;
+    let value_handle = ch.value_handle();
     // Update type 0x01 is commonly used to send notifications (per ST posts).
     const GATT_CHAR_UPDATE_SEND_NOTIFICATION: u8 = 0x01;
 
@@ -254,7 +464,7 @@ pub fn update_char_value_chunked_notify(svc: &Service, ch: &Char, val: &[u8]) ->
 pub fn update_char_value_chunked_notify(svc: &Service, ch: &Char, val: &[u8]) -> Result<()> {
     // Fallback: perform multiple basic updates with increasing offset.
     // Depending on stack configuration, this may generate multiple notifications.
-    let value_handle = ch.handle + 1;
+    let value_handle = ch.value_handle();
     let total = val.len() as u16;
     let mut off: u16 = 0;
     while off < total {
@@ -277,3 +487,647 @@ pub fn update_char_value_chunked_notify(svc: &Service, ch: &Char, val: &[u8]) ->
     }
     Ok(())
 }
+
+// ===== Attribute write listeners =====
+/// A client wrote one of our attributes (`aci_gatt_attribute_modified_event`).
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct AttributeWrite<'a> {
+    pub bearer: Bearer,
+    /// Attribute handle written (a value handle, CCCD or descriptor).
+    pub handle: u16,
+    pub offset: u16,
+    pub data: &'a [u8],
+}
+
+/// Called from the stack's event context for every attribute write; filter on
+/// [`AttributeWrite::handle`] and keep the work short.
+pub type WriteListener = fn(&AttributeWrite<'_>);
+
+/// Listeners that can be registered (one per profile/service is typical).
+pub const MAX_WRITE_LISTENERS: usize = 8;
+
+static WRITE_LISTENERS: Mutex<
+    CriticalSectionRawMutex,
+    RefCell<[Option<WriteListener>; MAX_WRITE_LISTENERS]>,
+> = Mutex::new(RefCell::new([None; MAX_WRITE_LISTENERS]));
+
+/// Register `f` for attribute writes. Characteristics must be added with
+/// [`GATT_NOTIFY_ATTRIBUTE_WRITE`] in their event mask.
+pub fn add_write_listener(f: WriteListener) -> Result<()> {
+    WRITE_LISTENERS.lock(|l| {
+        let mut l = l.borrow_mut();
+        if l.iter().flatten().any(|g| core::ptr::fn_addr_eq(*g, f)) {
+            return Ok(());
+        }
+        let slot = l
+            .iter_mut()
+            .find(|s| s.is_none())
+            .ok_or(BleStatus::Other(status::INSUFFICIENT_RESOURCES))?;
+        *slot = Some(f);
+        Ok(())
+    })
+}
+
+/// # Safety
+/// `attr_data` must point to `attr_data_length` bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn aci_gatt_attribute_modified_event(
+    connection_handle: u16,
+    attr_handle: u16,
+    offset: u16,
+    attr_data_length: u16,
+    attr_data: *const u8,
+) {
+    let data = if attr_data.is_null() {
+        &[][..]
+    } else {
+        unsafe { core::slice::from_raw_parts(attr_data, attr_data_length as usize) }
+    };
+    let write = AttributeWrite {
+        bearer: Bearer::from_raw(connection_handle),
+        handle: attr_handle,
+        offset,
+        data,
+    };
+    // Copy the table out so listeners may register others.
+    let listeners = WRITE_LISTENERS.lock(|l| *l.borrow());
+    for f in listeners.into_iter().flatten() {
+        f(&write);
+    }
+}
+
+// ===== ATT bearers (EATT) =====
+/// Enhanced bearers are addressed as `0xEA00 | channel index` wherever the
+/// GATT ACI takes a `Connection_Handle` (ble_gatt_aci.h).
+const EATT_BEARER_BASE: u16 = 0xEA00;
+const EATT_BEARER_MASK: u16 = 0xFF00;
+
+/// ATT bearer a GATT operation runs on.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Bearer {
+    /// The unenhanced bearer (fixed ATT channel) of a connection.
+    Unenhanced(u16),
+    /// An Enhanced ATT bearer, by its L2CAP channel index.
+    Enhanced(u8),
+}
+
+impl Bearer {
+    /// Value passed as `Connection_Handle` to the GATT ACI.
+    pub fn raw(self) -> u16 {
+        match self {
+            Bearer::Unenhanced(handle) => handle,
+            Bearer::Enhanced(index) => EATT_BEARER_BASE | index as u16,
+        }
+    }
+
+    /// Decode a `Connection_Handle` reported by a GATT event.
+    pub fn from_raw(raw: u16) -> Self {
+        if raw & EATT_BEARER_MASK == EATT_BEARER_BASE {
+            Bearer::Enhanced(raw as u8)
+        } else {
+            Bearer::Unenhanced(raw)
+        }
+    }
+}
+
+impl From<&Connection> for Bearer {
+    fn from(conn: &Connection) -> Self {
+        Bearer::Unenhanced(conn.handle())
+    }
+}
+
+/// An Enhanced ATT bearer was opened or closed (`aci_gatt_eatt_bearer_event`).
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct BearerEvent {
+    pub bearer: Bearer,
+    pub opened: bool,
+    /// HCI status; non-zero if opening failed.
+    pub status: u8,
+}
+
+/// Enhanced bearers tracked at once (match `StackConfig::eatt_bearers`).
+pub const MAX_EATT_BEARERS: usize = 8;
+
+const EAB_STATE_CREATED: u8 = 0x00;
+
+static BEARER_EVENTS: Channel<CriticalSectionRawMutex, BearerEvent, MAX_EATT_BEARERS> =
+    Channel::new();
+/// An open Enhanced bearer.
+#[derive(Copy, Clone)]
+struct EattBearer {
+    index: u8,
+    mtu: u16,
+    /// Link of the L2CAP channel carrying it, if opened through [`crate::l2cap`].
+    conn: Option<u16>,
+}
+
+static BEARERS: Mutex<CriticalSectionRawMutex, RefCell<[Option<EattBearer>; MAX_EATT_BEARERS]>> =
+    Mutex::new(RefCell::new([None; MAX_EATT_BEARERS]));
+
+/// Connection handle of the link `bearer` runs on; `None` for an Enhanced
+/// bearer that is closed or whose channel was not opened through
+/// [`crate::l2cap`].
+pub fn bearer_connection(bearer: Bearer) -> Option<u16> {
+    match bearer {
+        Bearer::Unenhanced(conn) => Some(conn),
+        Bearer::Enhanced(index) => BEARERS.lock(|b| {
+            b.borrow()
+                .iter()
+                .flatten()
+                .find(|e| e.index == index)
+                .and_then(|e| e.conn)
+        }),
+    }
+}
+
+/// Wait for the next Enhanced ATT bearer being opened or closed. Enable EATT
+/// with `options::ENHANCED_ATT` and `StackConfig::eatt_bearers`.
+pub async fn bearer_event() -> BearerEvent {
+    BEARER_EVENTS.receive().await
+}
+
+/// Enhanced bearers currently open.
+pub fn eatt_bearers() -> impl Iterator<Item = Bearer> {
+    BEARERS
+        .lock(|b| *b.borrow())
+        .into_iter()
+        .flatten()
+        .map(|e| Bearer::Enhanced(e.index))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn aci_gatt_eatt_bearer_event(channel_index: u8, eab_state: u8, status: u8) {
+    let opened = eab_state == EAB_STATE_CREATED && status == 0;
+    BEARERS.lock(|b| {
+        let mut b = b.borrow_mut();
+        let known = b
+            .iter()
+            .position(|e| e.is_some_and(|e| e.index == channel_index));
+        match (opened, known) {
+            // The MTU is raised by the aci_att_exchange_mtu_resp_event that
+            // reports the bearer's negotiated value.
+            (true, None) => {
+                if let Some(free) = b.iter_mut().find(|e| e.is_none()) {
+                    *free = Some(EattBearer {
+                        index: channel_index,
+                        mtu: EATT_MIN_MTU,
+                        conn: crate::l2cap::channel_connection(channel_index),
+                    });
+                }
+            }
+            (false, Some(i)) => b[i] = None,
+            _ => {}
+        }
+    });
+    if eab_state != EAB_STATE_CREATED {
+        end_procedure(
+            Bearer::Enhanced(channel_index).raw(),
+            Err(BleStatus::Other(status::UNKNOWN_CONNECTION)),
+        );
+    }
+    let _ = BEARER_EVENTS.try_send(BearerEvent {
+        bearer: Bearer::Enhanced(channel_index),
+        opened,
+        status,
+    });
+}
+
+// ===== Server operations on a bearer =====
+const GATT_CHAR_UPDATE_NOTIFICATION: u8 = 0x01;
+const GATT_CHAR_UPDATE_INDICATION: u8 = 0x02;
+
+fn send_update(bearer: Bearer, svc: &Service, ch: &Char, kind: u8, val: &[u8]) -> Result<()> {
+    if val.len() > u8::MAX as usize {
+        return Err(BleStatus::Other(status::INVALID_PARAMS));
+    }
+    let rc = unsafe {
+        ffi::aci_gatt_update_char_value_ext(
+            bearer.raw(),
+            svc.handle,
+            ch.value_handle(),
+            kind,
+            val.len() as u16, // Char_Length
+            0,                // Value_Offset
+            val.len() as u8,
+            val.as_ptr(),
+        )
+    } as i32;
+    if BleStatus::from(rc) == BleStatus::Ok {
+        Ok(())
+    } else {
+        Err(BleStatus::from(rc))
+    }
+}
+
+/// Update the value and notify it on `bearer` only.
+pub fn notify(bearer: Bearer, svc: &Service, ch: &Char, val: &[u8]) -> Result<()> {
+    send_update(bearer, svc, ch, GATT_CHAR_UPDATE_NOTIFICATION, val)
+}
+
+/// Update the value and indicate it on `bearer` only.
+pub fn indicate(bearer: Bearer, svc: &Service, ch: &Char, val: &[u8]) -> Result<()> {
+    send_update(bearer, svc, ch, GATT_CHAR_UPDATE_INDICATION, val)
+}
+
+// ===== ATT MTU and TX flow control =====
+/// ATT MTU before (or without) an MTU exchange.
+pub const DEFAULT_ATT_MTU: u16 = 23;
+/// Smallest MTU of an Enhanced ATT bearer.
+pub const EATT_MIN_MTU: u16 = 64;
+
+/// (connection handle, negotiated MTU).
+type LinkMtu = (u16, u16);
+
+static ATT_MTUS: Mutex<CriticalSectionRawMutex, RefCell<[Option<LinkMtu>; MAX_CONNECTIONS]>> =
+    Mutex::new(RefCell::new([None; MAX_CONNECTIONS]));
+static TX_POOL: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// ATT MTU negotiated on `bearer`.
+pub fn att_mtu(bearer: Bearer) -> u16 {
+    match bearer {
+        Bearer::Unenhanced(conn) => ATT_MTUS.lock(|m| {
+            m.borrow()
+                .iter()
+                .flatten()
+                .find(|(c, _)| *c == conn)
+                .map_or(DEFAULT_ATT_MTU, |(_, mtu)| *mtu)
+        }),
+        Bearer::Enhanced(index) => BEARERS.lock(|b| {
+            b.borrow()
+                .iter()
+                .flatten()
+                .find(|e| e.index == index)
+                .map_or(EATT_MIN_MTU, |e| e.mtu)
+        }),
+    }
+}
+
+/// Wait until the stack frees TX buffers after an update failed with
+/// `BLE_STATUS_INSUFFICIENT_RESOURCES`. Call [`tx_pool_reset`] before the
+/// attempt so an event in between is not lost.
+pub async fn tx_pool_available() {
+    TX_POOL.wait().await
+}
+
+/// Arm [`tx_pool_available`] before trying to send.
+pub fn tx_pool_reset() {
+    TX_POOL.reset();
+}
+
+/// MTU agreed on a bearer; `connection_handle` is `0xEA00 | index` for
+/// Enhanced bearers.
+#[unsafe(no_mangle)]
+pub extern "C" fn aci_att_exchange_mtu_resp_event(connection_handle: u16, server_rx_mtu: u16) {
+    if let Bearer::Enhanced(index) = Bearer::from_raw(connection_handle) {
+        BEARERS.lock(|b| {
+            if let Some(e) = b
+                .borrow_mut()
+                .iter_mut()
+                .flatten()
+                .find(|e| e.index == index)
+            {
+                e.mtu = server_rx_mtu;
+            }
+        });
+        return;
+    }
+    ATT_MTUS.lock(|m| {
+        let mut m = m.borrow_mut();
+        let slot = match m
+            .iter()
+            .position(|e| e.is_some_and(|(c, _)| c == connection_handle))
+        {
+            Some(i) => Some(i),
+            None => m.iter().position(Option::is_none),
+        };
+        if let Some(i) = slot {
+            m[i] = Some((connection_handle, server_rx_mtu));
+        }
+    });
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn aci_gatt_tx_pool_available_event(
+    _connection_handle: u16,
+    _available_buffers: u16,
+) {
+    TX_POOL.signal(());
+}
+
+// ===== Client operations on a bearer =====
+/// Client procedures that can run at once (one per bearer).
+pub const MAX_PROCEDURES: usize = 4;
+/// Longest attribute value a client read can return.
+pub const MAX_ATTR_LEN: usize = 512;
+
+struct Procedure {
+    bearer: u16,
+    value: [u8; MAX_ATTR_LEN],
+    len: usize,
+    /// ATT error from `aci_gatt_error_resp_event`, reported at completion.
+    att_error: u8,
+}
+
+static PROCEDURES: Mutex<CriticalSectionRawMutex, RefCell<[Option<Procedure>; MAX_PROCEDURES]>> =
+    Mutex::new(RefCell::new([const { None }; MAX_PROCEDURES]));
+static PROC_DONE: [Signal<CriticalSectionRawMutex, Result<()>>; MAX_PROCEDURES] =
+    [const { Signal::new() }; MAX_PROCEDURES];
+
+/// A claimed procedure slot, freed when the client call returns or its
+/// future is dropped, so an abandoned call does not leave the bearer busy.
+struct ProcedureSlot(usize);
+
+impl Drop for ProcedureSlot {
+    fn drop(&mut self) {
+        PROCEDURES.lock(|p| p.borrow_mut()[self.0] = None);
+    }
+}
+
+/// Claim a procedure slot; fails if `bearer` already runs one.
+fn begin_procedure(bearer: u16) -> Result<ProcedureSlot> {
+    let slot = PROCEDURES.lock(|p| {
+        let mut p = p.borrow_mut();
+        if p.iter().flatten().any(|e| e.bearer == bearer) {
+            return Err(BleStatus::Other(status::BUSY));
+        }
+        let i = p
+            .iter()
+            .position(Option::is_none)
+            .ok_or(BleStatus::Other(status::INSUFFICIENT_RESOURCES))?;
+        p[i] = Some(Procedure {
+            bearer,
+            value: [0; MAX_ATTR_LEN],
+            len: 0,
+            att_error: 0,
+        });
+        Ok(i)
+    })?;
+    PROC_DONE[slot].reset();
+    Ok(ProcedureSlot(slot))
+}
+
+fn procedure_slot(bearer: u16) -> Option<usize> {
+    PROCEDURES.lock(|p| {
+        p.borrow()
+            .iter()
+            .position(|e| e.as_ref().is_some_and(|e| e.bearer == bearer))
+    })
+}
+
+fn end_procedure(bearer: u16, result: Result<()>) {
+    if let Some(slot) = procedure_slot(bearer) {
+        PROC_DONE[slot].signal(result);
+    }
+}
+
+/// Wait for completion of the ACI command issued for a claimed slot.
+async fn run_procedure(slot: &ProcedureSlot, rc: i32) -> Result<()> {
+    if BleStatus::from(rc) == BleStatus::Ok {
+        PROC_DONE[slot.0].wait().await
+    } else {
+        Err(BleStatus::from(rc))
+    }
+}
+
+/// Read a remote attribute value over `bearer` into `buf`; returns its length.
+/// Procedures on different bearers of one link run concurrently.
+pub async fn read(bearer: Bearer, attr_handle: u16, buf: &mut [u8]) -> Result<usize> {
+    let raw = bearer.raw();
+    let slot = begin_procedure(raw)?;
+    let rc = unsafe { ffi::aci_gatt_read_char_value(raw, attr_handle) } as i32;
+    run_procedure(&slot, rc).await?;
+    let proc = PROCEDURES.lock(|p| p.borrow_mut()[slot.0].take());
+    let Some(proc) = proc else {
+        return Err(BleStatus::Other(status::UNKNOWN_CONNECTION));
+    };
+    let n = proc.len.min(buf.len());
+    buf[..n].copy_from_slice(&proc.value[..n]);
+    Ok(n)
+}
+
+/// Write a remote attribute value over `bearer` and wait for the response.
+pub async fn write(bearer: Bearer, attr_handle: u16, val: &[u8]) -> Result<()> {
+    let raw = bearer.raw();
+    let slot = begin_procedure(raw)?;
+    let rc =
+        unsafe { ffi::aci_gatt_write_char_value(raw, attr_handle, val.len() as _, val.as_ptr()) }
+            as i32;
+    run_procedure(&slot, rc).await
+}
+
+/// Write Command (no response) over `bearer`.
+pub fn write_without_response(bearer: Bearer, attr_handle: u16, val: &[u8]) -> Result<()> {
+    let rc = unsafe {
+        ffi::aci_gatt_write_without_resp(bearer.raw(), attr_handle, val.len() as _, val.as_ptr())
+    } as i32;
+    if BleStatus::from(rc) == BleStatus::Ok {
+        Ok(())
+    } else {
+        Err(BleStatus::from(rc))
+    }
+}
+
+/// Client Characteristic Configuration value enabling notifications.
+pub const CCCD_NOTIFY: u16 = 0x0001;
+/// Client Characteristic Configuration value enabling indications.
+pub const CCCD_INDICATE: u16 = 0x0002;
+
+/// Write a remote CCCD (`0` to unsubscribe); values then arrive through
+/// [`add_notification_listener`].
+pub async fn subscribe(bearer: Bearer, cccd_handle: u16, value: u16) -> Result<()> {
+    write(bearer, cccd_handle, &value.to_le_bytes()).await
+}
+
+// ===== Client notification listeners =====
+/// A server notified or indicated one of its attributes.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct AttributeNotification<'a> {
+    pub bearer: Bearer,
+    /// Remote value handle.
+    pub handle: u16,
+    /// Indication; confirmed once the listeners return.
+    pub indication: bool,
+    pub data: &'a [u8],
+}
+
+/// Called from the stack's event context for every notification/indication
+/// received; filter on [`AttributeNotification::handle`] and keep it short.
+pub type NotificationListener = fn(&AttributeNotification<'_>);
+
+/// Notification listeners that can be registered.
+pub const MAX_NOTIFICATION_LISTENERS: usize = 8;
+
+static NOTIFICATION_LISTENERS: Mutex<
+    CriticalSectionRawMutex,
+    RefCell<[Option<NotificationListener>; MAX_NOTIFICATION_LISTENERS]>,
+> = Mutex::new(RefCell::new([None; MAX_NOTIFICATION_LISTENERS]));
+
+/// Register `f` for notifications and indications from remote servers.
+pub fn add_notification_listener(f: NotificationListener) -> Result<()> {
+    NOTIFICATION_LISTENERS.lock(|l| {
+        let mut l = l.borrow_mut();
+        if l.iter().flatten().any(|g| core::ptr::fn_addr_eq(*g, f)) {
+            return Ok(());
+        }
+        let slot = l
+            .iter_mut()
+            .find(|s| s.is_none())
+            .ok_or(BleStatus::Other(status::INSUFFICIENT_RESOURCES))?;
+        *slot = Some(f);
+        Ok(())
+    })
+}
+
+fn dispatch_notification(
+    connection_handle: u16,
+    attribute_handle: u16,
+    indication: bool,
+    len: u16,
+    value: *const u8,
+) {
+    let data = if value.is_null() {
+        &[][..]
+    } else {
+        unsafe { core::slice::from_raw_parts(value, len as usize) }
+    };
+    let n = AttributeNotification {
+        bearer: Bearer::from_raw(connection_handle),
+        handle: attribute_handle,
+        indication,
+        data,
+    };
+    let listeners = NOTIFICATION_LISTENERS.lock(|l| *l.borrow());
+    for f in listeners.into_iter().flatten() {
+        f(&n);
+    }
+}
+
+/// # Safety
+/// `attribute_value` must point to `attribute_value_length` bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn aci_gatt_notification_event(
+    connection_handle: u16,
+    attribute_handle: u16,
+    attribute_value_length: u16,
+    attribute_value: *const u8,
+) {
+    dispatch_notification(
+        connection_handle,
+        attribute_handle,
+        false,
+        attribute_value_length,
+        attribute_value,
+    );
+}
+
+/// # Safety
+/// `attribute_value` must point to `attribute_value_length` bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn aci_gatt_indication_event(
+    connection_handle: u16,
+    attribute_handle: u16,
+    attribute_value_length: u16,
+    attribute_value: *const u8,
+) {
+    dispatch_notification(
+        connection_handle,
+        attribute_handle,
+        true,
+        attribute_value_length,
+        attribute_value,
+    );
+    unsafe { ffi::aci_gatt_confirm_indication(connection_handle) };
+}
+
+fn append_value(connection_handle: u16, data: *const u8, len: u16) {
+    if data.is_null() {
+        return;
+    }
+    let data = unsafe { core::slice::from_raw_parts(data, len as usize) };
+    PROCEDURES.lock(|p| {
+        let mut p = p.borrow_mut();
+        if let Some(proc) = p
+            .iter_mut()
+            .flatten()
+            .find(|e| e.bearer == connection_handle)
+        {
+            let end = (proc.len + data.len()).min(MAX_ATTR_LEN);
+            let n = end - proc.len;
+            proc.value[proc.len..end].copy_from_slice(&data[..n]);
+            proc.len = end;
+        }
+    });
+}
+
+/// # Safety
+/// `attribute_value` must point to `event_data_length` bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn aci_att_read_resp_event(
+    connection_handle: u16,
+    event_data_length: u16,
+    attribute_value: *const u8,
+) {
+    append_value(connection_handle, attribute_value, event_data_length);
+}
+
+/// Further parts of a long value read.
+///
+/// # Safety
+/// `attribute_value` must point to `event_data_length` bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn aci_att_read_blob_resp_event(
+    connection_handle: u16,
+    event_data_length: u16,
+    attribute_value: *const u8,
+) {
+    append_value(connection_handle, attribute_value, event_data_length);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn aci_gatt_error_resp_event(
+    connection_handle: u16,
+    _req_opcode: u8,
+    _attribute_handle: u16,
+    error_code: u8,
+) {
+    PROCEDURES.lock(|p| {
+        if let Some(proc) = p
+            .borrow_mut()
+            .iter_mut()
+            .flatten()
+            .find(|e| e.bearer == connection_handle)
+        {
+            proc.att_error = error_code;
+        }
+    });
+}
+
+/// End of a client procedure; `connection_handle` identifies the bearer.
+#[unsafe(no_mangle)]
+pub extern "C" fn aci_gatt_proc_complete_event(connection_handle: u16, error_code: u8) {
+    let att_error = PROCEDURES.lock(|p| {
+        p.borrow()
+            .iter()
+            .flatten()
+            .find(|e| e.bearer == connection_handle)
+            .map_or(0, |e| e.att_error)
+    });
+    let result = match (error_code, att_error) {
+        (0, 0) => Ok(()),
+        (0, att) => Err(BleStatus::Other(att as i32)),
+        (err, _) => Err(BleStatus::from(err as i32)),
+    };
+    end_procedure(connection_handle, result);
+}
+
+/// Fail procedures still running on a link that went down and forget its MTU.
+pub(crate) fn on_link_closed(conn: u16) {
+    end_procedure(conn, Err(BleStatus::Other(status::UNKNOWN_CONNECTION)));
+    ATT_MTUS.lock(|m| {
+        for e in m.borrow_mut().iter_mut() {
+            if e.is_some_and(|(c, _)| c == conn) {
+                *e = None;
+            }
+        }
+    });
+}
@@ -0,0 +1,1182 @@
+// st_wba_ble/src/l2cap.rs
+//! L2CAP credit-based connection-oriented channels: LE CoC and enhanced
+//! credit-based mode (up to five channels per request, reconfigurable).
+//!
+//! Wraps `aci_l2cap_coc_*` from `ble_l2cap_aci.h`. The stack exchanges
+//! K-frames; this module segments outgoing SDUs, reassembles incoming ones
+//! and handles credits: incoming SDUs are queued in a [`RX_BUFFER_LEN`]
+//! buffer per channel, the peer only holds credits for the space that is
+//! free, and they are returned as [`L2capChannel::receive`] takes SDUs.
+//! Reserve channels with [`StackConfig::coc`].
+//!
+//! ```ignore
+//! let ch = L2capChannel::connect(&conn, 0x0080, &CocConfig::default()).await?;
+//! ch.send(&image_block).await?;
+//!
+//! let req = l2cap::listen().await;
+//! let ch = req.accept(&CocConfig::default())?;
+//! let n = ch.receive(&mut buf).await?;
+//!
+//! // Enhanced mode: parallel channels, MTU raised later.
+//! let group = L2capChannel::connect_enhanced(&conn, 0x0027, &cfg, 3).await?;
+//! group.reconfigure(&CocConfig { mtu: 512, mps: 247 }).await?;
+//! ```
+//!
+//! [`StackConfig::coc`]: crate::StackConfig::coc
+
+use crate::conn::{Connection, MAX_CONNECTIONS};
+use crate::status::{self, BleStatus, Result};
+use core::cell::RefCell;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_sync::mutex::Mutex as AsyncMutex;
+use embassy_sync::signal::Signal;
+use st_wba_ble_sys::ffi;
+
+/// Channels tracked by this crate (match `StackConfig::coc`).
+pub const MAX_CHANNELS: usize = 4;
+/// Largest SDU that can be received; bounds [`CocConfig::mtu`].
+pub const MAX_SDU: usize = 512;
+/// Receive queue per channel; must hold the K-frames of one full SDU.
+pub const RX_BUFFER_LEN: usize = 2 * MAX_SDU;
+/// Channels one enhanced credit-based request can open.
+pub const MAX_ENHANCED_CHANNELS: usize = 5;
+
+/// Largest K-frame `aci_l2cap_coc_tx_data` accepts (`BLE_CMD_MAX_PARAM_LEN - 3`).
+const MAX_TX_FRAME: usize = 252;
+/// Size of the SDU length field leading the first K-frame.
+const SDU_LEN_FIELD: usize = 2;
+/// Smallest MTU/MPS allowed in enhanced credit-based mode.
+const ECFC_MIN_MTU: u16 = 64;
+
+/// L2CAP result codes for connection responses.
+pub mod result {
+    pub const SUCCESS: u16 = 0x0000;
+    pub const SPSM_NOT_SUPPORTED: u16 = 0x0002;
+    pub const NO_RESOURCES: u16 = 0x0004;
+    pub const INSUFFICIENT_AUTHENTICATION: u16 = 0x0005;
+    pub const INSUFFICIENT_AUTHORIZATION: u16 = 0x0006;
+    pub const INSUFFICIENT_ENCRYPTION_KEY_SIZE: u16 = 0x0007;
+    pub const INSUFFICIENT_ENCRYPTION: u16 = 0x0008;
+    pub const UNACCEPTABLE_PARAMETERS: u16 = 0x000B;
+
+    /// Reconfiguration results.
+    pub const RECONF_MTU_REDUCTION: u16 = 0x0001;
+    pub const RECONF_MPS_REDUCTION: u16 = 0x0002;
+    pub const RECONF_INVALID_CID: u16 = 0x0003;
+}
+
+/// Local receive parameters of a channel.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct CocConfig {
+    /// Largest SDU we accept (≤ [`MAX_SDU`]).
+    pub mtu: u16,
+    /// Largest K-frame payload we accept (≤ `StackConfig::max_coc_mps`);
+    /// the frames of one full SDU must fit in [`RX_BUFFER_LEN`].
+    pub mps: u16,
+}
+
+impl Default for CocConfig {
+    fn default() -> Self {
+        Self {
+            mtu: MAX_SDU as u16,
+            mps: 248,
+        }
+    }
+}
+
+impl CocConfig {
+    /// Credits covering one full SDU.
+    fn credits(&self) -> u16 {
+        (self.mtu as usize + SDU_LEN_FIELD).div_ceil(self.mps.max(1) as usize) as u16
+    }
+
+    fn is_valid(&self, enhanced: bool) -> bool {
+        let min = if enhanced { ECFC_MIN_MTU } else { 23 };
+        self.mtu >= min
+            && self.mtu as usize <= MAX_SDU
+            && self.mps >= min
+            && self.credits() as usize * self.mps as usize <= RX_BUFFER_LEN
+    }
+}
+
+struct Chan {
+    conn: u16,
+    index: u8,
+    enhanced: bool,
+    local: CocConfig,
+    peer_mtu: u16,
+    peer_mps: u16,
+    tx_credits: u16,
+    /// Received SDUs, each stored as its 2-byte length and payload, followed
+    /// by the one being reassembled.
+    rx: [u8; RX_BUFFER_LEN],
+    rx_head: usize,
+    rx_len: usize,
+    /// Bytes of complete SDUs at the front of the queue.
+    rx_ready: usize,
+    /// Length and bytes received so far of the SDU being reassembled.
+    rx_expected: Option<(usize, usize)>,
+    /// Credits the peer holds; each K-frame stores at most `local.mps` bytes,
+    /// so they never exceed the free space.
+    rx_credits: u16,
+}
+
+impl Chan {
+    fn new(conn: u16, index: u8, enhanced: bool, local: CocConfig, peer: Peer) -> Self {
+        Chan {
+            conn,
+            index,
+            enhanced,
+            local,
+            peer_mtu: peer.mtu,
+            peer_mps: peer.mps,
+            tx_credits: peer.credits,
+            rx: [0; RX_BUFFER_LEN],
+            rx_head: 0,
+            rx_len: 0,
+            rx_ready: 0,
+            rx_expected: None,
+            rx_credits: local.credits(),
+        }
+    }
+
+    /// Queue one received K-frame. `Ok(true)` when it completes an SDU,
+    /// `Err` on a protocol violation (oversized SDU or K-frame, or more
+    /// frames than credited), after which the channel must be closed.
+    fn rx_frame(&mut self, frame: &[u8]) -> core::result::Result<bool, ()> {
+        if frame.len() > self.local.mps as usize || self.rx_credits == 0 {
+            return Err(());
+        }
+        let (expected, received) = match self.rx_expected {
+            None => {
+                let Some((len, _)) = frame.split_first_chunk::<SDU_LEN_FIELD>() else {
+                    return Err(());
+                };
+                let len = u16::from_le_bytes(*len) as usize;
+                if len > self.local.mtu as usize {
+                    return Err(());
+                }
+                (len, frame.len() - SDU_LEN_FIELD)
+            }
+            Some((expected, received)) => (expected, received + frame.len()),
+        };
+        if received > expected || self.rx_len + frame.len() > RX_BUFFER_LEN {
+            return Err(());
+        }
+        // The first frame's SDU length field is kept as the queue record header.
+        self.rx_push(frame);
+        self.rx_credits -= 1;
+        if received < expected {
+            self.rx_expected = Some((expected, received));
+            return Ok(false);
+        }
+        self.rx_expected = None;
+        self.rx_ready = self.rx_len;
+        Ok(true)
+    }
+
+    /// Copy the oldest complete SDU into `buf`; returns its length and the
+    /// credits to give back to the peer. `None` if no SDU is queued.
+    fn take_sdu(&mut self, buf: &mut [u8]) -> Option<Result<(usize, u16)>> {
+        let n = self.rx_next_len()?;
+        if buf.len() < n {
+            return Some(Err(BleStatus::Other(status::INVALID_PARAMS)));
+        }
+        self.rx_pop(&mut [0; SDU_LEN_FIELD]);
+        self.rx_pop(&mut buf[..n]);
+        Some(Ok((n, self.top_up())))
+    }
+
+    fn rx_push(&mut self, data: &[u8]) {
+        for &b in data {
+            self.rx[(self.rx_head + self.rx_len) % RX_BUFFER_LEN] = b;
+            self.rx_len += 1;
+        }
+    }
+
+    fn rx_pop(&mut self, out: &mut [u8]) {
+        for o in out.iter_mut() {
+            *o = self.rx[self.rx_head];
+            self.rx_head = (self.rx_head + 1) % RX_BUFFER_LEN;
+        }
+        self.rx_len -= out.len();
+        self.rx_ready -= out.len();
+    }
+
+    /// Length of the oldest complete SDU.
+    fn rx_next_len(&self) -> Option<usize> {
+        if self.rx_ready == 0 {
+            return None;
+        }
+        let lo = self.rx[self.rx_head];
+        let hi = self.rx[(self.rx_head + 1) % RX_BUFFER_LEN];
+        Some(u16::from_le_bytes([lo, hi]) as usize)
+    }
+
+    /// Credits to grant so the peer holds as many as the free space allows,
+    /// up to one full SDU's worth.
+    fn top_up(&mut self) -> u16 {
+        let free = (RX_BUFFER_LEN - self.rx_len) / self.local.mps.max(1) as usize;
+        let target = (free as u16).min(self.local.credits());
+        let grant = target.saturating_sub(self.rx_credits);
+        self.rx_credits += grant;
+        grant
+    }
+}
+
+#[derive(Copy, Clone)]
+struct Pending {
+    conn: u16,
+    local: CocConfig,
+    enhanced: bool,
+}
+
+/// Channel indices opened or reconfigured together.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+struct Indices {
+    list: [u8; MAX_ENHANCED_CHANNELS],
+    len: u8,
+}
+
+impl Indices {
+    /// # Safety
+    /// `list` must be null or point to `n` bytes.
+    unsafe fn from_raw(n: u8, list: *const u8) -> Self {
+        let mut out = Indices::default();
+        if !list.is_null() {
+            let n = (n as usize).min(MAX_ENHANCED_CHANNELS);
+            out.list[..n].copy_from_slice(unsafe { core::slice::from_raw_parts(list, n) });
+            out.len = n as u8;
+        }
+        out
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        &self.list[..self.len as usize]
+    }
+}
+
+#[derive(Copy, Clone)]
+struct Reconf {
+    conn: u16,
+    local: CocConfig,
+    channels: Indices,
+}
+
+/// Peer parameters from a connection request or response.
+#[derive(Copy, Clone)]
+struct Peer {
+    mtu: u16,
+    mps: u16,
+    credits: u16,
+}
+
+static CHANS: Mutex<CriticalSectionRawMutex, RefCell<[Option<Chan>; MAX_CHANNELS]>> =
+    Mutex::new(RefCell::new([const { None }; MAX_CHANNELS]));
+static PENDING: Mutex<CriticalSectionRawMutex, RefCell<[Option<Pending>; MAX_CONNECTIONS]>> =
+    Mutex::new(RefCell::new([None; MAX_CONNECTIONS]));
+static CONFIRMED: [Signal<CriticalSectionRawMutex, Result<Indices>>; MAX_CONNECTIONS] =
+    [const { Signal::new() }; MAX_CONNECTIONS];
+static RECONF: Mutex<CriticalSectionRawMutex, RefCell<[Option<Reconf>; MAX_CONNECTIONS]>> =
+    Mutex::new(RefCell::new([None; MAX_CONNECTIONS]));
+static RECONFIGURED: [Signal<CriticalSectionRawMutex, Result<()>>; MAX_CONNECTIONS] =
+    [const { Signal::new() }; MAX_CONNECTIONS];
+static REQUESTS: Channel<CriticalSectionRawMutex, CocRequest, MAX_CHANNELS> = Channel::new();
+static RX_READY: [Signal<CriticalSectionRawMutex, ()>; MAX_CHANNELS] =
+    [const { Signal::new() }; MAX_CHANNELS];
+static CREDITS: [Signal<CriticalSectionRawMutex, ()>; MAX_CHANNELS] =
+    [const { Signal::new() }; MAX_CHANNELS];
+static TX_POOL: [Signal<CriticalSectionRawMutex, ()>; MAX_CHANNELS] =
+    [const { Signal::new() }; MAX_CHANNELS];
+static TX_LOCK: [AsyncMutex<CriticalSectionRawMutex, ()>; MAX_CHANNELS] =
+    [const { AsyncMutex::new(()) }; MAX_CHANNELS];
+
+fn slot_of(index: u8) -> Option<usize> {
+    CHANS.lock(|c| {
+        c.borrow()
+            .iter()
+            .position(|s| s.as_ref().is_some_and(|s| s.index == index))
+    })
+}
+
+/// Record a newly opened channel; false if the table is full.
+fn open(conn: u16, index: u8, enhanced: bool, local: CocConfig, peer: Peer) -> bool {
+    let slot = CHANS.lock(|c| {
+        let mut c = c.borrow_mut();
+        let i = c.iter().position(Option::is_none)?;
+        c[i] = Some(Chan::new(conn, index, enhanced, local, peer));
+        Some(i)
+    });
+    if let Some(i) = slot {
+        RX_READY[i].reset();
+        CREDITS[i].reset();
+    }
+    slot.is_some()
+}
+
+/// Link a tracked channel runs on.
+pub(crate) fn channel_connection(index: u8) -> Option<u16> {
+    CHANS.lock(|c| {
+        c.borrow()
+            .iter()
+            .flatten()
+            .find(|ch| ch.index == index)
+            .map(|ch| ch.conn)
+    })
+}
+
+fn close(slot: usize) {
+    CHANS.lock(|c| c.borrow_mut()[slot] = None);
+    RX_READY[slot].signal(());
+    CREDITS[slot].signal(());
+    TX_POOL[slot].signal(());
+}
+
+fn closed() -> BleStatus {
+    BleStatus::Other(status::UNKNOWN_CONNECTION)
+}
+
+/// Frees a per-link request entry if the future waiting for the peer's
+/// answer is dropped; `mem::forget` it once the answer has been taken.
+struct Waiting<T: 'static, R: 'static> {
+    table: &'static Mutex<CriticalSectionRawMutex, RefCell<[Option<T>; MAX_CONNECTIONS]>>,
+    answered: &'static Signal<CriticalSectionRawMutex, R>,
+    index: usize,
+}
+
+impl<T, R> Drop for Waiting<T, R> {
+    fn drop(&mut self) {
+        // Once signalled, the event handler has already taken the entry and
+        // the index may belong to a newer request.
+        self.table.lock(|t| {
+            if !self.answered.signaled() {
+                t.borrow_mut()[self.index] = None;
+            }
+        });
+    }
+}
+
+/// Incoming channel request, delivered by [`listen`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct CocRequest {
+    conn: u16,
+    spsm: u16,
+    peer_mtu: u16,
+    peer_mps: u16,
+    peer_credits: u16,
+    /// 0 for LE CoC, otherwise the number of enhanced channels requested.
+    channels: u8,
+}
+
+impl CocRequest {
+    /// Link the request arrived on.
+    pub fn connection(&self) -> Option<Connection> {
+        Connection::from_handle(self.conn)
+    }
+
+    /// Protocol/service multiplexer the peer is connecting to.
+    pub fn spsm(&self) -> u16 {
+        self.spsm
+    }
+
+    /// Largest SDU the peer accepts.
+    pub fn peer_mtu(&self) -> u16 {
+        self.peer_mtu
+    }
+
+    /// Whether this is an enhanced credit-based request.
+    pub fn is_enhanced(&self) -> bool {
+        self.channels != 0
+    }
+
+    /// Number of channels requested.
+    pub fn channels(&self) -> u8 {
+        self.channels.max(1)
+    }
+
+    /// Open the single requested channel with our receive parameters. Use
+    /// [`CocRequest::accept_group`] for enhanced requests of several channels.
+    pub fn accept(self, cfg: &CocConfig) -> Result<L2capChannel> {
+        if self.channels() > 1 {
+            return Err(BleStatus::Other(status::INVALID_PARAMS));
+        }
+        self.accept_group(cfg)?
+            .into_channels()
+            .next()
+            .ok_or(BleStatus::Other(status::INSUFFICIENT_RESOURCES))
+    }
+
+    /// Open every requested channel with our receive parameters.
+    pub fn accept_group(self, cfg: &CocConfig) -> Result<ChannelGroup> {
+        if !cfg.is_valid(self.is_enhanced()) {
+            return Err(BleStatus::Other(status::INVALID_PARAMS));
+        }
+        let mut number = 0u8;
+        let mut index = [0u8; MAX_ENHANCED_CHANNELS];
+        let rc = unsafe {
+            ffi::aci_l2cap_coc_connect_confirm(
+                self.conn,
+                cfg.mtu,
+                cfg.mps,
+                cfg.credits(),
+                result::SUCCESS,
+                &mut number,
+                index.as_mut_ptr(),
+            )
+        } as i32;
+        if BleStatus::from(rc) != BleStatus::Ok {
+            return Err(BleStatus::from(rc));
+        }
+        let peer = Peer {
+            mtu: self.peer_mtu,
+            mps: self.peer_mps,
+            credits: self.peer_credits,
+        };
+        let created = unsafe { Indices::from_raw(number, index.as_ptr()) };
+        let opened = open_all(self.conn, &created, self.is_enhanced(), *cfg, peer);
+        if opened.len == 0 {
+            return Err(BleStatus::Other(status::INSUFFICIENT_RESOURCES));
+        }
+        Ok(ChannelGroup { indices: opened })
+    }
+
+    /// Refuse the channel with an L2CAP [`result`] code.
+    pub fn reject(self, reason: u16) -> Result<()> {
+        let mut number = 0u8;
+        let mut index = [0u8; MAX_ENHANCED_CHANNELS];
+        let min = if self.is_enhanced() { ECFC_MIN_MTU } else { 23 };
+        let rc = unsafe {
+            ffi::aci_l2cap_coc_connect_confirm(
+                self.conn,
+                min,
+                min,
+                0,
+                reason,
+                &mut number,
+                index.as_mut_ptr(),
+            )
+        } as i32;
+        if BleStatus::from(rc) == BleStatus::Ok {
+            Ok(())
+        } else {
+            Err(BleStatus::from(rc))
+        }
+    }
+}
+
+/// Track `created` channels; any that do not fit in the table are closed.
+fn open_all(conn: u16, created: &Indices, enhanced: bool, local: CocConfig, peer: Peer) -> Indices {
+    let mut opened = Indices::default();
+    for &index in created.as_slice() {
+        if open(conn, index, enhanced, local, peer) {
+            opened.list[opened.len as usize] = index;
+            opened.len += 1;
+        } else {
+            unsafe { ffi::aci_l2cap_coc_disconnect(index) };
+        }
+    }
+    opened
+}
+
+/// Channels opened together by an enhanced credit-based request.
+#[derive(Debug, Eq, PartialEq)]
+pub struct ChannelGroup {
+    indices: Indices,
+}
+
+impl ChannelGroup {
+    /// Number of channels the peer accepted.
+    pub fn len(&self) -> usize {
+        self.indices.len as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.len == 0
+    }
+
+    /// Change our MTU/MPS on all channels of the group (see [`reconfigure`]).
+    pub async fn reconfigure(&self, cfg: &CocConfig) -> Result<()> {
+        reconfigure_indices(self.indices, cfg).await
+    }
+
+    /// Take ownership of the individual channels.
+    pub fn into_channels(self) -> impl Iterator<Item = L2capChannel> {
+        let Indices { list, len } = self.indices;
+        list.into_iter()
+            .take(len as usize)
+            .map(|index| L2capChannel { index })
+    }
+}
+
+/// Wait for the next channel request from a peer. Answer it promptly with
+/// [`CocRequest::accept`] or [`CocRequest::reject`].
+pub async fn listen() -> CocRequest {
+    REQUESTS.receive().await
+}
+
+/// An open credit-based channel, identified by the stack's channel index.
+#[derive(Debug, Eq, PartialEq)]
+pub struct L2capChannel {
+    index: u8,
+}
+
+impl L2capChannel {
+    /// Open a channel to `spsm` on `conn` and wait for the peer's answer.
+    pub async fn connect(conn: &Connection, spsm: u16, cfg: &CocConfig) -> Result<Self> {
+        open_channels(conn, spsm, cfg, 0)
+            .await?
+            .into_channels()
+            .next()
+            .ok_or(BleStatus::Other(status::INSUFFICIENT_RESOURCES))
+    }
+
+    /// Open up to [`MAX_ENHANCED_CHANNELS`] enhanced credit-based channels to
+    /// `spsm` in one request. The peer may accept fewer than `count`.
+    pub async fn connect_enhanced(
+        conn: &Connection,
+        spsm: u16,
+        cfg: &CocConfig,
+        count: u8,
+    ) -> Result<ChannelGroup> {
+        if count == 0 || count as usize > MAX_ENHANCED_CHANNELS {
+            return Err(BleStatus::Other(status::INVALID_PARAMS));
+        }
+        open_channels(conn, spsm, cfg, count).await
+    }
+
+    /// Channel index used by the stack.
+    pub fn index(&self) -> u8 {
+        self.index
+    }
+
+    /// Link the channel runs on.
+    pub fn connection(&self) -> Option<Connection> {
+        self.with(|c| c.conn).and_then(Connection::from_handle)
+    }
+
+    /// Whether the channel is still open.
+    pub fn is_open(&self) -> bool {
+        slot_of(self.index).is_some()
+    }
+
+    /// Largest SDU the peer accepts.
+    pub fn peer_mtu(&self) -> Option<u16> {
+        self.with(|c| c.peer_mtu)
+    }
+
+    /// Our current receive parameters.
+    pub fn config(&self) -> Option<CocConfig> {
+        self.with(|c| c.local)
+    }
+
+    /// Change our MTU/MPS on this enhanced channel (see [`reconfigure`]).
+    pub async fn reconfigure(&self, cfg: &CocConfig) -> Result<()> {
+        reconfigure(&[self], cfg).await
+    }
+
+    /// Credits the peer has currently granted us (one per K-frame).
+    pub fn tx_credits(&self) -> Option<u16> {
+        self.with(|c| c.tx_credits)
+    }
+
+    fn with<R>(&self, f: impl FnOnce(&Chan) -> R) -> Option<R> {
+        CHANS.lock(|c| {
+            c.borrow()
+                .iter()
+                .flatten()
+                .find(|s| s.index == self.index)
+                .map(f)
+        })
+    }
+
+    /// Send one SDU (≤ the peer's MTU), splitting it into K-frames and
+    /// waiting for credits and stack buffers as needed.
+    pub async fn send(&self, sdu: &[u8]) -> Result<()> {
+        let Some(slot) = slot_of(self.index) else {
+            return Err(closed());
+        };
+        let _tx = TX_LOCK[slot].lock().await;
+        let Some((peer_mtu, peer_mps)) = self.with(|c| (c.peer_mtu, c.peer_mps)) else {
+            return Err(closed());
+        };
+        if sdu.len() > peer_mtu as usize {
+            return Err(BleStatus::Other(status::INVALID_PARAMS));
+        }
+        let max_frame = (peer_mps as usize).min(MAX_TX_FRAME);
+
+        let mut frame = [0u8; MAX_TX_FRAME];
+        let mut off = 0;
+        let mut first = true;
+        while first || off < sdu.len() {
+            let mut len = 0;
+            if first {
+                frame[..SDU_LEN_FIELD].copy_from_slice(&(sdu.len() as u16).to_le_bytes());
+                len = SDU_LEN_FIELD;
+            }
+            let chunk = (max_frame - len).min(sdu.len() - off);
+            frame[len..len + chunk].copy_from_slice(&sdu[off..off + chunk]);
+            len += chunk;
+
+            self.send_frame(slot, &frame[..len]).await?;
+            off += chunk;
+            first = false;
+        }
+        Ok(())
+    }
+
+    async fn send_frame(&self, slot: usize, frame: &[u8]) -> Result<()> {
+        loop {
+            CREDITS[slot].reset();
+            match self.with(|c| c.tx_credits) {
+                None => return Err(closed()),
+                Some(0) => {
+                    CREDITS[slot].wait().await;
+                    continue;
+                }
+                Some(_) => {}
+            }
+            TX_POOL[slot].reset();
+            let rc = unsafe {
+                ffi::aci_l2cap_coc_tx_data(self.index, frame.len() as u16, frame.as_ptr())
+            } as i32;
+            if rc == status::INSUFFICIENT_RESOURCES {
+                // Stack buffers full; retry after aci_l2cap_coc_tx_pool_available_event.
+                TX_POOL[slot].wait().await;
+                continue;
+            }
+            if BleStatus::from(rc) != BleStatus::Ok {
+                return Err(BleStatus::from(rc));
+            }
+            CHANS.lock(|c| {
+                if let Some(ch) = c.borrow_mut()[slot].as_mut() {
+                    ch.tx_credits = ch.tx_credits.saturating_sub(1);
+                }
+            });
+            return Ok(());
+        }
+    }
+
+    /// Wait for the next SDU and copy it into `buf`; returns its length.
+    /// Credits for the space it occupied are returned to the peer.
+    pub async fn receive(&self, buf: &mut [u8]) -> Result<usize> {
+        let Some(slot) = slot_of(self.index) else {
+            return Err(closed());
+        };
+        loop {
+            RX_READY[slot].reset();
+            let taken = CHANS.lock(|c| {
+                let mut c = c.borrow_mut();
+                let Some(ch) = c[slot].as_mut().filter(|ch| ch.index == self.index) else {
+                    return Some(Err(closed()));
+                };
+                ch.take_sdu(buf)
+            });
+            match taken {
+                Some(Ok((n, 0))) => return Ok(n),
+                Some(Ok((n, credits))) => {
+                    let rc = unsafe { ffi::aci_l2cap_coc_flow_control(self.index, credits) } as i32;
+                    return if BleStatus::from(rc) == BleStatus::Ok {
+                        Ok(n)
+                    } else {
+                        Err(BleStatus::from(rc))
+                    };
+                }
+                Some(Err(e)) => return Err(e),
+                None => RX_READY[slot].wait().await,
+            }
+        }
+    }
+
+    /// Close the channel. Completion is reported by the stack; pending
+    /// `send`/`receive` calls then fail.
+    pub fn disconnect(self) -> Result<()> {
+        let rc = unsafe { ffi::aci_l2cap_coc_disconnect(self.index) } as i32;
+        if BleStatus::from(rc) == BleStatus::Ok {
+            Ok(())
+        } else {
+            Err(BleStatus::from(rc))
+        }
+    }
+}
+
+/// Send a connection request for `number` enhanced channels (0: one LE CoC).
+async fn open_channels(
+    conn: &Connection,
+    spsm: u16,
+    cfg: &CocConfig,
+    number: u8,
+) -> Result<ChannelGroup> {
+    if !cfg.is_valid(number != 0) {
+        return Err(BleStatus::Other(status::INVALID_PARAMS));
+    }
+    let handle = conn.handle();
+    let p = PENDING.lock(|p| {
+        let mut p = p.borrow_mut();
+        if p.iter().flatten().any(|e| e.conn == handle) {
+            return None;
+        }
+        let i = p.iter().position(Option::is_none)?;
+        p[i] = Some(Pending {
+            conn: handle,
+            local: *cfg,
+            enhanced: number != 0,
+        });
+        Some(i)
+    });
+    let Some(p) = p else {
+        return Err(BleStatus::Other(status::INSUFFICIENT_RESOURCES));
+    };
+    CONFIRMED[p].reset();
+
+    let rc = unsafe {
+        ffi::aci_l2cap_coc_connect(handle, spsm, cfg.mtu, cfg.mps, cfg.credits(), number)
+    } as i32;
+    if BleStatus::from(rc) != BleStatus::Ok {
+        PENDING.lock(|e| e.borrow_mut()[p] = None);
+        return Err(BleStatus::from(rc));
+    }
+    let waiting = Waiting {
+        table: &PENDING,
+        answered: &CONFIRMED[p],
+        index: p,
+    };
+    let indices = CONFIRMED[p].wait().await;
+    core::mem::forget(waiting);
+    Ok(ChannelGroup { indices: indices? })
+}
+
+/// Change our MTU/MPS on enhanced channels of one link and wait for the
+/// peer to confirm. The MTU may only grow; the MPS may only shrink when a
+/// single channel is reconfigured. Extra credits are granted for the larger
+/// SDUs once the peer agrees.
+pub async fn reconfigure(channels: &[&L2capChannel], cfg: &CocConfig) -> Result<()> {
+    if channels.is_empty() || channels.len() > MAX_ENHANCED_CHANNELS {
+        return Err(BleStatus::Other(status::INVALID_PARAMS));
+    }
+    let mut indices = Indices::default();
+    for ch in channels {
+        indices.list[indices.len as usize] = ch.index;
+        indices.len += 1;
+    }
+    reconfigure_indices(indices, cfg).await
+}
+
+async fn reconfigure_indices(indices: Indices, cfg: &CocConfig) -> Result<()> {
+    if !cfg.is_valid(true) {
+        return Err(BleStatus::Other(status::INVALID_PARAMS));
+    }
+    // All channels must be open, enhanced, on one link, and not shrink.
+    let conn = CHANS.lock(|c| {
+        let c = c.borrow();
+        let mut conn = None;
+        for &index in indices.as_slice() {
+            let ch = c.iter().flatten().find(|ch| ch.index == index)?;
+            let shrinks = cfg.mtu < ch.local.mtu || (indices.len > 1 && cfg.mps < ch.local.mps);
+            if !ch.enhanced || shrinks || conn.is_some_and(|h| h != ch.conn) {
+                return None;
+            }
+            conn = Some(ch.conn);
+        }
+        conn
+    });
+    let Some(conn) = conn else {
+        return Err(BleStatus::Other(status::INVALID_PARAMS));
+    };
+    let p = RECONF.lock(|r| {
+        let mut r = r.borrow_mut();
+        if r.iter().flatten().any(|e| e.conn == conn) {
+            return None;
+        }
+        let i = r.iter().position(Option::is_none)?;
+        r[i] = Some(Reconf {
+            conn,
+            local: *cfg,
+            channels: indices,
+        });
+        Some(i)
+    });
+    let Some(p) = p else {
+        return Err(BleStatus::Other(status::INSUFFICIENT_RESOURCES));
+    };
+    RECONFIGURED[p].reset();
+
+    let rc = unsafe {
+        ffi::aci_l2cap_coc_reconf(conn, cfg.mtu, cfg.mps, indices.len, indices.list.as_ptr())
+    } as i32;
+    if BleStatus::from(rc) != BleStatus::Ok {
+        RECONF.lock(|r| r.borrow_mut()[p] = None);
+        return Err(BleStatus::from(rc));
+    }
+    let waiting = Waiting {
+        table: &RECONF,
+        answered: &RECONFIGURED[p],
+        index: p,
+    };
+    let result = RECONFIGURED[p].wait().await;
+    core::mem::forget(waiting);
+    result
+}
+
+/// Drop channels and pending requests of a link that went down.
+pub(crate) fn on_link_closed(conn: u16) {
+    for slot in 0..MAX_CHANNELS {
+        let gone = CHANS.lock(|c| c.borrow()[slot].as_ref().is_some_and(|ch| ch.conn == conn));
+        if gone {
+            close(slot);
+        }
+    }
+    let pending = PENDING.lock(|p| {
+        let mut p = p.borrow_mut();
+        let i = p.iter().position(|e| e.is_some_and(|e| e.conn == conn))?;
+        p[i] = None;
+        Some(i)
+    });
+    if let Some(i) = pending {
+        CONFIRMED[i].signal(Err(closed()));
+    }
+    let reconf = RECONF.lock(|r| {
+        let mut r = r.borrow_mut();
+        let i = r.iter().position(|e| e.is_some_and(|e| e.conn == conn))?;
+        r[i] = None;
+        Some(i)
+    });
+    if let Some(i) = reconf {
+        RECONFIGURED[i].signal(Err(closed()));
+    }
+}
+
+// ===== Stack event callbacks =====
+
+/// Channel request from the peer.
+#[unsafe(no_mangle)]
+pub extern "C" fn aci_l2cap_coc_connect_event(
+    connection_handle: u16,
+    spsm: u16,
+    mtu: u16,
+    mps: u16,
+    initial_credits: u16,
+    channel_number: u8,
+) {
+    let req = CocRequest {
+        conn: connection_handle,
+        spsm,
+        peer_mtu: mtu,
+        peer_mps: mps,
+        peer_credits: initial_credits,
+        channels: channel_number,
+    };
+    if REQUESTS.try_send(req).is_err() {
+        let _ = req.reject(result::NO_RESOURCES);
+    }
+}
+
+/// Peer's answer to [`L2capChannel::connect`] / [`L2capChannel::connect_enhanced`].
+///
+/// # Safety
+/// `channel_index_list` must point to `channel_number` bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn aci_l2cap_coc_connect_confirm_event(
+    connection_handle: u16,
+    mtu: u16,
+    mps: u16,
+    initial_credits: u16,
+    result: u16,
+    channel_number: u8,
+    channel_index_list: *const u8,
+) {
+    let pending = PENDING.lock(|p| {
+        let mut p = p.borrow_mut();
+        let i = p
+            .iter()
+            .position(|e| e.is_some_and(|e| e.conn == connection_handle))?;
+        p[i].take().map(|e| (i, e))
+    });
+    // Enhanced requests may be partially refused: a non-zero result then
+    // still comes with the channels that were opened.
+    let created = unsafe { Indices::from_raw(channel_number, channel_index_list) };
+    let Some((i, pending)) = pending else {
+        // The connecting future was dropped; nobody will use these channels.
+        for &index in created.as_slice() {
+            unsafe { ffi::aci_l2cap_coc_disconnect(index) };
+        }
+        return;
+    };
+    if created.len == 0 {
+        let code = if result != result::SUCCESS {
+            result as i32
+        } else {
+            status::INSUFFICIENT_RESOURCES
+        };
+        CONFIRMED[i].signal(Err(BleStatus::Other(code)));
+        return;
+    }
+    let peer = Peer {
+        mtu,
+        mps,
+        credits: initial_credits,
+    };
+    let opened = open_all(
+        connection_handle,
+        &created,
+        pending.enhanced,
+        pending.local,
+        peer,
+    );
+    if opened.len == 0 {
+        CONFIRMED[i].signal(Err(BleStatus::Other(status::INSUFFICIENT_RESOURCES)));
+    } else {
+        CONFIRMED[i].signal(Ok(opened));
+    }
+}
+
+/// The peer changes its MTU/MPS (our transmit limits) on enhanced channels.
+///
+/// # Safety
+/// `channel_index_list` must point to `channel_number` bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn aci_l2cap_coc_reconf_event(
+    connection_handle: u16,
+    mtu: u16,
+    mps: u16,
+    channel_number: u8,
+    channel_index_list: *const u8,
+) {
+    let channels = unsafe { Indices::from_raw(channel_number, channel_index_list) };
+    let outcome = CHANS.lock(|c| {
+        let mut c = c.borrow_mut();
+        for &index in channels.as_slice() {
+            let Some(ch) = c
+                .iter()
+                .flatten()
+                .find(|ch| ch.index == index && ch.conn == connection_handle)
+            else {
+                return result::RECONF_INVALID_CID;
+            };
+            if mtu < ch.peer_mtu {
+                return result::RECONF_MTU_REDUCTION;
+            }
+            if channels.len > 1 && mps < ch.peer_mps {
+                return result::RECONF_MPS_REDUCTION;
+            }
+        }
+        for ch in c.iter_mut().flatten() {
+            if channels.as_slice().contains(&ch.index) {
+                ch.peer_mtu = mtu;
+                ch.peer_mps = mps;
+            }
+        }
+        result::SUCCESS
+    });
+    unsafe { ffi::aci_l2cap_coc_reconf_confirm(connection_handle, outcome) };
+}
+
+/// Peer's answer to [`reconfigure`].
+#[unsafe(no_mangle)]
+pub extern "C" fn aci_l2cap_coc_reconf_confirm_event(connection_handle: u16, result: u16) {
+    let pending = RECONF.lock(|r| {
+        let mut r = r.borrow_mut();
+        let i = r
+            .iter()
+            .position(|e| e.is_some_and(|e| e.conn == connection_handle))?;
+        r[i].take().map(|e| (i, e))
+    });
+    let Some((i, reconf)) = pending else {
+        return;
+    };
+    if result != result::SUCCESS {
+        RECONFIGURED[i].signal(Err(BleStatus::Other(result as i32)));
+        return;
+    }
+    for &index in reconf.channels.as_slice() {
+        // Top the peer up so a full SDU of the new size fits in its credits.
+        let extra = CHANS.lock(|c| {
+            let mut c = c.borrow_mut();
+            let ch = c.iter_mut().flatten().find(|ch| ch.index == index)?;
+            ch.local = reconf.local;
+            Some(ch.top_up())
+        });
+        if let Some(extra @ 1..) = extra {
+            unsafe { ffi::aci_l2cap_coc_flow_control(index, extra) };
+        }
+    }
+    RECONFIGURED[i].signal(Ok(()));
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn aci_l2cap_coc_disconnect_event(channel_index: u8) {
+    if let Some(slot) = slot_of(channel_index) {
+        close(slot);
+    }
+}
+
+/// Credits granted by the peer.
+#[unsafe(no_mangle)]
+pub extern "C" fn aci_l2cap_coc_flow_control_event(channel_index: u8, credits: u16) {
+    let Some(slot) = slot_of(channel_index) else {
+        return;
+    };
+    CHANS.lock(|c| {
+        if let Some(ch) = c.borrow_mut()[slot].as_mut() {
+            ch.tx_credits = ch.tx_credits.saturating_add(credits);
+        }
+    });
+    CREDITS[slot].signal(());
+}
+
+/// One K-frame; the first of an SDU starts with the 2-byte SDU length.
+///
+/// # Safety
+/// `data` must point to `length` bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn aci_l2cap_coc_rx_data_event(
+    channel_index: u8,
+    length: u16,
+    data: *const u8,
+) {
+    let Some(slot) = slot_of(channel_index) else {
+        return;
+    };
+    let frame = if data.is_null() {
+        &[][..]
+    } else {
+        unsafe { core::slice::from_raw_parts(data, length as usize) }
+    };
+    let outcome = CHANS.lock(|c| {
+        c.borrow_mut()[slot]
+            .as_mut()
+            .map_or(Ok(false), |ch| ch.rx_frame(frame))
+    });
+    match outcome {
+        Ok(true) => RX_READY[slot].signal(()),
+        Ok(false) => {}
+        // Oversized SDU/K-frame or more data than credited: the channel must be closed.
+        Err(()) => unsafe {
+            ffi::aci_l2cap_coc_disconnect(channel_index);
+        },
+    }
+}
+
+/// The stack has buffers again after `aci_l2cap_coc_tx_data` ran out.
+#[unsafe(no_mangle)]
+pub extern "C" fn aci_l2cap_coc_tx_pool_available_event() {
+    for s in &TX_POOL {
+        s.signal(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PEER: Peer = Peer {
+        mtu: 23,
+        mps: 23,
+        credits: 1,
+    };
+
+    fn chan(mtu: u16, mps: u16) -> Chan {
+        Chan::new(1, 0x40, false, CocConfig { mtu, mps }, PEER)
+    }
+
+    /// K-frames of `sdu` as the peer would send them.
+    fn frames(sdu: &[u8], mps: usize) -> Vec<Vec<u8>> {
+        let mut k = (sdu.len() as u16).to_le_bytes().to_vec();
+        k.extend_from_slice(sdu);
+        k.chunks(mps).map(<[u8]>::to_vec).collect()
+    }
+
+    #[test]
+    fn config_credits_and_bounds() {
+        assert_eq!(CocConfig { mtu: 23, mps: 23 }.credits(), 2);
+        assert_eq!(CocConfig { mtu: 21, mps: 23 }.credits(), 1);
+        assert_eq!(CocConfig::default().credits(), 3);
+        assert!(CocConfig::default().is_valid(true));
+        assert!(CocConfig { mtu: 23, mps: 23 }.is_valid(false));
+        assert!(!CocConfig { mtu: 23, mps: 23 }.is_valid(true));
+        assert!(!CocConfig { mtu: 22, mps: 23 }.is_valid(false));
+        assert!(
+            !CocConfig {
+                mtu: MAX_SDU as u16 + 1,
+                mps: 248
+            }
+            .is_valid(false)
+        );
+        // Two 513-byte credits would overrun the receive buffer.
+        assert!(!CocConfig { mtu: 512, mps: 513 }.is_valid(false));
+    }
+
+    #[test]
+    fn reassembles_multi_frame_sdus() {
+        let mut ch = chan(100, 23);
+        let sdu: Vec<u8> = (0..60).collect();
+        let k = frames(&sdu, 23);
+        assert_eq!(k.len(), 3);
+        assert_eq!(ch.rx_frame(&k[0]), Ok(false));
+        assert_eq!(ch.rx_frame(&k[1]), Ok(false));
+        let mut buf = [0u8; 100];
+        assert!(ch.take_sdu(&mut buf).is_none());
+        assert_eq!(ch.rx_frame(&k[2]), Ok(true));
+        let (n, _) = ch.take_sdu(&mut buf).unwrap().unwrap();
+        assert_eq!(&buf[..n], &sdu[..]);
+    }
+
+    #[test]
+    fn queue_wraps_around_the_ring() {
+        let mut ch = chan(512, 248);
+        let mut buf = [0u8; MAX_SDU];
+        for round in 0..8u8 {
+            let sdu: Vec<u8> = (0..500).map(|i| (i as u8).wrapping_add(round)).collect();
+            for k in frames(&sdu, 248) {
+                ch.rx_frame(&k).unwrap();
+            }
+            let (n, _) = ch.take_sdu(&mut buf).unwrap().unwrap();
+            assert_eq!(&buf[..n], &sdu[..]);
+        }
+        assert_ne!(ch.rx_head, 0);
+        assert_eq!(ch.rx_len, 0);
+    }
+
+    #[test]
+    fn credits_are_returned_on_receive() {
+        let mut ch = chan(100, 23);
+        assert_eq!(ch.rx_credits, 5);
+        let mut buf = [0u8; 100];
+        for sdu in [[1u8; 10], [2u8; 10]] {
+            for k in frames(&sdu, 23) {
+                assert_eq!(ch.rx_frame(&k), Ok(true));
+            }
+        }
+        assert_eq!(ch.rx_credits, 3);
+        // Both SDUs fit in the buffer, so the first take refills everything.
+        assert_eq!(ch.take_sdu(&mut buf).unwrap(), Ok((10, 2)));
+        assert_eq!(ch.rx_credits, 5);
+        assert_eq!(buf[..10], [1; 10]);
+        assert_eq!(ch.take_sdu(&mut buf).unwrap(), Ok((10, 0)));
+        assert_eq!(buf[..10], [2; 10]);
+        assert_eq!(
+            ch.take_sdu(&mut [0u8; 4]),
+            None,
+            "queue drained, nothing left to take"
+        );
+    }
+
+    #[test]
+    fn protocol_violations_are_rejected() {
+        // K-frame larger than our MPS.
+        assert_eq!(chan(100, 23).rx_frame(&[0; 24]), Err(()));
+        // SDU length above our MTU.
+        assert_eq!(chan(100, 23).rx_frame(&101u16.to_le_bytes()), Err(()));
+        // More data than the announced SDU length.
+        let mut ch = chan(100, 23);
+        assert_eq!(ch.rx_frame(&[4, 0, 1, 2]), Ok(false));
+        assert_eq!(ch.rx_frame(&[3, 4, 5]), Err(()));
+        // A frame without credits.
+        let mut ch = chan(23, 23);
+        ch.rx_credits = 0;
+        assert_eq!(ch.rx_frame(&[1, 0, 7]), Err(()));
+        // First frame too short to carry the SDU length.
+        assert_eq!(chan(100, 23).rx_frame(&[1]), Err(()));
+    }
+}
@@ -1,15 +1,64 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 // st_wba_ble/src/lib.rs
 #[cfg(feature = "rust-shims")]
 mod c_shims;
 use st_wba_ble_sys::ffi;
 
 // Only include modules that actually exist and are meant to build.
+pub mod addr;
 pub mod adv;
+pub mod bond;
+pub mod config;
+pub mod conn;
+#[cfg(feature = "rust-crypto")]
+pub mod crypto;
 pub mod evt;
+pub mod filter;
 pub mod gatt;
-pub use adv::{set_adv_data, start_fast_name, stop as adv_stop};
-pub use gatt::{Char, Service, add_char, add_primary_service, update_char_value};
+pub mod l2cap;
+#[cfg(feature = "rust-mm")]
+pub mod mm;
+#[cfg(feature = "rust-nvm")]
+pub mod nvm;
+#[cfg(feature = "rust-platform")]
+pub mod platform;
+#[cfg(feature = "low-power")]
+pub mod power;
+pub mod privacy;
+#[cfg(any(
+    feature = "profile-battery",
+    feature = "profile-dis",
+    feature = "profile-hid",
+    feature = "profile-heart-rate",
+    feature = "profile-thermometer",
+    feature = "profile-ess",
+    feature = "profile-cts",
+    feature = "profile-find-me",
+    feature = "profile-uart"
+))]
+pub mod profiles;
+#[cfg(feature = "rust-rng")]
+pub mod rng;
+pub mod runner;
+pub mod scan;
+pub mod security;
+pub mod stack;
+#[cfg(feature = "rust-timer")]
+pub mod timer;
+pub use addr::{Address, AddressType, OwnAddress};
+pub use adv::{
+    AdvFilterPolicy, AdvParams, set_adv_data, start_fast_name, start_name, stop as adv_stop,
+};
+pub use bond::BondStore;
+pub use config::{BleConfig, Roles};
+pub use conn::Connection;
+pub use filter::FilterAcceptList;
+pub use gatt::{Bearer, Char, Service, add_char, add_primary_service, update_char_value};
+pub use l2cap::{CocConfig, L2capChannel};
+pub use privacy::Privacy;
+pub use runner::StackRunner;
+pub use security::Security;
+pub use stack::StackConfig;
 
 /// Lightweight status mapping for ACI return codes.
 pub mod status {
@@ -28,50 +77,136 @@ pub mod status {
         }
     }
     pub type Result<T> = core::result::Result<T, BleStatus>;
+
+    /// HCI "Unknown Connection Identifier".
+    pub(crate) const UNKNOWN_CONNECTION: i32 = 0x02;
+    /// HCI "Invalid HCI Command Parameters".
+    pub(crate) const INVALID_PARAMS: i32 = 0x12;
+    /// `BLE_STATUS_FAILED`.
+    pub(crate) const FAILED: i32 = 0x41;
+    /// `BLE_STATUS_BUSY`.
+    pub(crate) const BUSY: i32 = 0x43;
+    /// `BLE_STATUS_INSUFFICIENT_RESOURCES`.
+    pub(crate) const INSUFFICIENT_RESOURCES: i32 = 0x64;
+
+    #[cfg(feature = "embedded-io")]
+    impl embedded_io_async::Error for BleStatus {
+        fn kind(&self) -> embedded_io_async::ErrorKind {
+            use embedded_io_async::ErrorKind;
+            match self {
+                BleStatus::Other(UNKNOWN_CONNECTION) => ErrorKind::NotConnected,
+                BleStatus::Other(INSUFFICIENT_RESOURCES) => ErrorKind::OutOfMemory,
+                _ => ErrorKind::Other,
+            }
+        }
+    }
 }
 
+/// Handle to an initialised stack, returned by [`BleConfig::init`].
 pub struct Ble {
-    _priv: (),
+    pub(crate) gap_service: u16,
+    pub(crate) device_name_handle: u16,
+    pub(crate) appearance_handle: u16,
+    pub(crate) device_name_max_len: u8,
+    pub(crate) mtu: u16,
+    pub(crate) attribute_table_size: u16,
 }
 
 impl Ble {
     /// Initialize the BLE stack for a GAP Peripheral role and optionally set the device name.
+    ///
+    /// Shorthand for `BleConfig::new().device_name(dev_name, 0).init()`.
     pub fn init_peripheral(dev_name: &str) -> status::Result<Self> {
-        unsafe {
-            // 1) GATT init
-            let rc = ffi::aci_gatt_init() as i32;
-            if status::BleStatus::from(rc) != status::BleStatus::Ok {
-                return Err(status::BleStatus::from(rc));
-            }
+        BleConfig::new().device_name(dev_name, 0).init()
+    }
 
-            // 2) GAP init (Peripheral role, no privacy)
-            let (mut svc, mut name_h, mut app_h) = (0u16, 0u16, 0u16);
-            let rc = ffi::aci_gap_init(
-                0x01, // GAP Peripheral role (replace with ffi::GAP_PERIPHERAL_ROLE if present)
-                0,    // privacy off
-                dev_name.len() as u8,
-                &mut svc,
-                &mut name_h,
-                &mut app_h,
-            ) as i32;
-            if status::BleStatus::from(rc) != status::BleStatus::Ok {
-                return Err(status::BleStatus::from(rc));
-            }
+    /// Like [`Ble::init_peripheral`], with host or controller privacy enabled.
+    pub fn init_peripheral_with_privacy(dev_name: &str, privacy: Privacy) -> status::Result<Self> {
+        BleConfig::new()
+            .device_name(dev_name, 0)
+            .privacy(privacy)
+            .init()
+    }
 
-            // 3) Optional: update the Device Name characteristic value
-            if !dev_name.is_empty() {
-                let rc = ffi::aci_gatt_update_char_value(
-                    svc,
-                    name_h,
-                    0u8,                  // offset
-                    dev_name.len() as u8, // length
-                    dev_name.as_ptr(),    // value pointer
-                ) as i32;
-                if status::BleStatus::from(rc) != status::BleStatus::Ok {
-                    return Err(status::BleStatus::from(rc));
-                }
-            }
+    /// GAP service handle.
+    pub fn gap_service(&self) -> u16 {
+        self.gap_service
+    }
+
+    /// Device Name characteristic handle.
+    pub fn device_name_handle(&self) -> u16 {
+        self.device_name_handle
+    }
+
+    /// Appearance characteristic handle.
+    pub fn appearance_handle(&self) -> u16 {
+        self.appearance_handle
+    }
+
+    /// Change the Device Name (up to the length reserved at init).
+    pub fn set_device_name(&self, name: &str) -> status::Result<()> {
+        if name.len() > self.device_name_max_len as usize {
+            return Err(status::BleStatus::Other(status::INVALID_PARAMS));
+        }
+        let rc = unsafe {
+            ffi::aci_gatt_update_char_value(
+                self.gap_service,
+                self.device_name_handle,
+                0,
+                name.len() as u8,
+                name.as_ptr(),
+            )
+        } as i32;
+        if status::BleStatus::from(rc) == status::BleStatus::Ok {
+            Ok(())
+        } else {
+            Err(status::BleStatus::from(rc))
+        }
+    }
+
+    /// Change the Appearance value.
+    pub fn set_appearance(&self, appearance: u16) -> status::Result<()> {
+        let val = appearance.to_le_bytes();
+        let rc = unsafe {
+            ffi::aci_gatt_update_char_value(
+                self.gap_service,
+                self.appearance_handle,
+                0,
+                2,
+                val.as_ptr(),
+            )
+        } as i32;
+        if status::BleStatus::from(rc) == status::BleStatus::Ok {
+            Ok(())
+        } else {
+            Err(status::BleStatus::from(rc))
         }
-        Ok(Ble { _priv: () })
+    }
+
+    /// ATT MTU requested at init.
+    pub fn mtu(&self) -> u16 {
+        self.mtu
+    }
+
+    /// Attribute records reserved for application services at init.
+    pub fn attribute_table_size(&self) -> u16 {
+        self.attribute_table_size
+    }
+
+    /// Access the bonding database.
+    pub fn bonds(&self) -> BondStore {
+        BondStore::new()
+    }
+
+    /// Usage of the stack's dynamic memory pool, for sizing it.
+    #[cfg(feature = "rust-mm")]
+    pub fn memory_stats(&self) -> mm::MemoryStats {
+        mm::stats()
+    }
+
+    /// Whether the radio allows Stop mode now (see [`power::policy`]).
+    #[cfg(feature = "low-power")]
+    pub fn can_enter_stop(&self) -> bool {
+        power::can_enter_stop()
     }
 }
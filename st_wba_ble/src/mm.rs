@@ -0,0 +1,559 @@
+// st_wba_ble/src/mm.rs
+//! Memory manager for the stack's dynamic buffers (enabled with the `rust-mm`
+//! feature).
+//!
+//! Replaces ST's `stm32_mm.c` (`UTIL_MM_*`) and `advanced_memory_manager.c`
+//! (`AMM_*`) with a first-fit allocator over one pool. Virtual memories
+//! reserve part of the pool for a given user (AMM semantics); allocations
+//! that fail can register a callback which [`crate::StackRunner`] invokes
+//! once memory is freed. Usage is visible through [`Ble::memory_stats`].
+//!
+//! No memory is reserved by the crate: allocations fail until [`init`] (or
+//! `AMM_Init`/`UTIL_MM_Init`) provides a pool, e.g. one from [`mm_pool!`].
+//!
+//! ```ignore
+//! st_wba_ble::mm::init(st_wba_ble::mm_pool!(1024), &[]).unwrap();
+//! ```
+//!
+//! [`Ble::memory_stats`]: crate::Ble::memory_stats
+
+#![allow(non_snake_case)]
+
+use core::cell::RefCell;
+use core::ffi::c_void;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+
+/// Virtual memories that can be configured.
+pub const MAX_VIRTUAL_MEMORIES: usize = 8;
+/// Failed `AMM_Alloc` callers that can wait for memory at once.
+pub const MAX_PENDING_CALLBACKS: usize = 8;
+
+// Block header: USED flag, owner id, size in words (header included).
+const USED: u32 = 1 << 31;
+const ID_SHIFT: u32 = 24;
+const ID_MASK: u32 = 0x7F << ID_SHIFT;
+const SIZE_MASK: u32 = (1 << ID_SHIFT) - 1;
+/// Owner id of `UTIL_MM_GetBuffer` allocations (no reservation).
+const BASIC_ID: u8 = 0x7F;
+
+/// Memory manager errors (`AMM_Function_Error_t`).
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u32)]
+pub enum MmError {
+    Nok = 0x01,
+    BadPoolConfig = 0x02,
+    BadVirtualMemoryId = 0x03,
+    BadPointer = 0x04,
+    BadAllocationSize = 0x05,
+    NotAligned = 0x06,
+}
+
+/// A share of the pool reserved for one user.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(C)]
+pub struct VirtualMemory {
+    pub id: u8,
+    /// Reserved size in 32-bit words.
+    pub reserved_words: u32,
+}
+
+/// Pool usage, in bytes (block headers included).
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct MemoryStats {
+    pub pool_size: usize,
+    pub used: usize,
+    /// High-water mark of `used` since init.
+    pub peak: usize,
+    /// Largest block that could be allocated right now.
+    pub largest_free: usize,
+    pub allocations: u32,
+    pub failures: u32,
+}
+
+#[derive(Copy, Clone)]
+struct Vm {
+    id: u8,
+    reserved: usize,
+    used: usize,
+}
+
+/// `AMM_VirtualMemoryCallbackFunction_t`: the caller-owned list node whose
+/// `Callback` runs when memory becomes available.
+#[repr(C)]
+pub struct AmmCallbackHeader {
+    pub next: *mut c_void,
+    pub callback: Option<unsafe extern "C" fn()>,
+}
+
+struct Pool {
+    mem: &'static mut [u32],
+    vms: [Option<Vm>; MAX_VIRTUAL_MEMORIES],
+    pending: [Option<*mut AmmCallbackHeader>; MAX_PENDING_CALLBACKS],
+    retry: bool,
+    used: usize,
+    peak: usize,
+    allocations: u32,
+    failures: u32,
+}
+
+// The raw callback pointers are only touched inside the critical section.
+unsafe impl Send for Pool {}
+
+static POOL: Mutex<CriticalSectionRawMutex, RefCell<Option<Pool>>> = Mutex::new(RefCell::new(None));
+
+impl Pool {
+    fn new(mem: &'static mut [u32]) -> Self {
+        let words = mem.len().min(SIZE_MASK as usize);
+        if words > 0 {
+            mem[0] = words as u32;
+        }
+        Pool {
+            mem: &mut mem[..words],
+            vms: [None; MAX_VIRTUAL_MEMORIES],
+            pending: [None; MAX_PENDING_CALLBACKS],
+            retry: false,
+            used: 0,
+            peak: 0,
+            allocations: 0,
+            failures: 0,
+        }
+    }
+
+    /// Header and size of the block at `i`; `None` if the header is corrupt
+    /// (zero size or running past the pool).
+    fn block(&self, i: usize) -> Option<(u32, usize)> {
+        let h = self.mem[i];
+        let size = (h & SIZE_MASK) as usize;
+        (size != 0 && size <= self.mem.len() - i).then_some((h, size))
+    }
+
+    fn vm(&mut self, id: u8) -> Option<&mut Vm> {
+        self.vms.iter_mut().flatten().find(|v| v.id == id)
+    }
+
+    /// Words other virtual memories still have a claim on.
+    fn reserved_for_others(&self, id: u8) -> usize {
+        self.vms
+            .iter()
+            .flatten()
+            .filter(|v| v.id != id)
+            .map(|v| v.reserved.saturating_sub(v.used))
+            .sum()
+    }
+
+    /// First fit; merges free neighbours while scanning. Returns the payload index.
+    fn alloc(&mut self, words: usize, id: u8) -> Option<usize> {
+        let need = words.checked_add(1)?;
+        let free = self.mem.len() - self.used;
+        if words == 0 || need > free.saturating_sub(self.reserved_for_others(id)) {
+            return None;
+        }
+        let len = self.mem.len();
+        let mut i = 0;
+        while i < len {
+            let (h, mut size) = self.block(i)?;
+            if h & USED == 0 {
+                while i + size < len {
+                    match self.block(i + size) {
+                        Some((n, s)) if n & USED == 0 => size += s,
+                        _ => break,
+                    }
+                }
+                self.mem[i] = size as u32;
+                if size >= need {
+                    // Split unless the remainder could not hold a one-word block.
+                    let take = if size - need >= 2 { need } else { size };
+                    if take < size {
+                        self.mem[i + take] = (size - take) as u32;
+                    }
+                    self.mem[i] = USED | ((id as u32) << ID_SHIFT) | take as u32;
+                    self.used += take;
+                    self.peak = self.peak.max(self.used);
+                    if let Some(vm) = self.vm(id) {
+                        vm.used += take;
+                    }
+                    return Some(i + 1);
+                }
+            }
+            i += size;
+        }
+        None
+    }
+
+    fn free(&mut self, payload: usize) -> Result<(), MmError> {
+        // Walk the block chain so only real block starts are accepted.
+        let mut i = 0;
+        while i + 1 < payload {
+            i += self.block(i).ok_or(MmError::BadPointer)?.1;
+        }
+        let (h, size) = self
+            .block(i)
+            .filter(|&(h, _)| i + 1 == payload && h & USED != 0)
+            .ok_or(MmError::BadPointer)?;
+        let id = ((h & ID_MASK) >> ID_SHIFT) as u8;
+        self.mem[i] = size as u32;
+        self.used -= size;
+        if let Some(vm) = self.vm(id) {
+            vm.used -= size;
+        }
+        if self.pending.iter().any(Option::is_some) {
+            self.retry = true;
+        }
+        Ok(())
+    }
+
+    fn index_of(&self, p: *const c_void) -> Result<usize, MmError> {
+        let base = self.mem.as_ptr() as usize;
+        let addr = p as usize;
+        if addr < base + 4 || addr >= base + 4 * self.mem.len() {
+            return Err(MmError::BadPointer);
+        }
+        if !(addr - base).is_multiple_of(4) {
+            return Err(MmError::NotAligned);
+        }
+        Ok((addr - base) / 4)
+    }
+
+    fn largest_free(&self) -> usize {
+        let mut best = 0;
+        let mut run = 0;
+        let mut i = 0;
+        while i < self.mem.len() {
+            let Some((h, size)) = self.block(i) else {
+                break;
+            };
+            if h & USED == 0 {
+                run += size;
+                best = best.max(run);
+            } else {
+                run = 0;
+            }
+            i += size;
+        }
+        best.saturating_sub(1)
+    }
+}
+
+/// Run `f` on the pool; `None` before [`init`].
+fn with_pool<R>(f: impl FnOnce(&mut Pool) -> R) -> Option<R> {
+    POOL.lock(|p| p.borrow_mut().as_mut().map(f))
+}
+
+/// Use `pool` for all allocations and set up the virtual memories; call
+/// before the stack starts.
+pub fn init(pool: &'static mut [u32], vms: &[VirtualMemory]) -> Result<(), MmError> {
+    let reserved: usize = vms.iter().map(|v| v.reserved_words as usize).sum();
+    if vms.len() > MAX_VIRTUAL_MEMORIES || reserved > pool.len() || pool.len() < 2 {
+        return Err(MmError::BadPoolConfig);
+    }
+    let mut p = Pool::new(pool);
+    for (slot, vm) in p.vms.iter_mut().zip(vms) {
+        if vm.id >= BASIC_ID {
+            return Err(MmError::BadVirtualMemoryId);
+        }
+        *slot = Some(Vm {
+            id: vm.id,
+            reserved: vm.reserved_words as usize,
+            used: 0,
+        });
+    }
+    POOL.lock(|c| *c.borrow_mut() = Some(p));
+    Ok(())
+}
+
+/// Reserve a static pool of `words` 32-bit words for [`init`].
+///
+/// Evaluates to a `&'static mut [u32]`; panics if the same invocation is
+/// evaluated twice.
+#[macro_export]
+macro_rules! mm_pool {
+    ($words:expr) => {{
+        const WORDS: usize = $words;
+        static mut POOL: [u32; WORDS] = [0; WORDS];
+        static TAKEN: ::core::sync::atomic::AtomicBool =
+            ::core::sync::atomic::AtomicBool::new(false);
+        assert!(
+            !TAKEN.swap(true, ::core::sync::atomic::Ordering::AcqRel),
+            "mm_pool! evaluated twice"
+        );
+        // SAFETY: guarded by TAKEN, so the static is borrowed exactly once.
+        unsafe { &mut *::core::ptr::addr_of_mut!(POOL) as &'static mut [u32] }
+    }};
+}
+
+/// Current pool usage; all zero before [`init`].
+pub fn stats() -> MemoryStats {
+    with_pool(|p| MemoryStats {
+        pool_size: 4 * p.mem.len(),
+        used: 4 * p.used,
+        peak: 4 * p.peak,
+        largest_free: 4 * p.largest_free(),
+        allocations: p.allocations,
+        failures: p.failures,
+    })
+    .unwrap_or_default()
+}
+
+/// Allocate `words` 32-bit words for virtual memory `id`.
+pub fn alloc(id: u8, words: usize) -> Option<*mut u32> {
+    with_pool(|p| {
+        if id != BASIC_ID && p.vm(id).is_none() {
+            return None;
+        }
+        match p.alloc(words, id) {
+            Some(i) => {
+                p.allocations += 1;
+                Some(&mut p.mem[i] as *mut u32)
+            }
+            None => {
+                p.failures += 1;
+                None
+            }
+        }
+    })
+    .flatten()
+}
+
+/// Return a block obtained from [`alloc`].
+pub fn free(ptr: *mut u32) -> Result<(), MmError> {
+    let retry = with_pool(|p| {
+        let i = p.index_of(ptr as *const c_void)?;
+        p.free(i)?;
+        Ok(p.retry)
+    })
+    .unwrap_or(Err(MmError::BadPointer))?;
+    if retry {
+        crate::runner::schedule();
+    }
+    Ok(())
+}
+
+/// Run callbacks of allocations that failed earlier; called from [`crate::StackRunner`].
+pub(crate) fn process() {
+    let ready = with_pool(|p| core::mem::replace(&mut p.retry, false));
+    if ready != Some(true) {
+        return;
+    }
+    while let Some(cb) = with_pool(|p| p.pending.iter_mut().find_map(Option::take)).flatten() {
+        if let Some(f) = unsafe { (*cb).callback } {
+            unsafe { f() };
+        }
+    }
+}
+
+// ===== stm32_mm.h =====
+
+/// # Safety
+/// `p_pool` must be a word-aligned, `'static` buffer of `pool_size` bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn UTIL_MM_Init(p_pool: *mut u8, pool_size: u32) {
+    if p_pool.is_null() || !(p_pool as usize).is_multiple_of(4) {
+        return;
+    }
+    let mem =
+        unsafe { core::slice::from_raw_parts_mut(p_pool as *mut u32, pool_size as usize / 4) };
+    let _ = init(mem, &[]);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn UTIL_MM_GetBuffer(buffer_size: u32) -> *mut c_void {
+    alloc(BASIC_ID, (buffer_size as usize).div_ceil(4))
+        .map_or(core::ptr::null_mut(), |p| p as *mut c_void)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn UTIL_MM_ReleaseBuffer(p_buffer: *mut c_void) {
+    let _ = free(p_buffer as *mut u32);
+}
+
+// ===== advanced_memory_manager.h =====
+
+/// `AMM_InitParameters_t`; sizes in 32-bit words.
+#[repr(C)]
+pub struct AmmInitParameters {
+    pub p_pool_addr: *mut u32,
+    pub pool_size: u32,
+    pub virtual_memory_number: u32,
+    pub p_virtual_memory_config_list: *const VirtualMemory,
+}
+
+const AMM_OK: u32 = 0;
+
+fn amm_result(r: Result<(), MmError>) -> u32 {
+    r.map_or_else(|e| e as u32, |()| AMM_OK)
+}
+
+/// # Safety
+/// `p_init_params` must describe a `'static` pool and a valid config list.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn AMM_Init(p_init_params: *const AmmInitParameters) -> u32 {
+    let Some(params) = (unsafe { p_init_params.as_ref() }) else {
+        return MmError::BadPoolConfig as u32;
+    };
+    if params.p_pool_addr.is_null() {
+        return MmError::BadPoolConfig as u32;
+    }
+    let mem =
+        unsafe { core::slice::from_raw_parts_mut(params.p_pool_addr, params.pool_size as usize) };
+    let vms = if params.virtual_memory_number == 0 || params.p_virtual_memory_config_list.is_null()
+    {
+        &[][..]
+    } else {
+        unsafe {
+            core::slice::from_raw_parts(
+                params.p_virtual_memory_config_list,
+                params.virtual_memory_number as usize,
+            )
+        }
+    };
+    amm_result(init(mem, vms))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn AMM_DeInit() -> u32 {
+    POOL.lock(|p| *p.borrow_mut() = None);
+    AMM_OK
+}
+
+/// Allocate `buffer_size` words for virtual memory `virtual_memory_id`. On
+/// failure `p_callback` (if any) is called once memory has been freed.
+///
+/// # Safety
+/// `pp_alloc_buffer` must be writable; `p_callback` must stay valid until called.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn AMM_Alloc(
+    virtual_memory_id: u8,
+    buffer_size: u32,
+    pp_alloc_buffer: *mut *mut u32,
+    p_callback: *mut AmmCallbackHeader,
+) -> u32 {
+    if pp_alloc_buffer.is_null() {
+        return MmError::BadPointer as u32;
+    }
+    if buffer_size == 0 {
+        return MmError::BadAllocationSize as u32;
+    }
+    if with_pool(|p| p.vm(virtual_memory_id).is_some()) != Some(true) {
+        return MmError::BadVirtualMemoryId as u32;
+    }
+    match alloc(virtual_memory_id, buffer_size as usize) {
+        Some(ptr) => {
+            unsafe { *pp_alloc_buffer = ptr };
+            AMM_OK
+        }
+        None => {
+            unsafe { *pp_alloc_buffer = core::ptr::null_mut() };
+            if !p_callback.is_null() {
+                with_pool(|p| {
+                    let queued = p.pending.contains(&Some(p_callback));
+                    if let (false, Some(slot)) =
+                        (queued, p.pending.iter_mut().find(|c| c.is_none()))
+                    {
+                        *slot = Some(p_callback);
+                    }
+                });
+            }
+            MmError::Nok as u32
+        }
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn AMM_Free(p_buffer_addr: *mut u32) -> u32 {
+    amm_result(free(p_buffer_addr))
+}
+
+/// Run pending retry callbacks (normally done by [`crate::StackRunner`]).
+#[unsafe(no_mangle)]
+pub extern "C" fn AMM_BackgroundProcess() {
+    process();
+}
+
+/// This allocator is its own basic memory manager; registration is ignored.
+#[unsafe(no_mangle)]
+pub extern "C" fn AMM_RegisterBasicMemoryManager(_p_basic_memory_manager_functions: *const c_void) {
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(words: usize) -> Pool {
+        Pool::new(Box::leak(vec![0u32; words].into_boxed_slice()))
+    }
+
+    #[test]
+    fn first_fit_reuses_freed_blocks() {
+        let mut p = pool(32);
+        let a = p.alloc(4, BASIC_ID).unwrap();
+        let b = p.alloc(4, BASIC_ID).unwrap();
+        let c = p.alloc(4, BASIC_ID).unwrap();
+        assert_eq!((a, b, c), (1, 6, 11));
+        p.free(b).unwrap();
+        // The first hole that fits is taken, not the tail.
+        assert_eq!(p.alloc(3, BASIC_ID), Some(6));
+        p.free(a).unwrap();
+        p.free(6).unwrap();
+        // Neighbouring free blocks are merged on the next scan.
+        assert_eq!(p.alloc(9, BASIC_ID), Some(1));
+        assert_eq!(p.largest_free(), 32 - 10 - 5 - 1);
+    }
+
+    #[test]
+    fn rejects_bad_frees_and_oversized_allocations() {
+        let mut p = pool(16);
+        let a = p.alloc(4, BASIC_ID).unwrap();
+        assert_eq!(p.free(a + 1), Err(MmError::BadPointer));
+        p.free(a).unwrap();
+        assert_eq!(p.free(a), Err(MmError::BadPointer));
+        assert_eq!(p.alloc(16, BASIC_ID), None);
+        assert_eq!(p.alloc(0, BASIC_ID), None);
+        assert_eq!(p.alloc(usize::MAX, BASIC_ID), None);
+    }
+
+    #[test]
+    fn corrupt_header_stops_the_walk() {
+        let mut p = pool(16);
+        let a = p.alloc(2, BASIC_ID).unwrap();
+        p.mem[a + 2] = 0; // header of the free remainder
+        assert_eq!(p.alloc(4, BASIC_ID), None);
+        assert_eq!(p.largest_free(), 0);
+        assert_eq!(p.free(a + 3), Err(MmError::BadPointer));
+        p.mem[a + 2] = 64; // past the end of the pool
+        assert_eq!(p.alloc(4, BASIC_ID), None);
+    }
+
+    #[test]
+    fn reservations_hold_space_for_their_owner() {
+        let mut p = pool(16);
+        p.vms[0] = Some(Vm {
+            id: 1,
+            reserved: 8,
+            used: 0,
+        });
+        assert_eq!(p.alloc(8, BASIC_ID), None);
+        assert!(p.alloc(7, 1).is_some());
+        assert!(p.alloc(7, BASIC_ID).is_some());
+    }
+
+    #[test]
+    fn stats_track_usage_and_peak() {
+        assert_eq!(stats(), MemoryStats::default());
+        assert_eq!(alloc(BASIC_ID, 4), None);
+        init(crate::mm_pool!(64), &[]).unwrap();
+        let a = alloc(BASIC_ID, 9).unwrap();
+        let b = alloc(BASIC_ID, 4).unwrap();
+        free(a).unwrap();
+        let s = stats();
+        assert_eq!(s.pool_size, 256);
+        assert_eq!(s.used, 4 * 5);
+        assert_eq!(s.peak, 4 * 15);
+        assert_eq!(s.allocations, 2);
+        free(b).unwrap();
+        assert_eq!(alloc(BASIC_ID, 64), None);
+        let s = stats();
+        assert_eq!((s.used, s.peak, s.failures), (0, 4 * 15, 1));
+        assert_eq!(s.largest_free, 4 * 63);
+    }
+}
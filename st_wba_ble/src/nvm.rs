@@ -0,0 +1,645 @@
+// st_wba_ble/src/nvm.rs
+//! Persistent storage for bonding keys and GATT state (enabled with the
+//! `rust-nvm` feature).
+//!
+//! Implements the record store behind `BLEPLAT_NvmAdd`/`NvmGet`/`NvmCompare`/
+//! `NvmDiscard` (see [`crate::platform`]), replacing ST's `nvm.c`,
+//! `flash_manager.c` and `simple_nvm_arbiter.c`.
+//!
+//! Records live in a RAM image that is written back to flash by
+//! [`crate::StackRunner`] after every change. The flash region is split into
+//! slots (at least two, each a whole number of erase pages); every write goes
+//! to the next slot in turn, which spreads wear, and the slot header carrying
+//! a sequence number and CRC is written last, so an interrupted write leaves
+//! the previous image intact.
+//!
+//! Erasing and programming stall the CPU and flash bus, which must not
+//! overlap a radio event. Each erase and chunk write therefore starts only
+//! while [`radio_active`](crate::platform::radio_active) is false; if a radio
+//! event begins mid-write, the write is abandoned and restarted once the
+//! event ends. A single erase still runs to completion, so keep connection
+//! intervals longer than one page erase.
+//!
+//! ```ignore
+//! // Any embedded-storage NorFlash, e.g. a region of the internal flash.
+//! static FLASH: StaticCell<MyFlashRegion> = StaticCell::new();
+//! st_wba_ble::nvm::init(FLASH.init(region)).unwrap();
+//! ```
+
+use crate::platform::code::{EOF, FULL, NVM_ALL, NVM_CURRENT, NVM_FIRST, NVM_NEXT, OK};
+use core::cell::RefCell;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embedded_storage::nor_flash::{
+    ErrorType, NorFlash, NorFlashError, NorFlashErrorKind, ReadNorFlash,
+};
+
+/// Bytes of record data kept in RAM and written to each slot.
+pub const NVM_IMAGE_SIZE: usize = 2048;
+
+const HEADER_SIZE: usize = 16;
+const MAGIC: u32 = 0x314D_564E; // "NVM1"
+/// Flash I/O granularity; also keeps critical sections short.
+const CHUNK: usize = 256;
+/// Per-record header: type, reserved, little-endian data length.
+const RECORD_HEADER: usize = 4;
+
+/// Storage failures.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum NvmError {
+    /// The flash driver reported an error.
+    Flash,
+    /// The region holds fewer than two slots or has an unsupported write size.
+    Geometry,
+    /// [`init`] has not been called.
+    NotInitialised,
+    /// Another flush is running or a radio event is in progress; retry later.
+    Busy,
+}
+
+/// Object-safe view of an `embedded-storage` [`NorFlash`], so the store can
+/// hold any driver behind `&'static mut dyn NvmFlash`.
+pub trait NvmFlash: Send {
+    fn capacity(&self) -> usize;
+    fn erase_size(&self) -> usize;
+    fn write_size(&self) -> usize;
+    fn read(&mut self, offset: u32, buf: &mut [u8]) -> Result<(), NvmError>;
+    fn write(&mut self, offset: u32, buf: &[u8]) -> Result<(), NvmError>;
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), NvmError>;
+}
+
+impl<F: NorFlash + Send> NvmFlash for F {
+    fn capacity(&self) -> usize {
+        ReadNorFlash::capacity(self)
+    }
+
+    fn erase_size(&self) -> usize {
+        F::ERASE_SIZE
+    }
+
+    fn write_size(&self) -> usize {
+        F::WRITE_SIZE
+    }
+
+    fn read(&mut self, offset: u32, buf: &mut [u8]) -> Result<(), NvmError> {
+        ReadNorFlash::read(self, offset, buf).map_err(|_| NvmError::Flash)
+    }
+
+    fn write(&mut self, offset: u32, buf: &[u8]) -> Result<(), NvmError> {
+        NorFlash::write(self, offset, buf).map_err(|_| NvmError::Flash)
+    }
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), NvmError> {
+        NorFlash::erase(self, from, to).map_err(|_| NvmError::Flash)
+    }
+}
+
+// ===== RAM image =====
+
+#[derive(Copy, Clone)]
+enum Cursor {
+    None,
+    /// Positioned on the record at this offset.
+    At(usize),
+    /// The current record was discarded; the next one now starts here.
+    Before(usize),
+}
+
+struct Image {
+    buf: [u8; NVM_IMAGE_SIZE],
+    used: usize,
+    cursor: Cursor,
+    dirty: bool,
+}
+
+impl Image {
+    fn record(&self, off: usize) -> (u8, usize) {
+        let len = u16::from_le_bytes([self.buf[off + 2], self.buf[off + 3]]) as usize;
+        (self.buf[off], len)
+    }
+
+    fn record_size(&self, off: usize) -> usize {
+        (RECORD_HEADER + self.record(off).1).next_multiple_of(4)
+    }
+
+    fn find(&self, mut off: usize, type_: u8) -> Option<usize> {
+        while off < self.used {
+            if self.record(off).0 == type_ {
+                return Some(off);
+            }
+            off += self.record_size(off);
+        }
+        None
+    }
+
+    fn data(&self, off: usize) -> &[u8] {
+        let len = self.record(off).1;
+        &self.buf[off + RECORD_HEADER..off + RECORD_HEADER + len]
+    }
+
+    fn remove(&mut self, off: usize) {
+        let size = self.record_size(off);
+        self.buf.copy_within(off + size..self.used, off);
+        self.used -= size;
+        self.buf[self.used..].fill(0);
+    }
+}
+
+struct Store {
+    flash: Option<&'static mut dyn NvmFlash>,
+    slot_size: usize,
+    slots: usize,
+    /// Slot holding the latest image, if any.
+    slot: Option<usize>,
+    seq: u32,
+}
+
+static IMAGE: Mutex<CriticalSectionRawMutex, RefCell<Image>> = Mutex::new(RefCell::new(Image {
+    buf: [0; NVM_IMAGE_SIZE],
+    used: 0,
+    cursor: Cursor::None,
+    dirty: false,
+}));
+static STORE: Mutex<CriticalSectionRawMutex, RefCell<Store>> = Mutex::new(RefCell::new(Store {
+    flash: None,
+    slot_size: 0,
+    slots: 0,
+    slot: None,
+    seq: 0,
+}));
+
+// ===== Flash image management =====
+
+/// Attach the flash region and load the newest valid image from it; call
+/// before the stack starts. Without `init` records are kept in RAM only.
+pub fn init(flash: &'static mut dyn NvmFlash) -> Result<(), NvmError> {
+    let write = flash.write_size();
+    if write == 0 || !HEADER_SIZE.is_multiple_of(write) || !CHUNK.is_multiple_of(write) {
+        return Err(NvmError::Geometry);
+    }
+    let slot_size = (HEADER_SIZE + NVM_IMAGE_SIZE).next_multiple_of(flash.erase_size());
+    let slots = flash.capacity() / slot_size;
+    if slots < 2 {
+        return Err(NvmError::Geometry);
+    }
+
+    // Newest slot whose header and CRC check out.
+    let mut best: Option<(usize, u32, usize)> = None;
+    for slot in 0..slots {
+        let Some((seq, len)) = read_header(flash, slot * slot_size)? else {
+            continue;
+        };
+        if best.is_none_or(|(_, s, _)| (seq.wrapping_sub(s) as i32) > 0) {
+            best = Some((slot, seq, len));
+        }
+    }
+
+    if let Some((slot, _, len)) = best {
+        let base = (slot * slot_size + HEADER_SIZE) as u32;
+        let mut chunk = [0u8; CHUNK];
+        let mut off = 0;
+        while off < len {
+            let n = (len - off).min(CHUNK);
+            flash.read(base + off as u32, &mut chunk[..n])?;
+            IMAGE.lock(|img| img.borrow_mut().buf[off..off + n].copy_from_slice(&chunk[..n]));
+            off += n;
+        }
+    }
+    IMAGE.lock(|img| {
+        let mut img = img.borrow_mut();
+        img.used = best.map_or(0, |(_, _, len)| len);
+        let used = img.used;
+        img.buf[used..].fill(0);
+        img.cursor = Cursor::None;
+        img.dirty = false;
+    });
+    STORE.lock(|s| {
+        *s.borrow_mut() = Store {
+            flash: Some(flash),
+            slot_size,
+            slots,
+            slot: best.map(|(slot, _, _)| slot),
+            seq: best.map_or(0, |(_, seq, _)| seq),
+        }
+    });
+    Ok(())
+}
+
+/// Validate a slot; returns its sequence number and image length.
+fn read_header(flash: &mut dyn NvmFlash, base: usize) -> Result<Option<(u32, usize)>, NvmError> {
+    let mut hdr = [0u8; HEADER_SIZE];
+    flash.read(base as u32, &mut hdr)?;
+    let word = |i: usize| u32::from_le_bytes(hdr[4 * i..4 * i + 4].try_into().unwrap());
+    let (magic, seq, len, crc) = (word(0), word(1), word(2) as usize, word(3));
+    if magic != MAGIC || len > NVM_IMAGE_SIZE {
+        return Ok(None);
+    }
+    let mut c = crc32_update(!0, &seq.to_le_bytes());
+    c = crc32_update(c, &(len as u32).to_le_bytes());
+    let mut chunk = [0u8; CHUNK];
+    let mut off = 0;
+    while off < len {
+        let n = (len - off).min(CHUNK);
+        flash.read((base + HEADER_SIZE + off) as u32, &mut chunk[..n])?;
+        c = crc32_update(c, &chunk[..n]);
+        off += n;
+    }
+    Ok((!c == crc).then_some((seq, len)))
+}
+
+/// Write the RAM image to the next slot now.
+pub fn flush() -> Result<(), NvmError> {
+    let flash = STORE.lock(|s| {
+        let mut s = s.borrow_mut();
+        match s.flash.take() {
+            Some(flash) => Ok(flash),
+            // Attached but taken out by a flush in progress.
+            None if s.slots > 0 => Err(NvmError::Busy),
+            None => Err(NvmError::NotInitialised),
+        }
+    })?;
+    let r = write_image(flash);
+    STORE.lock(|s| s.borrow_mut().flash = Some(flash));
+    r
+}
+
+fn write_image(flash: &mut dyn NvmFlash) -> Result<(), NvmError> {
+    let (slot_size, slots, slot, seq) = STORE.lock(|s| {
+        let s = s.borrow();
+        (s.slot_size, s.slots, s.slot, s.seq)
+    });
+    let next = slot.map_or(0, |s| (s + 1) % slots);
+    let seq = seq.wrapping_add(1);
+    let base = next * slot_size;
+    let used = IMAGE.lock(|img| img.borrow().used);
+
+    radio_idle()?;
+    flash.erase(base as u32, (base + slot_size) as u32)?;
+
+    // Data first, header last: a torn write never yields a valid header.
+    let mut c = crc32_update(!0, &seq.to_le_bytes());
+    c = crc32_update(c, &(used as u32).to_le_bytes());
+    let mut chunk = [0u8; CHUNK];
+    let mut off = 0;
+    while off < used {
+        let n = (used - off).min(CHUNK);
+        IMAGE.lock(|img| chunk[..n].copy_from_slice(&img.borrow().buf[off..off + n]));
+        c = crc32_update(c, &chunk[..n]);
+        // Pad the final chunk up to the flash write size (the tail is zero).
+        let padded = n.next_multiple_of(flash.write_size());
+        chunk[n..padded].fill(0);
+        radio_idle()?;
+        flash.write((base + HEADER_SIZE + off) as u32, &chunk[..padded])?;
+        off += n;
+    }
+    let mut hdr = [0u8; HEADER_SIZE];
+    for (i, w) in [MAGIC, seq, used as u32, !c].iter().enumerate() {
+        hdr[4 * i..4 * i + 4].copy_from_slice(&w.to_le_bytes());
+    }
+    radio_idle()?;
+    flash.write(base as u32, &hdr)?;
+
+    STORE.lock(|s| {
+        let mut s = s.borrow_mut();
+        s.slot = Some(next);
+        s.seq = seq;
+    });
+    IMAGE.lock(|img| img.borrow_mut().dirty = false);
+    Ok(())
+}
+
+fn radio_idle() -> Result<(), NvmError> {
+    if crate::platform::radio_active() {
+        Err(NvmError::Busy)
+    } else {
+        Ok(())
+    }
+}
+
+/// Write back pending changes; called from [`crate::StackRunner`].
+pub(crate) fn process() {
+    if IMAGE.lock(|img| img.borrow().dirty) {
+        // Without flash the image simply stays in RAM; a write abandoned for
+        // a radio event is retried from `on_radio_idle`.
+        let _ = flush();
+    }
+}
+
+/// A radio event ended; resume a write it interrupted.
+pub(crate) fn on_radio_idle() {
+    if IMAGE.lock(|img| img.borrow().dirty) {
+        crate::runner::schedule();
+    }
+}
+
+fn mark_dirty(img: &mut Image) {
+    img.dirty = true;
+    crate::runner::schedule();
+}
+
+// CRC-32 (IEEE 802.3, reflected), bitwise to avoid a 1 KiB table.
+fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+    for b in data {
+        crc ^= *b as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    crc
+}
+
+// ===== BLEPLAT_Nvm* semantics =====
+
+/// Append a record made of `data` followed by `extra`.
+pub(crate) fn add(type_: u8, data: &[u8], extra: &[u8]) -> i32 {
+    IMAGE.lock(|img| {
+        let mut img = img.borrow_mut();
+        let len = data.len() + extra.len();
+        let size = (RECORD_HEADER + len).next_multiple_of(4);
+        if len > u16::MAX as usize || img.used + size > NVM_IMAGE_SIZE {
+            return FULL;
+        }
+        let off = img.used;
+        let len_bytes = (len as u16).to_le_bytes();
+        img.buf[off..off + RECORD_HEADER].copy_from_slice(&[type_, 0, len_bytes[0], len_bytes[1]]);
+        let d = off + RECORD_HEADER;
+        img.buf[d..d + data.len()].copy_from_slice(data);
+        img.buf[d + data.len()..d + len].copy_from_slice(extra);
+        img.used += size;
+        mark_dirty(&mut img);
+        OK
+    })
+}
+
+/// Position on a record of `type_` and copy bytes from `offset` into `out`.
+/// Returns the number of bytes copied or `EOF`.
+pub(crate) fn get(mode: u8, type_: u8, offset: usize, out: &mut [u8]) -> i32 {
+    IMAGE.lock(|img| {
+        let mut img = img.borrow_mut();
+        let found = match (mode, img.cursor) {
+            (NVM_FIRST, _) => img.find(0, type_),
+            (NVM_NEXT, Cursor::At(o)) => img.find(o + img.record_size(o), type_),
+            (NVM_NEXT, Cursor::Before(o)) => img.find(o, type_),
+            (NVM_CURRENT, Cursor::At(o)) => Some(o),
+            _ => None,
+        };
+        let Some(off) = found else {
+            img.cursor = Cursor::None;
+            return EOF;
+        };
+        img.cursor = Cursor::At(off);
+        let data = img.data(off);
+        let data = data.get(offset..).unwrap_or(&[]);
+        let n = data.len().min(out.len());
+        out[..n].copy_from_slice(&data[..n]);
+        n as i32
+    })
+}
+
+/// Compare `data` with the current record at `offset`: 0 if equal.
+pub(crate) fn compare(offset: usize, data: &[u8]) -> i32 {
+    IMAGE.lock(|img| {
+        let img = img.borrow();
+        let Cursor::At(off) = img.cursor else {
+            return EOF;
+        };
+        let rec = img.data(off);
+        match rec.get(offset..offset + data.len()) {
+            Some(r) if r == data => OK,
+            _ => 1,
+        }
+    })
+}
+
+/// Drop the current record (`NVM_CURRENT`) or every record (`NVM_ALL`).
+pub(crate) fn discard(mode: u8) {
+    IMAGE.lock(|img| {
+        let mut img = img.borrow_mut();
+        match (mode, img.cursor) {
+            (NVM_CURRENT, Cursor::At(off)) => {
+                img.remove(off);
+                img.cursor = Cursor::Before(off);
+            }
+            (NVM_ALL, _) => {
+                let used = img.used;
+                img.buf[..used].fill(0);
+                img.used = 0;
+                img.cursor = Cursor::None;
+            }
+            _ => return,
+        }
+        mark_dirty(&mut img);
+    })
+}
+
+// ===== RAM-backed flash for host tests =====
+
+/// Errors from [`RamFlash`] (out-of-range or misaligned access).
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct RamFlashError(NorFlashErrorKind);
+
+impl NorFlashError for RamFlashError {
+    fn kind(&self) -> NorFlashErrorKind {
+        self.0
+    }
+}
+
+/// NOR flash simulated in RAM (8 KiB pages, 16-byte writes like the
+/// STM32WBA). Writes can only clear bits, as on real flash.
+pub struct RamFlash<const N: usize> {
+    mem: [u8; N],
+}
+
+impl<const N: usize> Default for RamFlash<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> RamFlash<N> {
+    pub const fn new() -> Self {
+        RamFlash { mem: [0xFF; N] }
+    }
+
+    /// Raw contents, e.g. to corrupt a slot in a test.
+    pub fn as_mut_bytes(&mut self) -> &mut [u8] {
+        &mut self.mem
+    }
+
+    fn check(&self, offset: u32, len: usize, align: usize) -> Result<usize, RamFlashError> {
+        let off = offset as usize;
+        if off + len > N {
+            return Err(RamFlashError(NorFlashErrorKind::OutOfBounds));
+        }
+        if !off.is_multiple_of(align) || !len.is_multiple_of(align) {
+            return Err(RamFlashError(NorFlashErrorKind::NotAligned));
+        }
+        Ok(off)
+    }
+}
+
+impl<const N: usize> ErrorType for RamFlash<N> {
+    type Error = RamFlashError;
+}
+
+impl<const N: usize> ReadNorFlash for RamFlash<N> {
+    const READ_SIZE: usize = 1;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        let off = self.check(offset, bytes.len(), Self::READ_SIZE)?;
+        bytes.copy_from_slice(&self.mem[off..off + bytes.len()]);
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        N
+    }
+}
+
+impl<const N: usize> NorFlash for RamFlash<N> {
+    const WRITE_SIZE: usize = 16;
+    const ERASE_SIZE: usize = 8192;
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        let Some(len) = to.checked_sub(from) else {
+            return Err(RamFlashError(NorFlashErrorKind::OutOfBounds));
+        };
+        let off = self.check(from, len as usize, Self::ERASE_SIZE)?;
+        self.mem[off..to as usize].fill(0xFF);
+        Ok(())
+    }
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        let off = self.check(offset, bytes.len(), Self::WRITE_SIZE)?;
+        for (m, b) in self.mem[off..off + bytes.len()].iter_mut().zip(bytes) {
+            *m &= *b;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex as TestLock;
+
+    /// The store is global; run one test at a time.
+    static SERIAL: TestLock<()> = TestLock::new(());
+
+    const PAGE: usize = 8192;
+    const SLOTS: usize = 3;
+
+    fn attach() {
+        let flash = Box::leak(Box::new(RamFlash::<{ SLOTS * PAGE }>::new()));
+        init(flash).unwrap();
+    }
+
+    /// Reload the image from the attached flash, as after a reset.
+    fn reboot() {
+        let flash = STORE.lock(|s| s.borrow_mut().flash.take()).unwrap();
+        init(flash).unwrap();
+    }
+
+    fn current_slot() -> (Option<usize>, u32) {
+        STORE.lock(|s| (s.borrow().slot, s.borrow().seq))
+    }
+
+    fn read(mode: u8, type_: u8) -> Option<Vec<u8>> {
+        let mut out = [0u8; 64];
+        let n = get(mode, type_, 0, &mut out);
+        (n >= 0).then(|| out[..n as usize].to_vec())
+    }
+
+    #[test]
+    fn get_add_delete() {
+        let _s = SERIAL.lock().unwrap();
+        attach();
+        assert_eq!(add(1, b"first", b""), OK);
+        assert_eq!(add(2, b"other", b""), OK);
+        assert_eq!(add(1, b"sec", b"ond"), OK);
+
+        assert_eq!(read(NVM_FIRST, 1).as_deref(), Some(&b"first"[..]));
+        assert_eq!(read(NVM_CURRENT, 1).as_deref(), Some(&b"first"[..]));
+        assert_eq!(compare(1, b"irs"), OK);
+        assert_ne!(compare(3, b"stX"), OK);
+        assert_eq!(read(NVM_NEXT, 1).as_deref(), Some(&b"second"[..]));
+        assert_eq!(read(NVM_NEXT, 1), None);
+
+        // Offsets at or past the end copy nothing.
+        let mut out = [0u8; 8];
+        assert_eq!(get(NVM_FIRST, 1, 3, &mut out), 2);
+        assert_eq!(&out[..2], b"st");
+        assert_eq!(get(NVM_CURRENT, 1, 5, &mut out), 0);
+        assert_eq!(get(NVM_CURRENT, 1, 100, &mut out), 0);
+
+        // Discarding the current record leaves NEXT on the one after it.
+        assert_eq!(read(NVM_FIRST, 1).as_deref(), Some(&b"first"[..]));
+        discard(NVM_CURRENT);
+        assert_eq!(read(NVM_NEXT, 1).as_deref(), Some(&b"second"[..]));
+        assert_eq!(read(NVM_FIRST, 2).as_deref(), Some(&b"other"[..]));
+
+        discard(NVM_ALL);
+        assert_eq!(read(NVM_FIRST, 1), None);
+        assert_eq!(read(NVM_FIRST, 2), None);
+    }
+
+    #[test]
+    fn add_reports_full() {
+        let _s = SERIAL.lock().unwrap();
+        attach();
+        let big = [0xAB; NVM_IMAGE_SIZE / 2];
+        assert_eq!(add(1, &big, b""), OK);
+        assert_eq!(add(1, &big, b""), FULL);
+    }
+
+    #[test]
+    fn slot_rotation() {
+        let _s = SERIAL.lock().unwrap();
+        attach();
+        assert_eq!(current_slot(), (None, 0));
+        assert_eq!(add(7, b"bond", b""), OK);
+        for seq in 1..=SLOTS as u32 + 1 {
+            flush().unwrap();
+            assert_eq!(current_slot(), (Some((seq as usize - 1) % SLOTS), seq));
+        }
+        reboot();
+        assert_eq!(current_slot(), (Some(0), SLOTS as u32 + 1));
+        assert_eq!(read(NVM_FIRST, 7).as_deref(), Some(&b"bond"[..]));
+    }
+
+    #[test]
+    fn crc_recovery() {
+        let _s = SERIAL.lock().unwrap();
+        attach();
+        assert_eq!(add(1, b"old", b""), OK);
+        flush().unwrap();
+        discard(NVM_ALL);
+        assert_eq!(add(1, b"new", b""), OK);
+        flush().unwrap();
+        assert_eq!(current_slot(), (Some(1), 2));
+
+        // Clear bits in slot 1's data: its CRC no longer matches.
+        STORE.lock(|s| {
+            let mut s = s.borrow_mut();
+            let base = s.slot_size + HEADER_SIZE;
+            s.flash
+                .as_mut()
+                .unwrap()
+                .write(base as u32, &[0; 16])
+                .unwrap();
+        });
+        reboot();
+        assert_eq!(current_slot(), (Some(0), 1));
+        assert_eq!(read(NVM_FIRST, 1).as_deref(), Some(&b"old"[..]));
+
+        // The next write goes to the slot after the recovered one.
+        flush().unwrap();
+        assert_eq!(current_slot(), (Some(1), 2));
+    }
+
+    #[test]
+    fn ram_flash_rejects_reversed_erase() {
+        let mut flash = RamFlash::<PAGE>::new();
+        assert!(NorFlash::erase(&mut flash, PAGE as u32, 0).is_err());
+    }
+}
@@ -0,0 +1,918 @@
+// st_wba_ble/src/platform.rs
+//! Rust port of ST's `bleplat.c`, `linklayer_plat.c` and `ll_sys_if.c`
+//! (enabled with the `rust-platform` feature).
+//!
+//! The link layer and host stack call these `BLEPLAT_*`, `LINKLAYER_PLAT_*`
+//! and `ll_sys_*` hooks for clocks, interrupts, delays and randomness.
+//! `BLEPLAT_*` services that still live in ST's C modules (RNG, AES/PKA,
+//! NVM, timers) are forwarded to them, so keep those in `System/` for now.
+//!
+//! The application owns the vector table and must forward two interrupts:
+//!
+//! ```ignore
+//! #[interrupt]
+//! fn RADIO() { st_wba_ble::platform::on_radio_interrupt(); }
+//! #[interrupt]
+//! fn HASH() { st_wba_ble::platform::on_sw_low_interrupt(); }
+//! ```
+
+#![allow(non_snake_case)]
+
+use core::cell::Cell;
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+
+/// Interrupt lines and priorities used for the radio.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct PlatformConfig {
+    /// Radio interrupt (`RADIO_IRQn`).
+    pub radio_irq: u16,
+    /// Spare interrupt the link layer triggers for deferred work (`HASH_IRQn` in ST examples).
+    pub sw_low_irq: u16,
+    /// Radio interrupt priority while a radio event is running.
+    pub radio_prio_high: u8,
+    /// Radio interrupt priority outside radio events.
+    pub radio_prio_low: u8,
+    /// Default priority of the software-low interrupt.
+    pub sw_low_prio: u8,
+}
+
+impl PlatformConfig {
+    /// STM32WBA5x values from ST's `app_conf.h`.
+    pub const DEFAULT: Self = Self {
+        radio_irq: 66,
+        sw_low_irq: 61,
+        radio_prio_high: 0,
+        radio_prio_low: 5,
+        sw_low_prio: 14,
+    };
+}
+
+impl Default for PlatformConfig {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Override the interrupt numbers/priorities; call before the link layer starts.
+pub fn configure(cfg: PlatformConfig) {
+    CONFIG.lock(|c| c.set(cfg));
+}
+
+fn config() -> PlatformConfig {
+    CONFIG.lock(|c| c.get())
+}
+
+/// Call `handler` instead of panicking when the link layer reports a failed
+/// assertion (`LINKLAYER_PLAT_Assert`) or the RNG cannot be recovered, e.g.
+/// to log and reset the chip. The caller carries on if the handler returns.
+pub fn set_assert_handler(handler: fn()) {
+    ASSERT_HANDLER.lock(|h| h.set(Some(handler)));
+}
+
+/// Forward the `RADIO` interrupt here.
+pub fn on_radio_interrupt() {
+    if let Some(cb) = RADIO_CB.lock(|c| c.get()) {
+        unsafe { cb() };
+    }
+}
+
+/// Forward the software-low interrupt (see [`PlatformConfig::sw_low_irq`]) here.
+pub fn on_sw_low_interrupt() {
+    if let Some(cb) = SW_LOW_CB.lock(|c| c.get()) {
+        unsafe { cb() };
+    }
+}
+
+/// Whether the link layer is in the middle of a radio event.
+pub fn radio_active() -> bool {
+    RADIO_ACTIVE.load(Ordering::Relaxed)
+}
+
+type IsrCallback = unsafe extern "C" fn();
+type AssertHandler = fn();
+
+static CONFIG: Mutex<CriticalSectionRawMutex, Cell<PlatformConfig>> =
+    Mutex::new(Cell::new(PlatformConfig::DEFAULT));
+static RADIO_CB: Mutex<CriticalSectionRawMutex, Cell<Option<IsrCallback>>> =
+    Mutex::new(Cell::new(None));
+static SW_LOW_CB: Mutex<CriticalSectionRawMutex, Cell<Option<IsrCallback>>> =
+    Mutex::new(Cell::new(None));
+static ASSERT_HANDLER: Mutex<CriticalSectionRawMutex, Cell<Option<AssertHandler>>> =
+    Mutex::new(Cell::new(None));
+/// Whether link-layer background work is waiting for the runner.
+#[cfg(feature = "low-power")]
+pub(crate) fn bg_pending() -> bool {
+    LL_BG_PENDING.load(Ordering::Acquire)
+}
+
+static RADIO_ACTIVE: AtomicBool = AtomicBool::new(false);
+static LL_BG_PENDING: AtomicBool = AtomicBool::new(false);
+#[cfg(not(feature = "rust-crypto"))]
+static PKA_BG_PENDING: AtomicBool = AtomicBool::new(false);
+
+/// Run deferred link-layer and PKA work; called from [`crate::StackRunner`].
+pub(crate) fn process() {
+    if LL_BG_PENDING.swap(false, Ordering::AcqRel) {
+        unsafe { ll_sys_bg_process() };
+    }
+    #[cfg(not(feature = "rust-crypto"))]
+    if PKA_BG_PENDING.swap(false, Ordering::AcqRel) {
+        unsafe { BPKA_BG_Process() };
+    }
+    #[cfg(feature = "rust-timer")]
+    crate::timer::process();
+    #[cfg(feature = "rust-nvm")]
+    crate::nvm::process();
+    #[cfg(feature = "rust-crypto")]
+    if crate::crypto::process() {
+        unsafe { BLEPLATCB_PkaComplete() };
+        crate::runner::schedule();
+    }
+}
+
+/// `BLEPLAT_*` return codes and NVM modes (bleplat.h).
+#[allow(dead_code)]
+pub(crate) mod code {
+    pub const OK: i32 = 0;
+    pub const FULL: i32 = -1;
+    pub const BUSY: i32 = -2;
+    pub const EOF: i32 = -3;
+    pub const WARNING: i32 = -4;
+    pub const ERROR: i32 = -5;
+
+    pub const NVM_FIRST: u8 = 0;
+    pub const NVM_NEXT: u8 = 1;
+    pub const NVM_CURRENT: u8 = 2;
+    pub const NVM_ALL: u8 = 3;
+}
+
+// ===== Registers (RM0493) =====
+
+mod regs {
+    pub const RCC_BASE: usize = 0x4602_0C00;
+    pub const RCC_CR: usize = RCC_BASE;
+    pub const RCC_CR_HSERDY: u32 = 1 << 17;
+    pub const RCC_AHB5ENR: usize = RCC_BASE + 0x098;
+    pub const RCC_AHB5ENR_RADIOEN: u32 = 1 << 0;
+    pub const RCC_RADIOENR: usize = RCC_BASE + 0x200;
+    pub const RCC_RADIOENR_BBCLKEN: u32 = 1 << 1;
+
+    pub const NVIC_ISER: usize = 0xE000_E100;
+    pub const NVIC_ICER: usize = 0xE000_E180;
+    pub const NVIC_ISPR: usize = 0xE000_E200;
+    pub const NVIC_IABR: usize = 0xE000_E300;
+    pub const NVIC_IPR: usize = 0xE000_E400;
+    /// Implemented priority bits on the Cortex-M33 in STM32WBA.
+    pub const NVIC_PRIO_BITS: u8 = 4;
+
+    /// 64-bit unique device number (UDN).
+    pub const UID64_BASE: usize = 0x0BF9_0700;
+
+    #[inline]
+    pub fn modify(addr: usize, set: u32, clear: u32) {
+        unsafe {
+            let p = addr as *mut u32;
+            let v = core::ptr::read_volatile(p);
+            core::ptr::write_volatile(p, (v & !clear) | set);
+        }
+    }
+
+    #[inline]
+    pub fn read(addr: usize) -> u32 {
+        unsafe { core::ptr::read_volatile(addr as *const u32) }
+    }
+}
+
+mod nvic {
+    use super::regs;
+
+    fn bank(irq: u16) -> (usize, u32) {
+        ((irq as usize / 32) * 4, 1 << (irq % 32))
+    }
+
+    pub fn enable(irq: u16) {
+        let (off, bit) = bank(irq);
+        unsafe { core::ptr::write_volatile((regs::NVIC_ISER + off) as *mut u32, bit) };
+    }
+
+    pub fn disable(irq: u16) {
+        let (off, bit) = bank(irq);
+        unsafe { core::ptr::write_volatile((regs::NVIC_ICER + off) as *mut u32, bit) };
+    }
+
+    pub fn pend(irq: u16) {
+        let (off, bit) = bank(irq);
+        unsafe { core::ptr::write_volatile((regs::NVIC_ISPR + off) as *mut u32, bit) };
+    }
+
+    pub fn is_active(irq: u16) -> bool {
+        let (off, bit) = bank(irq);
+        regs::read(regs::NVIC_IABR + off) & bit != 0
+    }
+
+    pub fn set_priority(irq: u16, prio: u8) {
+        let v = prio << (8 - regs::NVIC_PRIO_BITS);
+        unsafe { core::ptr::write_volatile((regs::NVIC_IPR + irq as usize) as *mut u8, v) };
+    }
+
+    /// Current raw BASEPRI value.
+    pub fn basepri() -> u8 {
+        #[cfg(target_arch = "arm")]
+        {
+            let v: u32;
+            unsafe {
+                core::arch::asm!(
+                    "mrs {}, BASEPRI",
+                    out(reg) v,
+                    options(nomem, nostack, preserves_flags)
+                )
+            };
+            v as u8
+        }
+        #[cfg(not(target_arch = "arm"))]
+        0
+    }
+
+    /// Write a raw BASEPRI value saved by [`basepri`].
+    #[allow(unused_variables)]
+    pub fn set_basepri(raw: u8) {
+        #[cfg(target_arch = "arm")]
+        unsafe {
+            core::arch::asm!(
+                "msr BASEPRI, {}",
+                in(reg) raw as u32,
+                options(nomem, nostack, preserves_flags)
+            );
+        }
+    }
+
+    /// Mask every interrupt at priority `prio` or lower urgency; like
+    /// `BASEPRI_MAX`, an existing stricter mask is kept.
+    #[allow(unused_variables)]
+    pub fn raise_basepri(prio: u8) {
+        #[cfg(target_arch = "arm")]
+        unsafe {
+            let v = ((prio as u32) << (8 - regs::NVIC_PRIO_BITS)) & 0xFF;
+            core::arch::asm!(
+                "msr BASEPRI_MAX, {}",
+                in(reg) v,
+                options(nomem, nostack, preserves_flags)
+            );
+        }
+    }
+}
+
+// ===== C services still provided by ST's modules =====
+
+unsafe extern "C" {
+    // ll_sys (link layer library)
+    fn ll_sys_bg_process();
+    fn ll_intf_config_ll_ctx_params(allow_low_isr: u8, run_post_evnt_frm_isr: u8);
+
+    // bleplat companions
+    fn BLEPLATCB_PkaComplete();
+    fn HW_RNG_Get(n: u8, val: *mut u32);
+}
+
+#[cfg(not(feature = "rust-timer"))]
+unsafe extern "C" {
+    fn BLE_TIMER_Init();
+    fn BLE_TIMER_Start(id: u16, timeout: u32) -> u8;
+    fn BLE_TIMER_Stop(id: u16);
+}
+
+#[cfg(not(feature = "rust-nvm"))]
+unsafe extern "C" {
+    fn NVM_Add(
+        type_: u8,
+        data: *const u8,
+        size: i32,
+        extra_data: *const u8,
+        extra_size: i32,
+    ) -> i32;
+    fn NVM_Get(mode: u8, type_: u8, offset: i32, data: *mut u8, size: i32) -> i32;
+    fn NVM_Compare(offset: i32, data: *const u8, size: i32) -> i32;
+    fn NVM_Discard(mode: u8);
+}
+
+#[cfg(not(feature = "rust-crypto"))]
+unsafe extern "C" {
+    fn BAES_Reset();
+    fn BAES_EcbCrypt(key: *const u8, input: *const u8, output: *mut u8, enc: i32);
+    fn BAES_CmacSetKey(key: *const u8);
+    fn BAES_CmacCompute(input: *const u8, size: u32, output: *mut u8);
+    fn BPKA_Reset();
+    fn BPKA_BG_Process();
+    fn BPKA_StartP256Key(local_private_key: *const u32) -> i32;
+    fn BPKA_ReadP256Key(local_public_key: *mut u32);
+    fn BPKA_StartDhKey(local_private_key: *const u32, remote_public_key: *const u32) -> i32;
+    fn BPKA_ReadDhKey(dh_key: *mut u32) -> i32;
+}
+
+// ===== bleplat =====
+
+#[unsafe(no_mangle)]
+pub extern "C" fn BLEPLAT_Init() {
+    #[cfg(not(feature = "rust-crypto"))]
+    unsafe {
+        BAES_Reset();
+        BPKA_Reset();
+    }
+    #[cfg(not(feature = "rust-timer"))]
+    unsafe {
+        BLE_TIMER_Init();
+    }
+}
+
+/// # Safety
+/// Called by the stack with valid buffers of the given sizes.
+#[cfg(not(feature = "rust-nvm"))]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn BLEPLAT_NvmAdd(
+    type_: u8,
+    data: *const u8,
+    size: i32,
+    extra_data: *const u8,
+    extra_size: i32,
+) -> i32 {
+    unsafe { NVM_Add(type_, data, size, extra_data, extra_size) }
+}
+
+/// # Safety
+/// Called by the stack with a valid buffer of `size` bytes.
+#[cfg(not(feature = "rust-nvm"))]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn BLEPLAT_NvmGet(
+    mode: u8,
+    type_: u8,
+    offset: i32,
+    data: *mut u8,
+    size: i32,
+) -> i32 {
+    unsafe { NVM_Get(mode, type_, offset, data, size) }
+}
+
+/// # Safety
+/// Called by the stack with a valid buffer of `size` bytes.
+#[cfg(not(feature = "rust-nvm"))]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn BLEPLAT_NvmCompare(offset: i32, data: *const u8, size: i32) -> i32 {
+    unsafe { NVM_Compare(offset, data, size) }
+}
+
+#[cfg(not(feature = "rust-nvm"))]
+#[unsafe(no_mangle)]
+pub extern "C" fn BLEPLAT_NvmDiscard(mode: u8) {
+    unsafe { NVM_Discard(mode) }
+}
+
+/// Store a record (`data` followed by `extra_data`); `BLEPLAT_FULL` when out of space.
+///
+/// # Safety
+/// Called by the stack with valid buffers of the given sizes.
+#[cfg(feature = "rust-nvm")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn BLEPLAT_NvmAdd(
+    type_: u8,
+    data: *const u8,
+    size: i32,
+    extra_data: *const u8,
+    extra_size: i32,
+) -> i32 {
+    let data = unsafe { raw_slice(data, size) };
+    let extra = unsafe { raw_slice(extra_data, extra_size) };
+    crate::nvm::add(type_, data, extra)
+}
+
+/// Read from the first/next/current record of `type_`; bytes read or `BLEPLAT_EOF`.
+///
+/// # Safety
+/// Called by the stack with a valid buffer of `size` bytes.
+#[cfg(feature = "rust-nvm")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn BLEPLAT_NvmGet(
+    mode: u8,
+    type_: u8,
+    offset: i32,
+    data: *mut u8,
+    size: i32,
+) -> i32 {
+    let out = if data.is_null() || size <= 0 {
+        &mut [][..]
+    } else {
+        unsafe { core::slice::from_raw_parts_mut(data, size as usize) }
+    };
+    crate::nvm::get(mode, type_, offset.max(0) as usize, out)
+}
+
+/// Compare with the current record; 0 when equal.
+///
+/// # Safety
+/// Called by the stack with a valid buffer of `size` bytes.
+#[cfg(feature = "rust-nvm")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn BLEPLAT_NvmCompare(offset: i32, data: *const u8, size: i32) -> i32 {
+    let data = unsafe { raw_slice(data, size) };
+    crate::nvm::compare(offset.max(0) as usize, data)
+}
+
+#[cfg(feature = "rust-nvm")]
+#[unsafe(no_mangle)]
+pub extern "C" fn BLEPLAT_NvmDiscard(mode: u8) {
+    crate::nvm::discard(mode)
+}
+
+/// # Safety
+/// `ptr` must be valid for `len` bytes when `len > 0`.
+#[cfg(feature = "rust-nvm")]
+unsafe fn raw_slice<'a>(ptr: *const u8, len: i32) -> &'a [u8] {
+    if ptr.is_null() || len <= 0 {
+        &[]
+    } else {
+        unsafe { core::slice::from_raw_parts(ptr, len as usize) }
+    }
+}
+
+/// # Safety
+/// Called by the stack with room for `n` words at `val`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn BLEPLAT_RngGet(n: u8, val: *mut u32) {
+    unsafe { HW_RNG_Get(n, val) }
+}
+
+/// # Safety
+/// Called by the stack with 16-byte `key`, `input` and `output` buffers.
+#[cfg(not(feature = "rust-crypto"))]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn BLEPLAT_AesEcbEncrypt(key: *const u8, input: *const u8, output: *mut u8) {
+    unsafe { BAES_EcbCrypt(key, input, output, 1) }
+}
+
+/// # Safety
+/// Called by the stack with a 16-byte `key`.
+#[cfg(not(feature = "rust-crypto"))]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn BLEPLAT_AesCmacSetKey(key: *const u8) {
+    unsafe { BAES_CmacSetKey(key) }
+}
+
+/// # Safety
+/// Called by the stack with `size` input bytes and a 16-byte `output`.
+#[cfg(not(feature = "rust-crypto"))]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn BLEPLAT_AesCmacCompute(input: *const u8, size: u32, output: *mut u8) {
+    unsafe { BAES_CmacCompute(input, size, output) }
+}
+
+/// # Safety
+/// Called by the stack with an 8-word private key.
+#[cfg(not(feature = "rust-crypto"))]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn BLEPLAT_PkaStartP256Key(local_private_key: *const u32) -> i32 {
+    unsafe { BPKA_StartP256Key(local_private_key) }
+}
+
+/// # Safety
+/// Called by the stack with room for a 16-word public key.
+#[cfg(not(feature = "rust-crypto"))]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn BLEPLAT_PkaReadP256Key(local_public_key: *mut u32) {
+    unsafe { BPKA_ReadP256Key(local_public_key) }
+}
+
+/// # Safety
+/// Called by the stack with an 8-word private key and a 16-word public key.
+#[cfg(not(feature = "rust-crypto"))]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn BLEPLAT_PkaStartDhKey(
+    local_private_key: *const u32,
+    remote_public_key: *const u32,
+) -> i32 {
+    unsafe { BPKA_StartDhKey(local_private_key, remote_public_key) }
+}
+
+/// # Safety
+/// Called by the stack with room for an 8-word DH key.
+#[cfg(not(feature = "rust-crypto"))]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn BLEPLAT_PkaReadDhKey(dh_key: *mut u32) -> i32 {
+    unsafe { BPKA_ReadDhKey(dh_key) }
+}
+
+/// PKA operation finished (called by `bpka.c`).
+#[cfg(not(feature = "rust-crypto"))]
+#[unsafe(no_mangle)]
+pub extern "C" fn BPKACB_Complete() {
+    unsafe { BLEPLATCB_PkaComplete() };
+    crate::runner::schedule();
+}
+
+/// `bpka.c` needs `BPKA_BG_Process` to run.
+#[cfg(not(feature = "rust-crypto"))]
+#[unsafe(no_mangle)]
+pub extern "C" fn BPKACB_Process() {
+    PKA_BG_PENDING.store(true, Ordering::Release);
+    crate::runner::schedule();
+}
+
+/// # Safety
+/// Called by the stack with 16-byte `key`, `input` and `output` buffers.
+#[cfg(feature = "rust-crypto")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn BLEPLAT_AesEcbEncrypt(key: *const u8, input: *const u8, output: *mut u8) {
+    let (key, input) = unsafe { (&*(key as *const [u8; 16]), &*(input as *const [u8; 16])) };
+    let out = crate::crypto::aes_ecb_encrypt(key, input);
+    unsafe { core::ptr::copy_nonoverlapping(out.as_ptr(), output, 16) };
+}
+
+/// # Safety
+/// Called by the stack with a 16-byte `key`.
+#[cfg(feature = "rust-crypto")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn BLEPLAT_AesCmacSetKey(key: *const u8) {
+    crate::crypto::cmac_set_key(unsafe { &*(key as *const [u8; 16]) });
+}
+
+/// # Safety
+/// Called by the stack with `size` input bytes and a 16-byte `output`.
+#[cfg(feature = "rust-crypto")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn BLEPLAT_AesCmacCompute(input: *const u8, size: u32, output: *mut u8) {
+    let msg = if size == 0 {
+        &[][..]
+    } else {
+        unsafe { core::slice::from_raw_parts(input, size as usize) }
+    };
+    let tag = crate::crypto::cmac_compute(msg);
+    unsafe { core::ptr::copy_nonoverlapping(tag.as_ptr(), output, 16) };
+}
+
+/// Queue public key generation; completion is signalled with `BLEPLATCB_PkaComplete`.
+/// Fails with `BLEPLAT_ERROR` for a private key outside `1..n`.
+///
+/// # Safety
+/// Called by the stack with an 8-word private key.
+#[cfg(feature = "rust-crypto")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn BLEPLAT_PkaStartP256Key(local_private_key: *const u32) -> i32 {
+    let private = unsafe { (local_private_key as *const [u32; 8]).read_unaligned() };
+    match crate::crypto::start_p256_key(&private) {
+        Ok(true) => code::OK,
+        Ok(false) => code::BUSY,
+        Err(_) => code::ERROR,
+    }
+}
+
+/// # Safety
+/// Called by the stack with room for a 16-word public key.
+#[cfg(feature = "rust-crypto")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn BLEPLAT_PkaReadP256Key(local_public_key: *mut u32) {
+    // The ACI has no error path here: invalid private keys are rejected by
+    // `BLEPLAT_PkaStartP256Key`, and after a peripheral fault the output is
+    // all zeros, which is not on the curve, so the peer aborts pairing.
+    let mut public = [0u32; 16];
+    if crate::crypto::read_p256_key(&mut public).is_err() {
+        public = [0; 16];
+    }
+    unsafe { (local_public_key as *mut [u32; 16]).write_unaligned(public) };
+}
+
+/// Queue DH key computation; completion is signalled with `BLEPLATCB_PkaComplete`.
+///
+/// # Safety
+/// Called by the stack with an 8-word private key and a 16-word public key.
+#[cfg(feature = "rust-crypto")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn BLEPLAT_PkaStartDhKey(
+    local_private_key: *const u32,
+    remote_public_key: *const u32,
+) -> i32 {
+    let private = unsafe { (local_private_key as *const [u32; 8]).read_unaligned() };
+    let remote = unsafe { (remote_public_key as *const [u32; 16]).read_unaligned() };
+    match crate::crypto::start_dh_key(&private, &remote) {
+        Ok(true) => code::OK,
+        Ok(false) => code::BUSY,
+        Err(_) => code::ERROR,
+    }
+}
+
+/// Fails with `BLEPLAT_ERROR` if the remote public key was invalid.
+///
+/// # Safety
+/// Called by the stack with room for an 8-word DH key.
+#[cfg(feature = "rust-crypto")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn BLEPLAT_PkaReadDhKey(dh_key: *mut u32) -> i32 {
+    let mut dh = [0u32; 8];
+    match crate::crypto::read_dh_key(&mut dh) {
+        Ok(()) => {
+            unsafe { (dh_key as *mut [u32; 8]).write_unaligned(dh) };
+            code::OK
+        }
+        Err(_) => code::ERROR,
+    }
+}
+
+#[cfg(not(feature = "rust-timer"))]
+#[unsafe(no_mangle)]
+pub extern "C" fn BLEPLAT_TimerStart(id: u16, timeout: u32) -> u8 {
+    unsafe { BLE_TIMER_Start(id, timeout) }
+}
+
+#[cfg(not(feature = "rust-timer"))]
+#[unsafe(no_mangle)]
+pub extern "C" fn BLEPLAT_TimerStop(id: u16) {
+    unsafe { BLE_TIMER_Stop(id) }
+}
+
+/// Start timer `id` (`timeout` in ms); expiry calls `BLEPLATCB_TimerExpiry`.
+#[cfg(feature = "rust-timer")]
+#[unsafe(no_mangle)]
+pub extern "C" fn BLEPLAT_TimerStart(id: u16, timeout: u32) -> u8 {
+    if crate::timer::start(id, timeout) {
+        0
+    } else {
+        crate::timer::INSUFFICIENT_RESOURCES
+    }
+}
+
+#[cfg(feature = "rust-timer")]
+#[unsafe(no_mangle)]
+pub extern "C" fn BLEPLAT_TimerStop(id: u16) {
+    crate::timer::stop(id)
+}
+
+// ===== linklayer_plat =====
+
+// LINKLAYER_PLAT_{En,Dis}ableSpecificIRQ selectors (ll_intf.h).
+const LL_HIGH_ISR_ONLY: u8 = 0x01;
+const LL_LOW_ISR_ONLY: u8 = 0x02;
+const SYS_LOW_ISR: u8 = 0x04;
+
+static IRQ: Mutex<CriticalSectionRawMutex, Cell<(u32, Option<critical_section::RestoreState>)>> =
+    Mutex::new(Cell::new((0, None)));
+static HIGH_ISR_DEPTH: AtomicU8 = AtomicU8::new(0);
+static LOW_ISR_DEPTH: AtomicU8 = AtomicU8::new(0);
+static SYS_ISR_DEPTH: AtomicU8 = AtomicU8::new(0);
+/// BASEPRI found by the outermost `DisableSpecificIRQ(SYS_LOW_ISR)`.
+static SAVED_BASEPRI: AtomicU8 = AtomicU8::new(0);
+
+#[unsafe(no_mangle)]
+pub extern "C" fn LINKLAYER_PLAT_ClockInit() {
+    regs::modify(regs::RCC_AHB5ENR, regs::RCC_AHB5ENR_RADIOEN, 0);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn LINKLAYER_PLAT_DelayUs(delay: u32) {
+    embassy_time::block_for(embassy_time::Duration::from_micros(delay as u64));
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn LINKLAYER_PLAT_Assert(condition: u8) {
+    if condition != 0 {
+        return;
+    }
+    assert_failed("link layer assertion failed");
+}
+
+/// Run the [assert handler](set_assert_handler), or panic with `msg`.
+pub(crate) fn assert_failed(msg: &str) {
+    match ASSERT_HANDLER.lock(|h| h.get()) {
+        Some(handler) => handler(),
+        None => panic!("{msg}"),
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn LINKLAYER_PLAT_WaitHclkRdy() {}
+
+/// Enable/disable the radio baseband (active) clock.
+#[unsafe(no_mangle)]
+pub extern "C" fn LINKLAYER_PLAT_AclkCtrl(enable: u8) {
+    if enable != 0 {
+        regs::modify(regs::RCC_RADIOENR, regs::RCC_RADIOENR_BBCLKEN, 0);
+        while regs::read(regs::RCC_CR) & regs::RCC_CR_HSERDY == 0 {}
+    } else {
+        regs::modify(regs::RCC_RADIOENR, 0, regs::RCC_RADIOENR_BBCLKEN);
+    }
+}
+
+/// # Safety
+/// Called by the link layer with room for `len` bytes at `ptr_rnd`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn LINKLAYER_PLAT_GetRNG(ptr_rnd: *mut u8, len: u32) {
+    let mut done = 0usize;
+    while done < len as usize {
+        let mut word: u32 = 0;
+        unsafe { HW_RNG_Get(1, &mut word) };
+        let bytes = word.to_le_bytes();
+        let n = core::cmp::min(4, len as usize - done);
+        unsafe { core::ptr::copy_nonoverlapping(bytes.as_ptr(), ptr_rnd.add(done), n) };
+        done += n;
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn LINKLAYER_PLAT_SetupRadioIT(intr_cb: Option<IsrCallback>) {
+    let cfg = config();
+    RADIO_CB.lock(|c| c.set(intr_cb));
+    nvic::set_priority(cfg.radio_irq, cfg.radio_prio_high);
+    nvic::enable(cfg.radio_irq);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn LINKLAYER_PLAT_SetupSwLowIT(intr_cb: Option<IsrCallback>) {
+    let cfg = config();
+    SW_LOW_CB.lock(|c| c.set(intr_cb));
+    nvic::set_priority(cfg.sw_low_irq, cfg.sw_low_prio);
+    nvic::enable(cfg.sw_low_irq);
+}
+
+/// Pend the software-low interrupt; `priority == 0` runs it at the default
+/// (low) priority, otherwise at the radio's low priority.
+#[unsafe(no_mangle)]
+pub extern "C" fn LINKLAYER_PLAT_TriggerSwLowIT(priority: u8) {
+    let cfg = config();
+    if !nvic::is_active(cfg.sw_low_irq) {
+        let prio = if priority == 0 {
+            cfg.sw_low_prio
+        } else {
+            cfg.radio_prio_low
+        };
+        nvic::set_priority(cfg.sw_low_irq, prio);
+    }
+    nvic::pend(cfg.sw_low_irq);
+}
+
+/// Re-enable interrupts after [`LINKLAYER_PLAT_DisableIRQ`] (nesting-aware).
+#[unsafe(no_mangle)]
+pub extern "C" fn LINKLAYER_PLAT_EnableIRQ() {
+    let restore = IRQ.lock(|s| {
+        let (depth, restore) = s.get();
+        match depth {
+            0 => None,
+            1 => {
+                s.set((0, None));
+                restore
+            }
+            n => {
+                s.set((n - 1, restore));
+                None
+            }
+        }
+    });
+    if let Some(r) = restore {
+        unsafe { critical_section::release(r) };
+    }
+}
+
+/// Disable interrupts (nesting-aware).
+#[unsafe(no_mangle)]
+pub extern "C" fn LINKLAYER_PLAT_DisableIRQ() {
+    // Enter first so the depth check and the store cannot be interleaved.
+    let restore = unsafe { critical_section::acquire() };
+    let nested = IRQ.lock(|s| {
+        let (depth, prev) = s.get();
+        match prev {
+            Some(outer) => s.set((depth + 1, Some(outer))),
+            None => s.set((1, Some(restore))),
+        }
+        prev.is_some()
+    });
+    if nested {
+        // The outermost section keeps interrupts masked until EnableIRQ.
+        unsafe { critical_section::release(restore) };
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn LINKLAYER_PLAT_EnableSpecificIRQ(isr_type: u8) {
+    let cfg = config();
+    if isr_type & LL_HIGH_ISR_ONLY != 0 && release(&HIGH_ISR_DEPTH) {
+        nvic::enable(cfg.radio_irq);
+    }
+    if isr_type & LL_LOW_ISR_ONLY != 0 && release(&LOW_ISR_DEPTH) {
+        nvic::enable(cfg.sw_low_irq);
+    }
+    if isr_type & SYS_LOW_ISR != 0 && release(&SYS_ISR_DEPTH) {
+        nvic::set_basepri(SAVED_BASEPRI.load(Ordering::Acquire));
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn LINKLAYER_PLAT_DisableSpecificIRQ(isr_type: u8) {
+    let cfg = config();
+    if isr_type & LL_HIGH_ISR_ONLY != 0 && HIGH_ISR_DEPTH.fetch_add(1, Ordering::AcqRel) == 0 {
+        nvic::disable(cfg.radio_irq);
+    }
+    if isr_type & LL_LOW_ISR_ONLY != 0 && LOW_ISR_DEPTH.fetch_add(1, Ordering::AcqRel) == 0 {
+        nvic::disable(cfg.sw_low_irq);
+    }
+    if isr_type & SYS_LOW_ISR != 0 && SYS_ISR_DEPTH.fetch_add(1, Ordering::AcqRel) == 0 {
+        // Mask everything at or below the radio's low priority, as ST does.
+        SAVED_BASEPRI.store(nvic::basepri(), Ordering::Release);
+        nvic::raise_basepri(cfg.radio_prio_low);
+    }
+}
+
+/// Decrement a nesting counter; true when it reaches zero.
+fn release(depth: &AtomicU8) -> bool {
+    depth
+        .fetch_update(Ordering::AcqRel, Ordering::Acquire, |d| d.checked_sub(1))
+        .is_ok_and(|prev| prev == 1)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn LINKLAYER_PLAT_EnableRadioIT() {
+    nvic::enable(config().radio_irq);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn LINKLAYER_PLAT_DisableRadioIT() {
+    nvic::disable(config().radio_irq);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn LINKLAYER_PLAT_StartRadioEvt() {
+    let cfg = config();
+    nvic::set_priority(cfg.radio_irq, cfg.radio_prio_high);
+    RADIO_ACTIVE.store(true, Ordering::Release);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn LINKLAYER_PLAT_StopRadioEvt() {
+    let cfg = config();
+    nvic::set_priority(cfg.radio_irq, cfg.radio_prio_low);
+    RADIO_ACTIVE.store(false, Ordering::Release);
+    #[cfg(feature = "rust-nvm")]
+    crate::nvm::on_radio_idle();
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn LINKLAYER_PLAT_RCOStartClbr() {}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn LINKLAYER_PLAT_RCOStopClbr() {}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn LINKLAYER_PLAT_RequestTemperature() {}
+
+/// STMicroelectronics' Bluetooth SIG company identifier.
+const ST_COMPANY_ID: u32 = 0x0030;
+
+/// # Safety
+/// Called by the link layer with a writable `company_id`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn LINKLAYER_PLAT_GetSTCompanyID(company_id: *mut u32) {
+    if let Some(id) = unsafe { company_id.as_mut() } {
+        *id = ST_COMPANY_ID;
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn LINKLAYER_PLAT_PhyStartClbr() {}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn LINKLAYER_PLAT_PhyStopClbr() {}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn LINKLAYER_PLAT_SCHLDR_TIMING_UPDATE_NOT(_p_evnt_timing: *mut core::ffi::c_void) {}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn LINKLAYER_PLAT_EnableOSContextSwitch() {}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn LINKLAYER_PLAT_DisableOSContextSwitch() {}
+
+/// Unique device number, used by the link layer to seed its identity.
+#[unsafe(no_mangle)]
+pub extern "C" fn LINKLAYER_PLAT_GetUDN() -> u32 {
+    regs::read(regs::UID64_BASE)
+}
+
+// ===== ll_sys_if =====
+
+#[unsafe(no_mangle)]
+pub extern "C" fn ll_sys_bg_process_init() {}
+
+/// Link layer requests `ll_sys_bg_process` from thread context.
+#[unsafe(no_mangle)]
+pub extern "C" fn ll_sys_schedule_bg_process() {
+    LL_BG_PENDING.store(true, Ordering::Release);
+    crate::runner::schedule();
+}
+
+/// Link layer requests `ll_sys_bg_process` from an ISR.
+#[unsafe(no_mangle)]
+pub extern "C" fn ll_sys_schedule_bg_process_isr() {
+    ll_sys_schedule_bg_process();
+}
+
+/// Link-layer context options: allow the low ISR, schedule next event from ISR.
+#[unsafe(no_mangle)]
+pub extern "C" fn ll_sys_config_params() {
+    unsafe { ll_intf_config_ll_ctx_params(1, 1) };
+}
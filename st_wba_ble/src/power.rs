@@ -0,0 +1,172 @@
+// st_wba_ble/src/power.rs
+//! Low-power coordination with the link layer (enabled with the `low-power`
+//! feature).
+//!
+//! Before the MCU enters Stop mode the link layer must be put into deep
+//! sleep: its context is retained in the radio domain and its sleep timer
+//! (clocked from LSE) wakes the radio ahead of the next event. This module
+//! asks the link layer how long until that event, decides between Run, Sleep
+//! and Stop, and performs the `ll_sys_dp_slp_enter`/`exit` handshake around
+//! the wait. Hook it into the executor's idle path:
+//!
+//! ```ignore
+//! loop {
+//!     unsafe { executor.poll() };
+//!     critical_section::with(|_| {
+//!         st_wba_ble::power::idle(|mode| match mode {
+//!             SleepMode::Stop => enter_stop1(), // HAL: SLEEPDEEP + WFI, then restore clocks
+//!             SleepMode::Sleep => cortex_m::asm::wfi(),
+//!             SleepMode::Run => {}
+//!         })
+//!     });
+//! }
+//! ```
+//!
+//! The `embassy-time` driver must keep running in Stop mode (RTC/LPTIM) so
+//! that the stack's timers still fire.
+
+use core::cell::Cell;
+use core::sync::atomic::{AtomicU8, Ordering};
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+
+/// Low-power state chosen for the next idle period.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SleepMode {
+    /// Radio work is imminent or running; do not wait.
+    Run,
+    /// WFI with clocks running.
+    Sleep,
+    /// Stop mode; the link layer is in deep sleep.
+    Stop,
+}
+
+/// Wake-up timing used when deciding on Stop mode.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct PowerConfig {
+    /// Time the radio needs before its next event after a deep-sleep wake-up
+    /// (HSE32 start-up plus link-layer restore), in µs.
+    pub wakeup_margin_us: u32,
+    /// Shortest gap to the next radio event worth entering Stop mode for, in µs.
+    pub min_stop_us: u32,
+}
+
+impl PowerConfig {
+    pub const DEFAULT: Self = Self {
+        wakeup_margin_us: 1_000,
+        min_stop_us: 2_000,
+    };
+}
+
+impl Default for PowerConfig {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Override the wake-up timing.
+pub fn configure(cfg: PowerConfig) {
+    CONFIG.lock(|c| c.set(cfg));
+}
+
+/// Keeps the MCU out of Stop mode while alive (e.g. during a UART transfer).
+pub struct StopGuard {
+    _priv: (),
+}
+
+/// Block Stop mode until the returned guard is dropped.
+pub fn block_stop() -> StopGuard {
+    STOP_BLOCKERS.fetch_add(1, Ordering::AcqRel);
+    StopGuard { _priv: () }
+}
+
+impl Drop for StopGuard {
+    fn drop(&mut self) {
+        STOP_BLOCKERS.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// `ll_intf_le_get_remaining_time_for_next_event` result when nothing is scheduled.
+const LL_DP_SLP_NO_WAKEUP: u32 = u32::MAX;
+const LL_SYS_DP_SLP_OK: u32 = 0;
+const LL_SYS_DP_SLP_ENABLED: u32 = 1;
+
+static CONFIG: Mutex<CriticalSectionRawMutex, Cell<PowerConfig>> =
+    Mutex::new(Cell::new(PowerConfig::DEFAULT));
+static STOP_BLOCKERS: AtomicU8 = AtomicU8::new(0);
+
+unsafe extern "C" {
+    fn ll_intf_le_get_remaining_time_for_next_event(remaining_us: *mut u32) -> u8;
+    fn ll_sys_dp_slp_enter(duration_us: u32) -> u32;
+    fn ll_sys_dp_slp_exit() -> u32;
+    fn ll_sys_dp_slp_get_state() -> u32;
+}
+
+/// Time until the link layer's next radio event; `None` if none is scheduled.
+pub fn next_radio_event_us() -> Option<u32> {
+    let mut remaining = 0u32;
+    let rc = unsafe { ll_intf_le_get_remaining_time_for_next_event(&mut remaining) };
+    if rc != 0 || remaining == LL_DP_SLP_NO_WAKEUP {
+        None
+    } else {
+        Some(remaining)
+    }
+}
+
+/// Mode the next idle period may use, without touching the link layer.
+pub fn policy() -> SleepMode {
+    if crate::platform::radio_active() || crate::platform::bg_pending() {
+        return SleepMode::Run;
+    }
+    if STOP_BLOCKERS.load(Ordering::Acquire) != 0 {
+        return SleepMode::Sleep;
+    }
+    let cfg = CONFIG.lock(|c| c.get());
+    match next_radio_event_us() {
+        None => SleepMode::Stop,
+        Some(t) if t >= cfg.min_stop_us.max(cfg.wakeup_margin_us) => SleepMode::Stop,
+        Some(_) => SleepMode::Sleep,
+    }
+}
+
+/// Whether Stop mode can be entered now.
+pub fn can_enter_stop() -> bool {
+    policy() == SleepMode::Stop
+}
+
+/// Prepare for the idle period and return the mode to enter. For
+/// [`SleepMode::Stop`] the link layer is put into deep sleep, programmed to
+/// wake [`PowerConfig::wakeup_margin_us`] before its next event; call
+/// [`exit`] after waking up. Call with interrupts masked.
+pub fn enter() -> SleepMode {
+    let mode = policy();
+    if mode != SleepMode::Stop {
+        return mode;
+    }
+    let margin = CONFIG.lock(|c| c.get()).wakeup_margin_us;
+    let duration = match next_radio_event_us() {
+        None => LL_DP_SLP_NO_WAKEUP,
+        Some(t) => t.saturating_sub(margin),
+    };
+    if unsafe { ll_sys_dp_slp_enter(duration) } == LL_SYS_DP_SLP_OK {
+        SleepMode::Stop
+    } else {
+        SleepMode::Sleep
+    }
+}
+
+/// Restore the link layer after waking from Stop mode (no-op otherwise).
+pub fn exit() {
+    if unsafe { ll_sys_dp_slp_get_state() } == LL_SYS_DP_SLP_ENABLED {
+        unsafe { ll_sys_dp_slp_exit() };
+    }
+}
+
+/// [`enter`], run `wait` for the chosen mode, then [`exit`].
+pub fn idle(wait: impl FnOnce(SleepMode)) {
+    let mode = enter();
+    wait(mode);
+    if mode == SleepMode::Stop {
+        exit();
+    }
+}
@@ -0,0 +1,95 @@
+// st_wba_ble/src/privacy.rs
+//! LE privacy: resolvable private addresses and the controller's resolving list.
+//!
+//! Enable privacy at init ([`crate::Ble::init_peripheral_with_privacy`]), load
+//! bonded peers into the resolving list with [`load_resolving_list`], and use
+//! one of the `Resolvable*` [`OwnAddress`](crate::addr::OwnAddress) variants when
+//! advertising, scanning or connecting.
+
+use crate::addr::Address;
+use crate::bond::{BondStore, MAX_LISTED_BONDS};
+use crate::status::{self, BleStatus, Result};
+use st_wba_ble_sys::ffi;
+
+/// Privacy mode passed to `aci_gap_init`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(u8)]
+pub enum Privacy {
+    #[default]
+    Disabled = 0x00,
+    /// Host generates and resolves private addresses.
+    Host = 0x01,
+    /// Address generation and resolution are offloaded to the controller.
+    Controller = 0x02,
+}
+
+/// Default RPA rotation period used by the controller (15 minutes).
+pub const DEFAULT_RPA_TIMEOUT_S: u16 = 900;
+
+/// Set how often the controller rotates our resolvable private address.
+/// `seconds` must be within 1..=41400 (0xA1B8).
+pub fn set_rpa_timeout(seconds: u16) -> Result<()> {
+    let rc = unsafe { ffi::hci_le_set_resolvable_private_address_timeout(seconds) } as i32;
+    if BleStatus::from(rc) == BleStatus::Ok {
+        Ok(())
+    } else {
+        Err(BleStatus::from(rc))
+    }
+}
+
+/// Enable or disable address resolution in the controller.
+pub fn set_address_resolution(enable: bool) -> Result<()> {
+    let rc = unsafe { ffi::hci_le_set_address_resolution_enable(enable as u8) } as i32;
+    if BleStatus::from(rc) == BleStatus::Ok {
+        Ok(())
+    } else {
+        Err(BleStatus::from(rc))
+    }
+}
+
+/// Replace the resolving list with the given identity addresses.
+///
+/// The stack looks up the peers' IRKs in its security database, so only
+/// bonded devices can be added. Fails with `INVALID_PARAMS` for more than
+/// [`MAX_LISTED_BONDS`] peers.
+pub fn set_resolving_list(peers: &[Address]) -> Result<()> {
+    if peers.len() > MAX_LISTED_BONDS {
+        return Err(BleStatus::Other(status::INVALID_PARAMS));
+    }
+    let mut entries: [ffi::Identity_Entry_t; MAX_LISTED_BONDS] = unsafe { core::mem::zeroed() };
+    let n = peers.len();
+    for (e, p) in entries.iter_mut().zip(peers) {
+        e.Peer_Identity_Address_Type = p.kind as u8;
+        e.Peer_Identity_Address = p.bytes;
+    }
+    let rc = unsafe {
+        ffi::aci_gap_add_devices_to_resolving_list(
+            n as u8,
+            entries.as_ptr(),
+            1, // Clear_Resolving_List: replace the current contents
+        )
+    } as i32;
+    if BleStatus::from(rc) == BleStatus::Ok {
+        Ok(())
+    } else {
+        Err(BleStatus::from(rc))
+    }
+}
+
+/// Fill the resolving list from the bond store. Returns the number of peers loaded.
+pub fn load_resolving_list(bonds: &BondStore) -> Result<usize> {
+    let mut peers = [Address::public([0; 6]); MAX_LISTED_BONDS];
+    let n = bonds.list(&mut peers)?;
+    set_resolving_list(&peers[..n])?;
+    Ok(n)
+}
+
+/// Empty the controller's resolving list.
+pub fn clear_resolving_list() -> Result<()> {
+    let rc = unsafe { ffi::hci_le_clear_resolving_list() } as i32;
+    if BleStatus::from(rc) == BleStatus::Ok {
+        Ok(())
+    } else {
+        Err(BleStatus::from(rc))
+    }
+}
@@ -0,0 +1,25 @@
+// st_wba_ble/src/profiles.rs
+//! Ready-made GATT services built on [`crate::gatt`]. Each profile sits
+//! behind its own `profile-*` feature and exposes `ATTRIBUTE_RECORDS` so the
+//! attribute budget in [`crate::StackConfig`] can be sized.
+
+#[cfg(feature = "profile-battery")]
+pub mod battery;
+#[cfg(feature = "profile-cts")]
+pub mod cts;
+#[cfg(feature = "profile-dis")]
+pub mod dis;
+#[cfg(feature = "profile-ess")]
+pub mod ess;
+#[cfg(feature = "profile-find-me")]
+pub mod find_me;
+#[cfg(feature = "profile-heart-rate")]
+pub mod heart_rate;
+#[cfg(feature = "profile-hid")]
+pub mod hid;
+#[cfg(feature = "profile-thermometer")]
+pub mod thermometer;
+#[cfg(feature = "profile-uart")]
+pub mod uart;
+
+pub mod encoding;
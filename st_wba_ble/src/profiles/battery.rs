@@ -0,0 +1,143 @@
+// st_wba_ble/src/profiles/battery.rs
+//! Battery Service (0x180F) with Battery Level and, optionally, the Battery
+//! Power State characteristic (enabled with the `profile-battery` feature).
+//!
+//! ```ignore
+//! let bas = BatteryService::register(&BatteryConfig::default())?;
+//! bas.set_level(87)?; // notifies subscribed clients
+//! ```
+
+use crate::gatt::{self, CHAR_PROP_NOTIFY, CHAR_PROP_READ, Char, Service};
+use crate::status::Result;
+
+pub const SERVICE_UUID: u16 = 0x180F;
+pub const BATTERY_LEVEL_UUID: u16 = 0x2A19;
+pub const POWER_STATE_UUID: u16 = 0x2A1A;
+
+/// Service declaration plus Battery Level (declaration, value, CCCD).
+pub const ATTRIBUTE_RECORDS: u16 = 4;
+/// Additional records when [`BatteryConfig::power_state`] is set.
+pub const POWER_STATE_RECORDS: u16 = 3;
+
+/// Two-bit state used by the Battery Power State fields.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(u8)]
+pub enum State {
+    #[default]
+    Unknown = 0,
+    NotSupported = 1,
+    No = 2,
+    Yes = 3,
+}
+
+/// Battery level state in Battery Power State.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(u8)]
+pub enum LevelState {
+    #[default]
+    Unknown = 0,
+    NotSupported = 1,
+    Good = 2,
+    CriticallyLow = 3,
+}
+
+/// Battery Power State (0x2A1A).
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct PowerState {
+    pub present: State,
+    pub discharging: State,
+    pub charging: State,
+    pub level: LevelState,
+}
+
+impl PowerState {
+    pub fn encode(&self) -> u8 {
+        self.present as u8
+            | (self.discharging as u8) << 2
+            | (self.charging as u8) << 4
+            | (self.level as u8) << 6
+    }
+}
+
+/// What to register.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct BatteryConfig {
+    /// Battery Level reported until the first [`BatteryService::set_level`].
+    pub initial_level: u8,
+    /// Also register Battery Power State.
+    pub power_state: bool,
+}
+
+impl Default for BatteryConfig {
+    fn default() -> Self {
+        Self {
+            initial_level: 100,
+            power_state: false,
+        }
+    }
+}
+
+/// A registered Battery Service.
+pub struct BatteryService {
+    service: Service,
+    level: Char,
+    power_state: Option<Char>,
+}
+
+impl BatteryService {
+    /// Add the service to the GATT database; call once after stack init.
+    pub fn register(cfg: &BatteryConfig) -> Result<Self> {
+        let records = ATTRIBUTE_RECORDS
+            + if cfg.power_state {
+                POWER_STATE_RECORDS
+            } else {
+                0
+            };
+        let service = gatt::add_primary_service(SERVICE_UUID, records as u8)?;
+        let level = gatt::add_char(
+            &service,
+            BATTERY_LEVEL_UUID,
+            CHAR_PROP_READ | CHAR_PROP_NOTIFY,
+            gatt::ATTR_PERMISSION_NONE,
+            1,
+        )?;
+        let power_state = if cfg.power_state {
+            Some(gatt::add_char(
+                &service,
+                POWER_STATE_UUID,
+                CHAR_PROP_READ | CHAR_PROP_NOTIFY,
+                gatt::ATTR_PERMISSION_NONE,
+                1,
+            )?)
+        } else {
+            None
+        };
+        let bas = BatteryService {
+            service,
+            level,
+            power_state,
+        };
+        bas.set_level(cfg.initial_level)?;
+        if bas.power_state.is_some() {
+            bas.set_power_state(&PowerState::default())?;
+        }
+        Ok(bas)
+    }
+
+    /// Set the level (clamped to 100 %) and notify subscribed clients.
+    pub fn set_level(&self, percent: u8) -> Result<()> {
+        gatt::update_char_value(&self.service, &self.level, &[percent.min(100)])
+    }
+
+    /// Update Battery Power State; no-op if it was not registered.
+    pub fn set_power_state(&self, state: &PowerState) -> Result<()> {
+        match &self.power_state {
+            Some(ch) => gatt::update_char_value(&self.service, ch, &[state.encode()]),
+            None => Ok(()),
+        }
+    }
+
+    pub fn service(&self) -> &Service {
+        &self.service
+    }
+}
@@ -0,0 +1,407 @@
+// st_wba_ble/src/profiles/cts.rs
+//! Current Time Service (0x1805) server and client (enabled with the
+//! `profile-cts` feature).
+//!
+//! The server exposes Current Time (read/notify, optionally writable) and,
+//! if configured, Local Time Information. The client reads, writes and
+//! subscribes to a peer's Current Time given handles found by discovery;
+//! typically a wearable reads the phone's clock after connecting.
+//!
+//! ```ignore
+//! // Client: sync from the phone.
+//! let cts = CtsClient::new(Bearer::from(&conn), current_time_handle);
+//! let now = cts.read().await?;
+//! cts.subscribe(current_time_handle + 1).await?;
+//! let (_, update) = cts::next_time_update().await;
+//! ```
+
+use super::encoding::DateTime;
+use crate::conn::{self, Disconnection};
+use crate::gatt::{
+    self, AttributeNotification, AttributeWrite, Bearer, CCCD_NOTIFY, CHAR_PROP_NOTIFY,
+    CHAR_PROP_READ, CHAR_PROP_WRITE, Char, GATT_NOTIFY_ATTRIBUTE_WRITE, Service,
+};
+use crate::status::{self, BleStatus, Result};
+use core::cell::{Cell, RefCell};
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+
+pub const SERVICE_UUID: u16 = 0x1805;
+pub const LOCAL_TIME_INFORMATION_UUID: u16 = 0x2A0F;
+pub const CURRENT_TIME_UUID: u16 = 0x2A2B;
+
+/// Service declaration plus Current Time (declaration, value, CCCD).
+pub const ATTRIBUTE_RECORDS: u16 = 4;
+/// Additional records for Local Time Information.
+pub const LOCAL_TIME_INFORMATION_RECORDS: u16 = 2;
+
+/// Peer Current Time characteristics a client can subscribe to at once.
+pub const MAX_SUBSCRIPTIONS: usize = 4;
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(u8)]
+pub enum DayOfWeek {
+    #[default]
+    Unknown = 0,
+    Monday = 1,
+    Tuesday = 2,
+    Wednesday = 3,
+    Thursday = 4,
+    Friday = 5,
+    Saturday = 6,
+    Sunday = 7,
+}
+
+impl DayOfWeek {
+    fn from_u8(v: u8) -> Self {
+        match v {
+            1 => DayOfWeek::Monday,
+            2 => DayOfWeek::Tuesday,
+            3 => DayOfWeek::Wednesday,
+            4 => DayOfWeek::Thursday,
+            5 => DayOfWeek::Friday,
+            6 => DayOfWeek::Saturday,
+            7 => DayOfWeek::Sunday,
+            _ => DayOfWeek::Unknown,
+        }
+    }
+}
+
+/// Why the time changed (Current Time adjust reason bits).
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct AdjustReason(u8);
+
+impl AdjustReason {
+    pub const NONE: AdjustReason = AdjustReason(0x00);
+    pub const MANUAL_TIME_UPDATE: AdjustReason = AdjustReason(0x01);
+    pub const EXTERNAL_REFERENCE_TIME_UPDATE: AdjustReason = AdjustReason(0x02);
+    pub const TIME_ZONE_CHANGE: AdjustReason = AdjustReason(0x04);
+    pub const DST_CHANGE: AdjustReason = AdjustReason(0x08);
+
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+
+    pub const fn contains(self, other: AdjustReason) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl core::ops::BitOr for AdjustReason {
+    type Output = AdjustReason;
+    fn bitor(self, rhs: AdjustReason) -> AdjustReason {
+        AdjustReason(self.0 | rhs.0)
+    }
+}
+
+/// Current Time (0x2A2B): Exact Time 256 plus adjust reason.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct CurrentTime {
+    pub date_time: DateTime,
+    pub day_of_week: DayOfWeek,
+    /// 1/256 s.
+    pub fractions256: u8,
+    pub adjust_reason: AdjustReason,
+}
+
+impl CurrentTime {
+    pub const LEN: usize = DateTime::LEN + 3;
+
+    pub fn encode(&self) -> [u8; Self::LEN] {
+        let mut b = [0u8; Self::LEN];
+        b[..DateTime::LEN].copy_from_slice(&self.date_time.encode());
+        b[7] = self.day_of_week as u8;
+        b[8] = self.fractions256;
+        b[9] = self.adjust_reason.bits();
+        b
+    }
+
+    pub fn decode(b: &[u8]) -> Option<Self> {
+        if b.len() < Self::LEN {
+            return None;
+        }
+        Some(Self {
+            date_time: DateTime::decode(b)?,
+            day_of_week: DayOfWeek::from_u8(b[7]),
+            fractions256: b[8],
+            adjust_reason: AdjustReason(b[9] & 0x0F),
+        })
+    }
+}
+
+/// DST offset in Local Time Information.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(u8)]
+pub enum DstOffset {
+    #[default]
+    Standard = 0,
+    HalfHour = 2,
+    Daylight = 4,
+    DoubleDaylight = 8,
+    Unknown = 255,
+}
+
+/// Local Time Information (0x2A0F).
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct LocalTimeInfo {
+    /// Offset from UTC in 15-minute steps (-48..=56; -128 = unknown).
+    pub time_zone: i8,
+    pub dst_offset: DstOffset,
+}
+
+impl LocalTimeInfo {
+    pub fn encode(&self) -> [u8; 2] {
+        [self.time_zone as u8, self.dst_offset as u8]
+    }
+}
+
+/// What to register.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct CtsConfig {
+    /// Clients may set the time (see [`time_written`]).
+    pub writable: bool,
+    pub local_time_information: Option<LocalTimeInfo>,
+}
+
+impl CtsConfig {
+    /// Attribute records the service needs (service declaration included).
+    pub fn attribute_records(&self) -> u16 {
+        ATTRIBUTE_RECORDS
+            + if self.local_time_information.is_some() {
+                LOCAL_TIME_INFORMATION_RECORDS
+            } else {
+                0
+            }
+    }
+}
+
+static CURRENT_TIME_HANDLE: Mutex<CriticalSectionRawMutex, Cell<u16>> = Mutex::new(Cell::new(0));
+static WRITTEN: Channel<CriticalSectionRawMutex, CurrentTime, 2> = Channel::new();
+
+/// Wait until a client writes our Current Time.
+pub async fn time_written() -> CurrentTime {
+    WRITTEN.receive().await
+}
+
+/// A registered Current Time Service.
+pub struct CurrentTimeService {
+    service: Service,
+    current_time: Char,
+    local_time_information: Option<Char>,
+}
+
+impl CurrentTimeService {
+    /// Add the service to the GATT database; call once after stack init.
+    pub fn register(cfg: &CtsConfig) -> Result<Self> {
+        let service = gatt::add_primary_service(SERVICE_UUID, cfg.attribute_records() as u8)?;
+        let (props, mask) = if cfg.writable {
+            (
+                CHAR_PROP_READ | CHAR_PROP_NOTIFY | CHAR_PROP_WRITE,
+                GATT_NOTIFY_ATTRIBUTE_WRITE,
+            )
+        } else {
+            (CHAR_PROP_READ | CHAR_PROP_NOTIFY, 0)
+        };
+        let current_time = gatt::add_char_with_mask(
+            &service,
+            CURRENT_TIME_UUID,
+            props,
+            gatt::ATTR_PERMISSION_NONE,
+            CurrentTime::LEN as u16,
+            mask,
+        )?;
+        let local_time_information = match &cfg.local_time_information {
+            Some(info) => {
+                let ch = gatt::add_char(
+                    &service,
+                    LOCAL_TIME_INFORMATION_UUID,
+                    CHAR_PROP_READ,
+                    gatt::ATTR_PERMISSION_NONE,
+                    2,
+                )?;
+                gatt::update_char_value(&service, &ch, &info.encode())?;
+                Some(ch)
+            }
+            None => None,
+        };
+        if cfg.writable {
+            CURRENT_TIME_HANDLE.lock(|h| h.set(current_time.value_handle()));
+            gatt::add_write_listener(on_write)?;
+        }
+        let cts = CurrentTimeService {
+            service,
+            current_time,
+            local_time_information,
+        };
+        cts.set_time(&CurrentTime::default())?;
+        Ok(cts)
+    }
+
+    /// Update Current Time and notify subscribed clients. Per the spec,
+    /// call on adjustments rather than every tick.
+    pub fn set_time(&self, t: &CurrentTime) -> Result<()> {
+        gatt::update_char_value(&self.service, &self.current_time, &t.encode())
+    }
+
+    /// Update Local Time Information; no-op if it was not registered.
+    pub fn set_local_time_info(&self, info: &LocalTimeInfo) -> Result<()> {
+        match &self.local_time_information {
+            Some(ch) => gatt::update_char_value(&self.service, ch, &info.encode()),
+            None => Ok(()),
+        }
+    }
+
+    pub fn service(&self) -> &Service {
+        &self.service
+    }
+}
+
+fn on_write(w: &AttributeWrite<'_>) {
+    let handle = CURRENT_TIME_HANDLE.lock(|h| h.get());
+    if handle == 0 || w.handle != handle || w.offset != 0 {
+        return;
+    }
+    if let Some(t) = CurrentTime::decode(w.data) {
+        let _ = WRITTEN.try_send(t);
+    }
+}
+
+// ===== Client =====
+#[derive(Copy, Clone, Eq, PartialEq)]
+struct Subscription {
+    /// Link the bearer runs on, so Enhanced bearers are dropped with it.
+    conn: u16,
+    /// Raw bearer and peer Current Time value handle.
+    bearer: u16,
+    handle: u16,
+}
+
+static SUBSCRIPTIONS: Mutex<
+    CriticalSectionRawMutex,
+    RefCell<[Option<Subscription>; MAX_SUBSCRIPTIONS]>,
+> = Mutex::new(RefCell::new([None; MAX_SUBSCRIPTIONS]));
+
+/// A subscription entry claimed by a pending [`CtsClient::subscribe`],
+/// freed on drop unless the CCCD write went through. The entry is only
+/// cleared if it still holds our key: a disconnect may have freed and
+/// reused it in the meantime.
+struct SubscriptionSlot(usize, Subscription);
+
+impl Drop for SubscriptionSlot {
+    fn drop(&mut self) {
+        SUBSCRIPTIONS.lock(|s| {
+            let e = &mut s.borrow_mut()[self.0];
+            if *e == Some(self.1) {
+                *e = None;
+            }
+        });
+    }
+}
+
+static UPDATES: Channel<CriticalSectionRawMutex, (Bearer, CurrentTime), 2> = Channel::new();
+
+/// Wait for a Current Time notification from a subscribed peer.
+pub async fn next_time_update() -> (Bearer, CurrentTime) {
+    UPDATES.receive().await
+}
+
+/// A peer's Current Time Service.
+pub struct CtsClient {
+    bearer: Bearer,
+    current_time: u16,
+}
+
+impl CtsClient {
+    /// `current_time` is the peer's Current Time value handle.
+    pub fn new(bearer: Bearer, current_time: u16) -> Self {
+        Self {
+            bearer,
+            current_time,
+        }
+    }
+
+    /// Read the peer's Current Time.
+    pub async fn read(&self) -> Result<CurrentTime> {
+        let mut buf = [0u8; CurrentTime::LEN];
+        let n = gatt::read(self.bearer, self.current_time, &mut buf).await?;
+        CurrentTime::decode(&buf[..n]).ok_or(BleStatus::Other(status::INVALID_PARAMS))
+    }
+
+    /// Set the peer's time (if its Current Time is writable).
+    pub async fn write(&self, t: &CurrentTime) -> Result<()> {
+        gatt::write(self.bearer, self.current_time, &t.encode()).await
+    }
+
+    /// Enable notifications via the peer's CCCD; updates arrive through
+    /// [`next_time_update`]. Enhanced bearers must have been opened through
+    /// [`crate::l2cap`].
+    pub async fn subscribe(&self, cccd_handle: u16) -> Result<()> {
+        let conn = gatt::bearer_connection(self.bearer)
+            .ok_or(BleStatus::Other(status::UNKNOWN_CONNECTION))?;
+        let key = Subscription {
+            conn,
+            bearer: self.bearer.raw(),
+            handle: self.current_time,
+        };
+        // Claimed before the CCCD write so a notification sent right after
+        // it is not missed; released again if the write fails or is dropped.
+        let claimed = SUBSCRIPTIONS.lock(|s| -> Result<Option<SubscriptionSlot>> {
+            let mut s = s.borrow_mut();
+            if s.contains(&Some(key)) {
+                return Ok(None);
+            }
+            let i = s
+                .iter()
+                .position(Option::is_none)
+                .ok_or(BleStatus::Other(status::INSUFFICIENT_RESOURCES))?;
+            s[i] = Some(key);
+            Ok(Some(SubscriptionSlot(i, key)))
+        })?;
+        gatt::add_notification_listener(on_notification)?;
+        conn::add_disconnect_listener(on_link_closed)?;
+        gatt::subscribe(self.bearer, cccd_handle, CCCD_NOTIFY).await?;
+        core::mem::forget(claimed);
+        Ok(())
+    }
+
+    /// Disable notifications again.
+    pub async fn unsubscribe(&self, cccd_handle: u16) -> Result<()> {
+        let (bearer, handle) = (self.bearer.raw(), self.current_time);
+        SUBSCRIPTIONS.lock(|s| {
+            for e in s.borrow_mut().iter_mut() {
+                if e.is_some_and(|e| e.bearer == bearer && e.handle == handle) {
+                    *e = None;
+                }
+            }
+        });
+        gatt::subscribe(self.bearer, cccd_handle, 0).await
+    }
+}
+
+fn on_notification(n: &AttributeNotification<'_>) {
+    let (bearer, handle) = (n.bearer.raw(), n.handle);
+    let subscribed = SUBSCRIPTIONS.lock(|s| {
+        s.borrow()
+            .iter()
+            .flatten()
+            .any(|e| e.bearer == bearer && e.handle == handle)
+    });
+    if !subscribed {
+        return;
+    }
+    if let Some(t) = CurrentTime::decode(n.data) {
+        let _ = UPDATES.try_send((n.bearer, t));
+    }
+}
+
+/// Drop subscriptions on every bearer of a link that went down.
+fn on_link_closed(d: &Disconnection) {
+    SUBSCRIPTIONS.lock(|s| {
+        for e in s.borrow_mut().iter_mut() {
+            if e.is_some_and(|e| e.conn == d.handle) {
+                *e = None;
+            }
+        }
+    });
+}
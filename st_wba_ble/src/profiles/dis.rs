@@ -0,0 +1,164 @@
+// st_wba_ble/src/profiles/dis.rs
+//! Device Information Service (0x180A) (enabled with the `profile-dis`
+//! feature). Only the fields set in [`DisConfig`] are registered; all of
+//! them are read-only.
+//!
+//! ```ignore
+//! let cfg = DisConfig {
+//!     manufacturer_name: Some("ACME"),
+//!     firmware_revision: Some(env!("CARGO_PKG_VERSION")),
+//!     ..DisConfig::EMPTY
+//! };
+//! StackConfig::new().gatt(base_attrs + cfg.attribute_records(), ..);
+//! DeviceInformationService::register(&cfg)?;
+//! ```
+
+use crate::gatt::{self, CHAR_PROP_READ, Service};
+use crate::status::{self, BleStatus, Result};
+
+pub const SERVICE_UUID: u16 = 0x180A;
+pub const SYSTEM_ID_UUID: u16 = 0x2A23;
+pub const MODEL_NUMBER_UUID: u16 = 0x2A24;
+pub const SERIAL_NUMBER_UUID: u16 = 0x2A25;
+pub const FIRMWARE_REVISION_UUID: u16 = 0x2A26;
+pub const HARDWARE_REVISION_UUID: u16 = 0x2A27;
+pub const SOFTWARE_REVISION_UUID: u16 = 0x2A28;
+pub const MANUFACTURER_NAME_UUID: u16 = 0x2A29;
+pub const REGULATORY_LIST_UUID: u16 = 0x2A2A;
+pub const PNP_ID_UUID: u16 = 0x2A50;
+
+/// Records per characteristic (declaration + value).
+const CHAR_RECORDS: u16 = 2;
+
+/// Who assigned [`PnpId::vendor_id`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum VendorIdSource {
+    Bluetooth = 0x01,
+    Usb = 0x02,
+}
+
+/// PnP ID (0x2A50).
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct PnpId {
+    pub vendor_id_source: VendorIdSource,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub product_version: u16,
+}
+
+impl PnpId {
+    pub fn encode(&self) -> [u8; 7] {
+        let mut b = [0u8; 7];
+        b[0] = self.vendor_id_source as u8;
+        b[1..3].copy_from_slice(&self.vendor_id.to_le_bytes());
+        b[3..5].copy_from_slice(&self.product_id.to_le_bytes());
+        b[5..7].copy_from_slice(&self.product_version.to_le_bytes());
+        b
+    }
+}
+
+/// Fields to expose; `None` fields are not registered.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct DisConfig {
+    pub manufacturer_name: Option<&'static str>,
+    pub model_number: Option<&'static str>,
+    pub serial_number: Option<&'static str>,
+    pub hardware_revision: Option<&'static str>,
+    pub firmware_revision: Option<&'static str>,
+    pub software_revision: Option<&'static str>,
+    /// System ID (0x2A23): 40-bit manufacturer identifier and 24-bit OUI, little-endian.
+    pub system_id: Option<[u8; 8]>,
+    /// IEEE 11073-20601 Regulatory Certification Data List, already encoded.
+    pub regulatory_list: Option<&'static [u8]>,
+    pub pnp_id: Option<PnpId>,
+}
+
+impl DisConfig {
+    /// No fields; start from this with struct update syntax.
+    pub const EMPTY: Self = Self {
+        manufacturer_name: None,
+        model_number: None,
+        serial_number: None,
+        hardware_revision: None,
+        firmware_revision: None,
+        software_revision: None,
+        system_id: None,
+        regulatory_list: None,
+        pnp_id: None,
+    };
+
+    fn strings(&self) -> [(u16, Option<&'static str>); 6] {
+        [
+            (MANUFACTURER_NAME_UUID, self.manufacturer_name),
+            (MODEL_NUMBER_UUID, self.model_number),
+            (SERIAL_NUMBER_UUID, self.serial_number),
+            (HARDWARE_REVISION_UUID, self.hardware_revision),
+            (FIRMWARE_REVISION_UUID, self.firmware_revision),
+            (SOFTWARE_REVISION_UUID, self.software_revision),
+        ]
+    }
+
+    /// Number of characteristics that will be registered.
+    pub fn characteristics(&self) -> u16 {
+        let strings = self.strings().iter().filter(|(_, v)| v.is_some()).count();
+        (strings
+            + self.system_id.is_some() as usize
+            + self.regulatory_list.is_some() as usize
+            + self.pnp_id.is_some() as usize) as u16
+    }
+
+    /// Attribute records the service needs (service declaration included).
+    pub fn attribute_records(&self) -> u16 {
+        1 + CHAR_RECORDS * self.characteristics()
+    }
+}
+
+/// A registered Device Information Service.
+pub struct DeviceInformationService {
+    service: Service,
+}
+
+impl DeviceInformationService {
+    /// Add the service with the fields of `cfg`; call once after stack init.
+    pub fn register(cfg: &DisConfig) -> Result<Self> {
+        if cfg.characteristics() == 0 {
+            return Err(BleStatus::Other(status::INVALID_PARAMS));
+        }
+        let service = gatt::add_primary_service(SERVICE_UUID, cfg.attribute_records() as u8)?;
+        for (uuid, value) in cfg.strings() {
+            if let Some(value) = value {
+                add_read_only(&service, uuid, value.as_bytes())?;
+            }
+        }
+        if let Some(id) = &cfg.system_id {
+            add_read_only(&service, SYSTEM_ID_UUID, id)?;
+        }
+        if let Some(list) = cfg.regulatory_list {
+            add_read_only(&service, REGULATORY_LIST_UUID, list)?;
+        }
+        if let Some(pnp) = &cfg.pnp_id {
+            add_read_only(&service, PNP_ID_UUID, &pnp.encode())?;
+        }
+        Ok(DeviceInformationService { service })
+    }
+
+    pub fn service(&self) -> &Service {
+        &self.service
+    }
+}
+
+/// Fixed-length, read-only characteristic holding `value`.
+fn add_read_only(service: &Service, uuid: u16, value: &[u8]) -> Result<()> {
+    if value.is_empty() || value.len() > u8::MAX as usize {
+        return Err(BleStatus::Other(status::INVALID_PARAMS));
+    }
+    let ch = gatt::add_char(
+        service,
+        uuid,
+        CHAR_PROP_READ,
+        gatt::ATTR_PERMISSION_NONE,
+        value.len() as u16,
+    )?;
+    gatt::update_char_value(service, &ch, value)
+}
@@ -0,0 +1,171 @@
+// st_wba_ble/src/profiles/encoding.rs
+//! Value formats shared by the profiles: IEEE 11073-20601 SFLOAT/FLOAT and
+//! the Date Time characteristic (GATT Specification Supplement).
+//!
+//! Medical floats are decimal: `mantissa × 10^exponent`, so 36.75 °C is
+//! `Float::new(3675, -2)`. No floating-point arithmetic is involved.
+
+/// 16-bit medical float: 4-bit exponent, 12-bit mantissa.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct SFloat(u16);
+
+impl SFloat {
+    pub const NAN: Self = Self(0x07FF);
+    /// Not at this resolution.
+    pub const NRES: Self = Self(0x0800);
+    pub const POSITIVE_INFINITY: Self = Self(0x07FE);
+    pub const NEGATIVE_INFINITY: Self = Self(0x0802);
+
+    // -2046..=-2048 encode -INFINITY, a reserved value and NRES.
+    const MANTISSA_MIN: i16 = -2045;
+    const MANTISSA_MAX: i16 = 2045;
+
+    /// `mantissa × 10^exponent`; `None` if either is out of range
+    /// (mantissa -2045..=2045, exponent -8..=7).
+    pub const fn new(mantissa: i16, exponent: i8) -> Option<Self> {
+        if mantissa < Self::MANTISSA_MIN
+            || mantissa > Self::MANTISSA_MAX
+            || exponent < -8
+            || exponent > 7
+        {
+            return None;
+        }
+        Some(Self(
+            ((exponent as u16 & 0x0F) << 12) | (mantissa as u16 & 0x0FFF),
+        ))
+    }
+
+    pub const fn raw(self) -> u16 {
+        self.0
+    }
+
+    pub const fn to_le_bytes(self) -> [u8; 2] {
+        self.0.to_le_bytes()
+    }
+}
+
+/// 32-bit medical float: 8-bit exponent, 24-bit mantissa.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Float(u32);
+
+impl Float {
+    pub const NAN: Self = Self(0x007F_FFFF);
+    /// Not at this resolution.
+    pub const NRES: Self = Self(0x0080_0000);
+    pub const POSITIVE_INFINITY: Self = Self(0x007F_FFFE);
+    pub const NEGATIVE_INFINITY: Self = Self(0x0080_0002);
+
+    // Below this are -INFINITY, a reserved value and NRES.
+    const MANTISSA_MIN: i32 = -0x7F_FFFD;
+    const MANTISSA_MAX: i32 = 0x7F_FFFD;
+
+    /// `mantissa × 10^exponent`; `None` if the mantissa does not fit in
+    /// 24 bits (or hits a reserved value).
+    pub const fn new(mantissa: i32, exponent: i8) -> Option<Self> {
+        if mantissa < Self::MANTISSA_MIN || mantissa > Self::MANTISSA_MAX {
+            return None;
+        }
+        Some(Self(
+            ((exponent as u8 as u32) << 24) | (mantissa as u32 & 0x00FF_FFFF),
+        ))
+    }
+
+    pub const fn raw(self) -> u32 {
+        self.0
+    }
+
+    pub const fn to_le_bytes(self) -> [u8; 4] {
+        self.0.to_le_bytes()
+    }
+}
+
+/// Date Time (0x2A08). Zero in any field means "not known".
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct DateTime {
+    /// 1582..=9999.
+    pub year: u16,
+    /// 1..=12.
+    pub month: u8,
+    /// 1..=31.
+    pub day: u8,
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: u8,
+}
+
+impl DateTime {
+    pub const LEN: usize = 7;
+
+    pub fn encode(&self) -> [u8; Self::LEN] {
+        let [lo, hi] = self.year.to_le_bytes();
+        [
+            lo,
+            hi,
+            self.month,
+            self.day,
+            self.hours,
+            self.minutes,
+            self.seconds,
+        ]
+    }
+
+    pub fn decode(b: &[u8]) -> Option<Self> {
+        let b: &[u8; Self::LEN] = b.get(..Self::LEN)?.try_into().ok()?;
+        Some(Self {
+            year: u16::from_le_bytes([b[0], b[1]]),
+            month: b[2],
+            day: b[3],
+            hours: b[4],
+            minutes: b[5],
+            seconds: b[6],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sfloat_encoding_and_range() {
+        assert_eq!(SFloat::new(123, -1).unwrap().raw(), 0xF07B);
+        assert_eq!(SFloat::new(-1, 0).unwrap().raw(), 0x0FFF);
+        assert_eq!(SFloat::new(2045, 7).unwrap().to_le_bytes(), [0xFD, 0x77]);
+        assert_eq!(SFloat::new(-2045, -8).unwrap().raw(), 0x8803);
+        // Mantissas of the special values are rejected.
+        assert_eq!(SFloat::new(2046, 0), None);
+        assert_eq!(SFloat::new(-2046, 0), None);
+        assert_eq!(SFloat::new(0, 8), None);
+        assert_eq!(SFloat::new(0, -9), None);
+    }
+
+    #[test]
+    fn float_encoding_and_range() {
+        let f = Float::new(3675, -2).unwrap();
+        assert_eq!(f.raw(), 0xFE00_0E5B);
+        assert_eq!(f.to_le_bytes(), [0x5B, 0x0E, 0x00, 0xFE]);
+        assert_eq!(Float::new(-1, 0).unwrap().raw(), 0x00FF_FFFF);
+        assert_eq!(Float::new(0x7F_FFFD, 0).unwrap().raw(), 0x007F_FFFD);
+        assert_eq!(Float::new(-0x7F_FFFD, 0).unwrap().raw(), 0x0080_0003);
+        assert_eq!(Float::new(0x7F_FFFE, 0), None);
+        assert_eq!(Float::new(-0x7F_FFFE, 0), None);
+        assert_eq!(Float::new(-0x80_0000, 0), None);
+    }
+
+    #[test]
+    fn date_time_round_trip() {
+        let t = DateTime {
+            year: 2024,
+            month: 3,
+            day: 15,
+            hours: 10,
+            minutes: 20,
+            seconds: 30,
+        };
+        let b = t.encode();
+        assert_eq!(b, [0xE8, 0x07, 3, 15, 10, 20, 30]);
+        assert_eq!(DateTime::decode(&b), Some(t));
+        assert_eq!(DateTime::decode(&b[..6]), None);
+        assert_eq!(DateTime::decode(&[0; 8]), Some(DateTime::default()));
+    }
+}
@@ -0,0 +1,196 @@
+// st_wba_ble/src/profiles/ess.rs
+//! Environmental Sensing Service (0x181A) server (enabled with the
+//! `profile-ess` feature) with Temperature, Humidity and Pressure.
+//!
+//! Each characteristic is readable and notifiable and carries an ES Trigger
+//! Setting descriptor ("when value changes"), so collectors get a
+//! notification on every `set_*` call.
+//!
+//! ```ignore
+//! let ess = EnvironmentalSensingService::register(&EssConfig::default())?;
+//! ess.set_temperature(Temperature::from_centi_celsius(2150))?; // 21.50 °C
+//! ess.set_humidity(Humidity::from_centi_percent(4520))?;      // 45.20 %
+//! ```
+
+use crate::gatt::{self, ATTR_ACCESS_READ_ONLY, CHAR_PROP_NOTIFY, CHAR_PROP_READ, Char, Service};
+use crate::status::Result;
+
+pub const SERVICE_UUID: u16 = 0x181A;
+pub const PRESSURE_UUID: u16 = 0x2A6D;
+pub const TEMPERATURE_UUID: u16 = 0x2A6E;
+pub const HUMIDITY_UUID: u16 = 0x2A6F;
+pub const ES_TRIGGER_SETTING_UUID: u16 = 0x290D;
+
+/// Declaration, value, CCCD and ES Trigger Setting per characteristic.
+pub const RECORDS_PER_CHARACTERISTIC: u16 = 4;
+
+/// ES Trigger Setting condition: notify when the value changes.
+const TRIGGER_VALUE_CHANGED: u8 = 0x03;
+
+/// Temperature (0x2A6E): sint16 in 0.01 °C.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Temperature(i16);
+
+impl Temperature {
+    /// Value not known.
+    pub const UNKNOWN: Self = Self(i16::MIN);
+
+    pub const fn from_centi_celsius(v: i16) -> Self {
+        Self(v)
+    }
+
+    pub const fn encode(self) -> [u8; 2] {
+        self.0.to_le_bytes()
+    }
+}
+
+/// Humidity (0x2A6F): uint16 in 0.01 %.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Humidity(u16);
+
+impl Humidity {
+    /// Value not known.
+    pub const UNKNOWN: Self = Self(0xFFFF);
+
+    /// Clamped to 100.00 %.
+    pub const fn from_centi_percent(v: u16) -> Self {
+        Self(if v > 10_000 { 10_000 } else { v })
+    }
+
+    pub const fn encode(self) -> [u8; 2] {
+        self.0.to_le_bytes()
+    }
+}
+
+/// Pressure (0x2A6D): uint32 in 0.1 Pa.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Pressure(u32);
+
+impl Pressure {
+    pub const fn from_deci_pascal(v: u32) -> Self {
+        Self(v)
+    }
+
+    pub const fn from_pascal(v: u32) -> Self {
+        Self(v.saturating_mul(10))
+    }
+
+    pub const fn encode(self) -> [u8; 4] {
+        self.0.to_le_bytes()
+    }
+}
+
+/// Which characteristics to register.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct EssConfig {
+    pub temperature: bool,
+    pub humidity: bool,
+    pub pressure: bool,
+}
+
+impl Default for EssConfig {
+    fn default() -> Self {
+        Self {
+            temperature: true,
+            humidity: true,
+            pressure: false,
+        }
+    }
+}
+
+impl EssConfig {
+    /// Attribute records the service needs (service declaration included).
+    pub fn attribute_records(&self) -> u16 {
+        let chars = self.temperature as u16 + self.humidity as u16 + self.pressure as u16;
+        1 + chars * RECORDS_PER_CHARACTERISTIC
+    }
+}
+
+/// A registered Environmental Sensing Service.
+pub struct EnvironmentalSensingService {
+    service: Service,
+    temperature: Option<Char>,
+    humidity: Option<Char>,
+    pressure: Option<Char>,
+}
+
+fn add_sensor(service: &Service, enabled: bool, uuid16: u16, init: &[u8]) -> Result<Option<Char>> {
+    if !enabled {
+        return Ok(None);
+    }
+    let ch = gatt::add_char(
+        service,
+        uuid16,
+        CHAR_PROP_READ | CHAR_PROP_NOTIFY,
+        gatt::ATTR_PERMISSION_NONE,
+        init.len() as u16,
+    )?;
+    gatt::add_descriptor(
+        service,
+        &ch,
+        ES_TRIGGER_SETTING_UUID,
+        gatt::ATTR_PERMISSION_NONE,
+        ATTR_ACCESS_READ_ONLY,
+        &[TRIGGER_VALUE_CHANGED],
+        1,
+    )?;
+    gatt::update_char_value(service, &ch, init)?;
+    Ok(Some(ch))
+}
+
+impl EnvironmentalSensingService {
+    /// Add the service to the GATT database; call once after stack init.
+    pub fn register(cfg: &EssConfig) -> Result<Self> {
+        let service = gatt::add_primary_service(SERVICE_UUID, cfg.attribute_records() as u8)?;
+        let temperature = add_sensor(
+            &service,
+            cfg.temperature,
+            TEMPERATURE_UUID,
+            &Temperature::UNKNOWN.encode(),
+        )?;
+        let humidity = add_sensor(
+            &service,
+            cfg.humidity,
+            HUMIDITY_UUID,
+            &Humidity::UNKNOWN.encode(),
+        )?;
+        let pressure = add_sensor(
+            &service,
+            cfg.pressure,
+            PRESSURE_UUID,
+            &Pressure::default().encode(),
+        )?;
+        Ok(EnvironmentalSensingService {
+            service,
+            temperature,
+            humidity,
+            pressure,
+        })
+    }
+
+    fn set(&self, ch: &Option<Char>, val: &[u8]) -> Result<()> {
+        match ch {
+            Some(ch) => gatt::update_char_value(&self.service, ch, val),
+            None => Ok(()),
+        }
+    }
+
+    /// Update Temperature and notify subscribers; no-op if not registered.
+    pub fn set_temperature(&self, t: Temperature) -> Result<()> {
+        self.set(&self.temperature, &t.encode())
+    }
+
+    /// Update Humidity and notify subscribers; no-op if not registered.
+    pub fn set_humidity(&self, h: Humidity) -> Result<()> {
+        self.set(&self.humidity, &h.encode())
+    }
+
+    /// Update Pressure and notify subscribers; no-op if not registered.
+    pub fn set_pressure(&self, p: Pressure) -> Result<()> {
+        self.set(&self.pressure, &p.encode())
+    }
+
+    pub fn service(&self) -> &Service {
+        &self.service
+    }
+}
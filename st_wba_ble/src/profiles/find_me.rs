@@ -0,0 +1,257 @@
+// st_wba_ble/src/profiles/find_me.rs
+//! Find Me target: Immediate Alert Service (0x1802) and, optionally, the
+//! Link Loss Service (0x1803) (enabled with the `profile-find-me` feature).
+//!
+//! Levels are kept per link. The handler runs in the stack's event context
+//! whenever the alert level changes: when a locator writes Immediate Alert
+//! (with the highest level any link asks for), when the link carrying an
+//! immediate alert goes down, and when a link is lost through a supervision
+//! timeout (at the Link Loss level that peer configured). Keep it short:
+//! start a buzzer or signal a task.
+//!
+//! ```ignore
+//! fn on_alert(source: AlertSource, level: AlertLevel) {
+//!     BUZZER.signal(level);
+//! }
+//! FindMeTarget::register(&FindMeConfig { link_loss: true, on_alert })?;
+//! ```
+
+use crate::conn::{self, Connection, Disconnection, MAX_CONNECTIONS};
+use crate::gatt::{
+    self, AttributeWrite, CHAR_PROP_READ, CHAR_PROP_WRITE, CHAR_PROP_WRITE_WITHOUT_RESP, Char,
+    GATT_NOTIFY_ATTRIBUTE_WRITE, Service,
+};
+use crate::status::Result;
+use core::cell::Cell;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+
+pub const IMMEDIATE_ALERT_SERVICE_UUID: u16 = 0x1802;
+pub const LINK_LOSS_SERVICE_UUID: u16 = 0x1803;
+pub const ALERT_LEVEL_UUID: u16 = 0x2A06;
+
+/// Records per service (service declaration, Alert Level declaration and value).
+pub const SERVICE_RECORDS: u16 = 3;
+
+/// HCI disconnect reason: Connection Timeout.
+const REASON_CONNECTION_TIMEOUT: u8 = 0x08;
+
+/// Alert Level (0x2A06).
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd)]
+#[repr(u8)]
+pub enum AlertLevel {
+    #[default]
+    NoAlert = 0,
+    MildAlert = 1,
+    HighAlert = 2,
+}
+
+impl AlertLevel {
+    pub fn from_u8(v: u8) -> Option<Self> {
+        match v {
+            0 => Some(AlertLevel::NoAlert),
+            1 => Some(AlertLevel::MildAlert),
+            2 => Some(AlertLevel::HighAlert),
+            _ => None,
+        }
+    }
+}
+
+/// Which service raised the alert.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum AlertSource {
+    ImmediateAlert,
+    LinkLoss,
+}
+
+/// Called with every alert level change.
+pub type AlertHandler = fn(AlertSource, AlertLevel);
+
+/// What to register.
+#[derive(Copy, Clone, Debug)]
+pub struct FindMeConfig {
+    /// Also register the Link Loss Service.
+    pub link_loss: bool,
+    pub on_alert: AlertHandler,
+}
+
+impl FindMeConfig {
+    /// Attribute records both services need.
+    pub fn attribute_records(&self) -> u16 {
+        SERVICE_RECORDS * if self.link_loss { 2 } else { 1 }
+    }
+}
+
+#[derive(Copy, Clone)]
+struct State {
+    handler: Option<AlertHandler>,
+    immediate_handle: u16,
+    link_loss_handle: u16,
+}
+
+/// Levels written by the locator on one link.
+#[derive(Copy, Clone)]
+struct LinkAlert {
+    conn: u16,
+    immediate: AlertLevel,
+    link_loss: AlertLevel,
+}
+
+type Links = [Option<LinkAlert>; MAX_CONNECTIONS];
+
+static STATE: Mutex<CriticalSectionRawMutex, Cell<State>> = Mutex::new(Cell::new(State {
+    handler: None,
+    immediate_handle: 0,
+    link_loss_handle: 0,
+}));
+static LINKS: Mutex<CriticalSectionRawMutex, Cell<Links>> =
+    Mutex::new(Cell::new([None; MAX_CONNECTIONS]));
+
+/// Highest Immediate Alert level over all links.
+fn immediate(links: &Links) -> AlertLevel {
+    links
+        .iter()
+        .flatten()
+        .map(|l| l.immediate)
+        .max()
+        .unwrap_or_default()
+}
+
+/// Registered Immediate Alert (and Link Loss) services.
+pub struct FindMeTarget {
+    immediate_alert: Service,
+    link_loss: Option<(Service, Char)>,
+}
+
+impl FindMeTarget {
+    /// Add the services to the GATT database; call once after stack init.
+    pub fn register(cfg: &FindMeConfig) -> Result<Self> {
+        let immediate_alert =
+            gatt::add_primary_service(IMMEDIATE_ALERT_SERVICE_UUID, SERVICE_RECORDS as u8)?;
+        let immediate = gatt::add_char_with_mask(
+            &immediate_alert,
+            ALERT_LEVEL_UUID,
+            CHAR_PROP_WRITE_WITHOUT_RESP,
+            gatt::ATTR_PERMISSION_NONE,
+            1,
+            GATT_NOTIFY_ATTRIBUTE_WRITE,
+        )?;
+        let link_loss = if cfg.link_loss {
+            let svc = gatt::add_primary_service(LINK_LOSS_SERVICE_UUID, SERVICE_RECORDS as u8)?;
+            let ch = gatt::add_char_with_mask(
+                &svc,
+                ALERT_LEVEL_UUID,
+                CHAR_PROP_READ | CHAR_PROP_WRITE,
+                gatt::ATTR_PERMISSION_NONE,
+                1,
+                GATT_NOTIFY_ATTRIBUTE_WRITE,
+            )?;
+            gatt::update_char_value(&svc, &ch, &[AlertLevel::NoAlert as u8])?;
+            Some((svc, ch))
+        } else {
+            None
+        };
+        STATE.lock(|s| {
+            s.set(State {
+                handler: Some(cfg.on_alert),
+                immediate_handle: immediate.value_handle(),
+                link_loss_handle: link_loss.as_ref().map_or(0, |(_, ch)| ch.value_handle()),
+            })
+        });
+        LINKS.lock(|l| l.set([None; MAX_CONNECTIONS]));
+        gatt::add_write_listener(on_write)?;
+        conn::add_disconnect_listener(on_link_closed)?;
+        Ok(FindMeTarget {
+            immediate_alert,
+            link_loss,
+        })
+    }
+
+    /// Current Immediate Alert level (the highest any link asks for).
+    pub fn immediate_level(&self) -> AlertLevel {
+        LINKS.lock(|l| immediate(&l.get()))
+    }
+
+    /// Level to alert at if `conn` is lost, as configured by its peer.
+    pub fn link_loss_level(&self, conn: &Connection) -> AlertLevel {
+        LINKS.lock(|l| {
+            l.get()
+                .iter()
+                .flatten()
+                .find(|e| e.conn == conn.handle())
+                .map_or(AlertLevel::NoAlert, |e| e.link_loss)
+        })
+    }
+
+    pub fn immediate_alert_service(&self) -> &Service {
+        &self.immediate_alert
+    }
+
+    pub fn link_loss_service(&self) -> Option<&Service> {
+        self.link_loss.as_ref().map(|(svc, _)| svc)
+    }
+}
+
+fn on_write(w: &AttributeWrite<'_>) {
+    let Some(level) = w.data.first().copied().and_then(AlertLevel::from_u8) else {
+        return;
+    };
+    let st = STATE.lock(|s| s.get());
+    let is_immediate = st.immediate_handle != 0 && w.handle == st.immediate_handle;
+    let is_link_loss = st.link_loss_handle != 0 && w.handle == st.link_loss_handle;
+    if !is_immediate && !is_link_loss {
+        return;
+    }
+    let Some(conn) = gatt::bearer_connection(w.bearer) else {
+        return;
+    };
+    let fire = LINKS.lock(|l| {
+        let mut links = l.get();
+        let slot = match links.iter().position(|e| e.is_some_and(|e| e.conn == conn)) {
+            Some(i) => i,
+            None => links.iter().position(Option::is_none)?,
+        };
+        let entry = links[slot].get_or_insert(LinkAlert {
+            conn,
+            immediate: AlertLevel::NoAlert,
+            link_loss: AlertLevel::NoAlert,
+        });
+        if is_immediate {
+            entry.immediate = level;
+        } else {
+            // Only stored; the alert itself happens on link loss.
+            entry.link_loss = level;
+        }
+        l.set(links);
+        is_immediate.then(|| immediate(&links))
+    });
+    if let Some((level, f)) = fire.zip(st.handler) {
+        f(AlertSource::ImmediateAlert, level);
+    }
+}
+
+/// Stop the closed link's immediate alert and raise its Link Loss alert if
+/// the link timed out.
+fn on_link_closed(d: &Disconnection) {
+    let Some((before, after, closed)) = LINKS.lock(|l| {
+        let mut links = l.get();
+        let before = immediate(&links);
+        let closed = links
+            .iter_mut()
+            .find(|e| e.is_some_and(|e| e.conn == d.handle))?
+            .take()?;
+        l.set(links);
+        Some((before, immediate(&links), closed))
+    }) else {
+        return;
+    };
+    let Some(f) = STATE.lock(|s| s.get().handler) else {
+        return;
+    };
+    if after != before {
+        f(AlertSource::ImmediateAlert, after);
+    }
+    if d.reason == REASON_CONNECTION_TIMEOUT && closed.link_loss != AlertLevel::NoAlert {
+        f(AlertSource::LinkLoss, closed.link_loss);
+    }
+}
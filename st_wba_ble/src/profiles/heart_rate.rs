@@ -0,0 +1,286 @@
+// st_wba_ble/src/profiles/heart_rate.rs
+//! Heart Rate Service (0x180D) sensor role (enabled with the
+//! `profile-heart-rate` feature).
+//!
+//! Heart Rate Measurement is notified; Body Sensor Location and, when energy
+//! expended is reported, the Heart Rate Control Point are registered as
+//! configured.
+//!
+//! ```ignore
+//! let hrs = HeartRateService::register(&HeartRateConfig::default())?;
+//! hrs.notify_measurement(&HeartRateMeasurement {
+//!     bpm: 72,
+//!     contact: Some(true),
+//!     rr_intervals: &[853],
+//!     ..Default::default()
+//! })?;
+//! ```
+
+use crate::gatt::{
+    self, AttributeWrite, Bearer, CHAR_PROP_NOTIFY, CHAR_PROP_READ, CHAR_PROP_WRITE, Char,
+    GATT_NOTIFY_ATTRIBUTE_WRITE, Service,
+};
+use crate::status::Result;
+use core::cell::Cell;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+
+pub const SERVICE_UUID: u16 = 0x180D;
+pub const MEASUREMENT_UUID: u16 = 0x2A37;
+pub const BODY_SENSOR_LOCATION_UUID: u16 = 0x2A38;
+pub const CONTROL_POINT_UUID: u16 = 0x2A39;
+
+/// Service declaration plus Heart Rate Measurement (declaration, value, CCCD).
+pub const ATTRIBUTE_RECORDS: u16 = 4;
+/// Additional records for Body Sensor Location.
+pub const BODY_SENSOR_LOCATION_RECORDS: u16 = 2;
+/// Additional records for the Heart Rate Control Point.
+pub const CONTROL_POINT_RECORDS: u16 = 2;
+
+/// Longest encoded measurement; fits the default ATT MTU. RR-intervals that
+/// do not fit are dropped.
+pub const MAX_MEASUREMENT_LEN: usize = 20;
+
+/// Heart Rate Control Point opcode: reset Energy Expended.
+const RESET_ENERGY_EXPENDED: u8 = 0x01;
+
+const FLAG_VALUE_U16: u8 = 0x01;
+const FLAG_CONTACT_DETECTED: u8 = 0x02;
+const FLAG_CONTACT_SUPPORTED: u8 = 0x04;
+const FLAG_ENERGY_EXPENDED: u8 = 0x08;
+const FLAG_RR_INTERVAL: u8 = 0x10;
+
+/// Body Sensor Location (0x2A38).
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum BodySensorLocation {
+    Other = 0,
+    Chest = 1,
+    Wrist = 2,
+    Finger = 3,
+    Hand = 4,
+    EarLobe = 5,
+    Foot = 6,
+}
+
+/// Heart Rate Measurement (0x2A37).
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct HeartRateMeasurement<'a> {
+    /// Beats per minute; sent as UINT8 when ≤ 255.
+    pub bpm: u16,
+    /// Skin contact; `None` if the sensor cannot detect it.
+    pub contact: Option<bool>,
+    /// Energy expended since the last reset, in kJ (saturates at 0xFFFF).
+    pub energy_expended: Option<u16>,
+    /// RR-intervals in 1/1024 s, oldest first.
+    pub rr_intervals: &'a [u16],
+}
+
+impl HeartRateMeasurement<'_> {
+    /// Encode into `buf`, returning the length used.
+    pub fn encode(&self, buf: &mut [u8; MAX_MEASUREMENT_LEN]) -> usize {
+        let mut flags = 0;
+        let mut n = 1;
+        if self.bpm > u8::MAX as u16 {
+            flags |= FLAG_VALUE_U16;
+            buf[n..n + 2].copy_from_slice(&self.bpm.to_le_bytes());
+            n += 2;
+        } else {
+            buf[n] = self.bpm as u8;
+            n += 1;
+        }
+        match self.contact {
+            Some(true) => flags |= FLAG_CONTACT_SUPPORTED | FLAG_CONTACT_DETECTED,
+            Some(false) => flags |= FLAG_CONTACT_SUPPORTED,
+            None => {}
+        }
+        if let Some(kj) = self.energy_expended {
+            flags |= FLAG_ENERGY_EXPENDED;
+            buf[n..n + 2].copy_from_slice(&kj.to_le_bytes());
+            n += 2;
+        }
+        let room = (MAX_MEASUREMENT_LEN - n) / 2;
+        if !self.rr_intervals.is_empty() {
+            flags |= FLAG_RR_INTERVAL;
+            for rr in self.rr_intervals.iter().take(room) {
+                buf[n..n + 2].copy_from_slice(&rr.to_le_bytes());
+                n += 2;
+            }
+        }
+        buf[0] = flags;
+        n
+    }
+}
+
+/// What to register.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct HeartRateConfig {
+    pub body_sensor_location: Option<BodySensorLocation>,
+    /// Energy expended is reported (adds the Heart Rate Control Point).
+    pub energy_expended: bool,
+}
+
+impl Default for HeartRateConfig {
+    fn default() -> Self {
+        Self {
+            body_sensor_location: Some(BodySensorLocation::Chest),
+            energy_expended: false,
+        }
+    }
+}
+
+impl HeartRateConfig {
+    /// Attribute records the service needs (service declaration included).
+    pub fn attribute_records(&self) -> u16 {
+        ATTRIBUTE_RECORDS
+            + if self.body_sensor_location.is_some() {
+                BODY_SENSOR_LOCATION_RECORDS
+            } else {
+                0
+            }
+            + if self.energy_expended {
+                CONTROL_POINT_RECORDS
+            } else {
+                0
+            }
+    }
+}
+
+static CONTROL_POINT: Mutex<CriticalSectionRawMutex, Cell<u16>> = Mutex::new(Cell::new(0));
+static RESET: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// Wait until a collector asks to reset Energy Expended.
+pub async fn energy_expended_reset() {
+    RESET.wait().await
+}
+
+/// A registered Heart Rate Service.
+pub struct HeartRateService {
+    service: Service,
+    measurement: Char,
+}
+
+impl HeartRateService {
+    /// Add the service to the GATT database; call once after stack init.
+    pub fn register(cfg: &HeartRateConfig) -> Result<Self> {
+        let service = gatt::add_primary_service(SERVICE_UUID, cfg.attribute_records() as u8)?;
+        let measurement = gatt::add_char_variable(
+            &service,
+            MEASUREMENT_UUID,
+            CHAR_PROP_NOTIFY,
+            gatt::ATTR_PERMISSION_NONE,
+            MAX_MEASUREMENT_LEN as u16,
+            0,
+            0,
+        )?;
+        if let Some(loc) = cfg.body_sensor_location {
+            let ch = gatt::add_char(
+                &service,
+                BODY_SENSOR_LOCATION_UUID,
+                CHAR_PROP_READ,
+                gatt::ATTR_PERMISSION_NONE,
+                1,
+            )?;
+            gatt::update_char_value(&service, &ch, &[loc as u8])?;
+        }
+        if cfg.energy_expended {
+            let ch = gatt::add_char_with_mask(
+                &service,
+                CONTROL_POINT_UUID,
+                CHAR_PROP_WRITE,
+                gatt::ATTR_PERMISSION_NONE,
+                1,
+                GATT_NOTIFY_ATTRIBUTE_WRITE,
+            )?;
+            CONTROL_POINT.lock(|c| c.set(ch.value_handle()));
+            gatt::add_write_listener(on_write)?;
+        }
+        Ok(HeartRateService {
+            service,
+            measurement,
+        })
+    }
+
+    /// Notify a measurement to every subscribed collector.
+    pub fn notify_measurement(&self, m: &HeartRateMeasurement<'_>) -> Result<()> {
+        let mut buf = [0u8; MAX_MEASUREMENT_LEN];
+        let n = m.encode(&mut buf);
+        gatt::update_char_value(&self.service, &self.measurement, &buf[..n])
+    }
+
+    /// Notify a measurement on one bearer only.
+    pub fn notify_measurement_to(
+        &self,
+        bearer: Bearer,
+        m: &HeartRateMeasurement<'_>,
+    ) -> Result<()> {
+        let mut buf = [0u8; MAX_MEASUREMENT_LEN];
+        let n = m.encode(&mut buf);
+        gatt::notify(bearer, &self.service, &self.measurement, &buf[..n])
+    }
+
+    pub fn service(&self) -> &Service {
+        &self.service
+    }
+}
+
+fn on_write(w: &AttributeWrite<'_>) {
+    let cp = CONTROL_POINT.lock(|c| c.get());
+    if cp != 0 && w.handle == cp && w.data.first() == Some(&RESET_ENERGY_EXPENDED) {
+        RESET.signal(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(m: &HeartRateMeasurement<'_>) -> Vec<u8> {
+        let mut buf = [0u8; MAX_MEASUREMENT_LEN];
+        let n = m.encode(&mut buf);
+        buf[..n].to_vec()
+    }
+
+    #[test]
+    fn measurement_u8_with_contact() {
+        let m = HeartRateMeasurement {
+            bpm: 72,
+            contact: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(encode(&m), [0x06, 72]);
+        let m = HeartRateMeasurement {
+            contact: Some(false),
+            ..m
+        };
+        assert_eq!(encode(&m), [0x04, 72]);
+    }
+
+    #[test]
+    fn measurement_u16_energy_rr() {
+        let m = HeartRateMeasurement {
+            bpm: 300,
+            contact: None,
+            energy_expended: Some(1000),
+            rr_intervals: &[1024, 512],
+        };
+        assert_eq!(
+            encode(&m),
+            [0x19, 0x2C, 0x01, 0xE8, 0x03, 0x00, 0x04, 0x00, 0x02]
+        );
+    }
+
+    #[test]
+    fn measurement_drops_rr_intervals_that_do_not_fit() {
+        let rr = [1u16; 12];
+        let m = HeartRateMeasurement {
+            bpm: 60,
+            rr_intervals: &rr,
+            ..Default::default()
+        };
+        let b = encode(&m);
+        assert_eq!(b[0], FLAG_RR_INTERVAL);
+        assert_eq!(b.len(), MAX_MEASUREMENT_LEN);
+    }
+}
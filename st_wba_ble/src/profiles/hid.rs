@@ -0,0 +1,429 @@
+// st_wba_ble/src/profiles/hid.rs
+//! HID over GATT (HOGP) device role: HID Service (0x1812) for keyboards,
+//! mice and consumer controls (enabled with the `profile-hid` feature).
+//!
+//! Registers HID Information, Report Map, HID Control Point, the configured
+//! reports (each with a Report Reference descriptor) and, when boot
+//! protocol is supported, Protocol Mode and the boot keyboard/mouse reports.
+//! HOGP requires an encrypted, bonded link: every characteristic needs
+//! encryption, so pair with bonding enabled (see [`crate::Security`]).
+//!
+//! ```ignore
+//! static REPORTS: [ReportSpec; 2] = [
+//!     ReportSpec { id: 1, kind: ReportType::Input, len: 8 },  // keyboard
+//!     ReportSpec { id: 2, kind: ReportType::Input, len: 2 },  // consumer control
+//! ];
+//! let hid = HidService::register(&HidConfig {
+//!     report_map: &REPORT_MAP,
+//!     reports: &REPORTS,
+//!     boot_keyboard: true,
+//!     ..HidConfig::DEFAULT
+//! })?;
+//! hid.send_input(2, &0x00E9u16.to_le_bytes())?; // Volume Up
+//! match hid::next_event().await { HidEvent::OutputReport { id, .. } => {}, _ => {} }
+//! ```
+
+use crate::gatt::{
+    self, ATTR_ACCESS_READ_ONLY, AttributeWrite, CHAR_PROP_NOTIFY, CHAR_PROP_READ, CHAR_PROP_WRITE,
+    CHAR_PROP_WRITE_WITHOUT_RESP, Char, GATT_NOTIFY_ATTRIBUTE_WRITE, Service,
+};
+use crate::status::{self, BleStatus, Result};
+use core::cell::RefCell;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+
+pub const SERVICE_UUID: u16 = 0x1812;
+pub const BOOT_KEYBOARD_INPUT_UUID: u16 = 0x2A22;
+pub const BOOT_KEYBOARD_OUTPUT_UUID: u16 = 0x2A32;
+pub const BOOT_MOUSE_INPUT_UUID: u16 = 0x2A33;
+pub const HID_INFORMATION_UUID: u16 = 0x2A4A;
+pub const REPORT_MAP_UUID: u16 = 0x2A4B;
+pub const HID_CONTROL_POINT_UUID: u16 = 0x2A4C;
+pub const REPORT_UUID: u16 = 0x2A4D;
+pub const PROTOCOL_MODE_UUID: u16 = 0x2A4E;
+pub const REPORT_REFERENCE_UUID: u16 = 0x2908;
+
+/// Reports that can be configured.
+pub const MAX_REPORTS: usize = 8;
+/// Longest report value.
+pub const MAX_REPORT_LEN: usize = 64;
+/// Longest Report Map.
+pub const MAX_REPORT_MAP_LEN: usize = 512;
+/// Boot keyboard input report length.
+pub const BOOT_KEYBOARD_INPUT_LEN: usize = 8;
+/// Boot mouse input report length (buttons, X, Y plus optional extra bytes).
+pub const BOOT_MOUSE_INPUT_LEN: usize = 3;
+
+/// HOGP: all HID characteristics need an encrypted link.
+const PERM: u8 = gatt::ATTR_PERMISSION_ENCRY_READ | gatt::ATTR_PERMISSION_ENCRY_WRITE;
+
+/// Report type in the Report Reference descriptor.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum ReportType {
+    Input = 1,
+    Output = 2,
+    Feature = 3,
+}
+
+/// One report declared by the Report Map.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ReportSpec {
+    /// Report ID as used in the Report Map (0 if the map uses none).
+    pub id: u8,
+    pub kind: ReportType,
+    /// Report length in bytes, without the ID.
+    pub len: u8,
+}
+
+/// HID Information (0x2A4A).
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct HidInfo {
+    /// HID specification release in BCD (0x0111 = 1.11).
+    pub bcd_hid: u16,
+    pub country_code: u8,
+    pub remote_wake: bool,
+    pub normally_connectable: bool,
+}
+
+impl HidInfo {
+    pub fn encode(&self) -> [u8; 4] {
+        let [lo, hi] = self.bcd_hid.to_le_bytes();
+        let flags = self.remote_wake as u8 | (self.normally_connectable as u8) << 1;
+        [lo, hi, self.country_code, flags]
+    }
+}
+
+/// What to register.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct HidConfig {
+    pub info: HidInfo,
+    /// USB HID report descriptor (≤ [`MAX_REPORT_MAP_LEN`]).
+    pub report_map: &'static [u8],
+    pub reports: &'static [ReportSpec],
+    /// Boot keyboard input/output reports (implies Protocol Mode).
+    pub boot_keyboard: bool,
+    /// Boot mouse input report (implies Protocol Mode).
+    pub boot_mouse: bool,
+}
+
+impl HidConfig {
+    pub const DEFAULT: Self = Self {
+        info: HidInfo {
+            bcd_hid: 0x0111,
+            country_code: 0,
+            remote_wake: true,
+            normally_connectable: true,
+        },
+        report_map: &[],
+        reports: &[],
+        boot_keyboard: false,
+        boot_mouse: false,
+    };
+
+    fn boot(&self) -> bool {
+        self.boot_keyboard || self.boot_mouse
+    }
+
+    /// Attribute records the service needs (service declaration included).
+    pub fn attribute_records(&self) -> u16 {
+        // Service, HID Information, Report Map, Control Point.
+        let mut n = 1 + 2 + 2 + 2;
+        if self.boot() {
+            n += 2;
+        }
+        for r in self.reports {
+            // Declaration, value, Report Reference (+ CCCD for input reports).
+            n += 3 + (r.kind == ReportType::Input) as u16;
+        }
+        if self.boot_keyboard {
+            n += 3 + 2;
+        }
+        if self.boot_mouse {
+            n += 3;
+        }
+        n
+    }
+}
+
+/// Protocol Mode (0x2A4E).
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ProtocolMode {
+    Boot,
+    Report,
+}
+
+/// Something a HID host did.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum HidEvent {
+    ProtocolMode(ProtocolMode),
+    /// HID Control Point: true on Suspend, false on Exit Suspend.
+    Suspend(bool),
+    /// Output report written (e.g. keyboard LEDs); `id` 0 for the boot keyboard output.
+    OutputReport {
+        id: u8,
+        data: [u8; MAX_REPORT_LEN],
+        len: usize,
+    },
+    FeatureReport {
+        id: u8,
+        data: [u8; MAX_REPORT_LEN],
+        len: usize,
+    },
+}
+
+#[derive(Copy, Clone)]
+struct ReportHandle {
+    id: u8,
+    kind: ReportType,
+    value: u16,
+}
+
+#[derive(Copy, Clone)]
+struct Handles {
+    protocol_mode: Option<u16>,
+    control_point: u16,
+    boot_keyboard_output: Option<u16>,
+    reports: [Option<ReportHandle>; MAX_REPORTS],
+}
+
+static HANDLES: Mutex<CriticalSectionRawMutex, RefCell<Option<Handles>>> =
+    Mutex::new(RefCell::new(None));
+static MODE: Mutex<CriticalSectionRawMutex, RefCell<ProtocolMode>> =
+    Mutex::new(RefCell::new(ProtocolMode::Report));
+static EVENTS: Channel<CriticalSectionRawMutex, HidEvent, 4> = Channel::new();
+
+/// Wait for the next [`HidEvent`].
+pub async fn next_event() -> HidEvent {
+    EVENTS.receive().await
+}
+
+/// A registered HID Service.
+pub struct HidService {
+    service: Service,
+    reports: [Option<(ReportSpec, Char)>; MAX_REPORTS],
+    boot_keyboard_input: Option<Char>,
+    boot_mouse_input: Option<Char>,
+}
+
+impl HidService {
+    /// Add the service; call once after stack init.
+    pub fn register(cfg: &HidConfig) -> Result<Self> {
+        if cfg.reports.len() > MAX_REPORTS
+            || cfg.report_map.is_empty()
+            || cfg.report_map.len() > MAX_REPORT_MAP_LEN
+            || cfg.reports.iter().any(|r| r.len as usize > MAX_REPORT_LEN)
+        {
+            return Err(BleStatus::Other(status::INVALID_PARAMS));
+        }
+        let service = gatt::add_primary_service(SERVICE_UUID, cfg.attribute_records() as u8)?;
+
+        let protocol_mode = if cfg.boot() {
+            let ch = gatt::add_char_with_mask(
+                &service,
+                PROTOCOL_MODE_UUID,
+                CHAR_PROP_READ | CHAR_PROP_WRITE_WITHOUT_RESP,
+                PERM,
+                1,
+                GATT_NOTIFY_ATTRIBUTE_WRITE,
+            )?;
+            gatt::update_char_value(&service, &ch, &[1])?; // Report Protocol Mode
+            Some(ch.value_handle())
+        } else {
+            None
+        };
+
+        let mut reports = [const { None }; MAX_REPORTS];
+        let mut handles = [None; MAX_REPORTS];
+        for (i, spec) in cfg.reports.iter().enumerate() {
+            let props = match spec.kind {
+                ReportType::Input => CHAR_PROP_READ | CHAR_PROP_NOTIFY,
+                ReportType::Output => {
+                    CHAR_PROP_READ | CHAR_PROP_WRITE | CHAR_PROP_WRITE_WITHOUT_RESP
+                }
+                ReportType::Feature => CHAR_PROP_READ | CHAR_PROP_WRITE,
+            };
+            let ch = gatt::add_char_with_mask(
+                &service,
+                REPORT_UUID,
+                props,
+                PERM,
+                spec.len as u16,
+                GATT_NOTIFY_ATTRIBUTE_WRITE,
+            )?;
+            gatt::add_descriptor(
+                &service,
+                &ch,
+                REPORT_REFERENCE_UUID,
+                gatt::ATTR_PERMISSION_ENCRY_READ,
+                ATTR_ACCESS_READ_ONLY,
+                &[spec.id, spec.kind as u8],
+                2,
+            )?;
+            handles[i] = Some(ReportHandle {
+                id: spec.id,
+                kind: spec.kind,
+                value: ch.value_handle(),
+            });
+            reports[i] = Some((*spec, ch));
+        }
+
+        let (boot_keyboard_input, boot_keyboard_output) = if cfg.boot_keyboard {
+            let input = gatt::add_char_with_mask(
+                &service,
+                BOOT_KEYBOARD_INPUT_UUID,
+                CHAR_PROP_READ | CHAR_PROP_NOTIFY,
+                PERM,
+                BOOT_KEYBOARD_INPUT_LEN as u16,
+                0,
+            )?;
+            let output = gatt::add_char_with_mask(
+                &service,
+                BOOT_KEYBOARD_OUTPUT_UUID,
+                CHAR_PROP_READ | CHAR_PROP_WRITE | CHAR_PROP_WRITE_WITHOUT_RESP,
+                PERM,
+                1,
+                GATT_NOTIFY_ATTRIBUTE_WRITE,
+            )?;
+            (Some(input), Some(output.value_handle()))
+        } else {
+            (None, None)
+        };
+        let boot_mouse_input = if cfg.boot_mouse {
+            Some(gatt::add_char_with_mask(
+                &service,
+                BOOT_MOUSE_INPUT_UUID,
+                CHAR_PROP_READ | CHAR_PROP_NOTIFY,
+                PERM,
+                BOOT_MOUSE_INPUT_LEN as u16,
+                0,
+            )?)
+        } else {
+            None
+        };
+
+        let info = gatt::add_char(&service, HID_INFORMATION_UUID, CHAR_PROP_READ, PERM, 4)?;
+        gatt::update_char_value(&service, &info, &cfg.info.encode())?;
+
+        let map = gatt::add_char_variable(
+            &service,
+            REPORT_MAP_UUID,
+            CHAR_PROP_READ,
+            PERM,
+            cfg.report_map.len() as u16,
+            0,
+            0,
+        )?;
+        gatt::set_char_value(&service, &map, cfg.report_map)?;
+
+        let control_point = gatt::add_char_with_mask(
+            &service,
+            HID_CONTROL_POINT_UUID,
+            CHAR_PROP_WRITE_WITHOUT_RESP,
+            PERM,
+            1,
+            GATT_NOTIFY_ATTRIBUTE_WRITE,
+        )?;
+
+        HANDLES.lock(|h| {
+            *h.borrow_mut() = Some(Handles {
+                protocol_mode,
+                control_point: control_point.value_handle(),
+                boot_keyboard_output,
+                reports: handles,
+            })
+        });
+        MODE.lock(|m| *m.borrow_mut() = ProtocolMode::Report);
+        gatt::add_write_listener(on_write)?;
+
+        Ok(HidService {
+            service,
+            reports,
+            boot_keyboard_input,
+            boot_mouse_input,
+        })
+    }
+
+    /// Protocol mode selected by the host.
+    pub fn protocol_mode(&self) -> ProtocolMode {
+        MODE.lock(|m| *m.borrow())
+    }
+
+    fn report(&self, id: u8, kind: ReportType) -> Result<&Char> {
+        self.reports
+            .iter()
+            .flatten()
+            .find(|(s, _)| s.id == id && s.kind == kind)
+            .map(|(_, ch)| ch)
+            .ok_or(BleStatus::Other(status::INVALID_PARAMS))
+    }
+
+    /// Send input report `id` (Report protocol mode) to subscribed hosts.
+    pub fn send_input(&self, id: u8, data: &[u8]) -> Result<()> {
+        let ch = self.report(id, ReportType::Input)?;
+        gatt::update_char_value(&self.service, ch, data)
+    }
+
+    /// Set the value hosts read for feature report `id`.
+    pub fn set_feature(&self, id: u8, data: &[u8]) -> Result<()> {
+        let ch = self.report(id, ReportType::Feature)?;
+        gatt::update_char_value(&self.service, ch, data)
+    }
+
+    /// Send a boot keyboard input report (Boot protocol mode).
+    pub fn send_boot_keyboard(&self, report: &[u8; BOOT_KEYBOARD_INPUT_LEN]) -> Result<()> {
+        match &self.boot_keyboard_input {
+            Some(ch) => gatt::update_char_value(&self.service, ch, report),
+            None => Err(BleStatus::Other(status::INVALID_PARAMS)),
+        }
+    }
+
+    /// Send a boot mouse input report (Boot protocol mode).
+    pub fn send_boot_mouse(&self, report: &[u8; BOOT_MOUSE_INPUT_LEN]) -> Result<()> {
+        match &self.boot_mouse_input {
+            Some(ch) => gatt::update_char_value(&self.service, ch, report),
+            None => Err(BleStatus::Other(status::INVALID_PARAMS)),
+        }
+    }
+
+    pub fn service(&self) -> &Service {
+        &self.service
+    }
+}
+
+fn report_event(id: u8, kind: ReportType, bytes: &[u8]) -> HidEvent {
+    let mut data = [0u8; MAX_REPORT_LEN];
+    let len = bytes.len().min(MAX_REPORT_LEN);
+    data[..len].copy_from_slice(&bytes[..len]);
+    match kind {
+        ReportType::Feature => HidEvent::FeatureReport { id, data, len },
+        _ => HidEvent::OutputReport { id, data, len },
+    }
+}
+
+fn on_write(w: &AttributeWrite<'_>) {
+    let Some(h) = HANDLES.lock(|h| *h.borrow()) else {
+        return;
+    };
+    let event = if Some(w.handle) == h.protocol_mode {
+        let mode = match w.data.first() {
+            Some(0) => ProtocolMode::Boot,
+            _ => ProtocolMode::Report,
+        };
+        MODE.lock(|m| *m.borrow_mut() = mode);
+        HidEvent::ProtocolMode(mode)
+    } else if w.handle == h.control_point {
+        HidEvent::Suspend(w.data.first() == Some(&0))
+    } else if Some(w.handle) == h.boot_keyboard_output {
+        report_event(0, ReportType::Output, w.data)
+    } else if let Some(r) = h.reports.iter().flatten().find(|r| r.value == w.handle) {
+        if r.kind == ReportType::Input {
+            return;
+        }
+        report_event(r.id, r.kind, w.data)
+    } else {
+        return;
+    };
+    let _ = EVENTS.try_send(event);
+}
@@ -0,0 +1,276 @@
+// st_wba_ble/src/profiles/thermometer.rs
+//! Health Thermometer Service (0x1809) thermometer role (enabled with the
+//! `profile-thermometer` feature).
+//!
+//! Temperature Measurement is indicated; Temperature Type, Intermediate
+//! Temperature (notified) and Measurement Interval are registered as
+//! configured. Temperatures are IEEE 11073 [`Float`]s.
+//!
+//! ```ignore
+//! let hts = HealthThermometerService::register(&ThermometerConfig::default())?;
+//! hts.indicate_measurement(&TemperatureMeasurement::celsius(Float::new(3675, -2).unwrap()))?;
+//! ```
+
+use super::encoding::{DateTime, Float};
+use crate::gatt::{
+    self, Bearer, CHAR_PROP_INDICATE, CHAR_PROP_NOTIFY, CHAR_PROP_READ, Char, Service,
+};
+use crate::status::Result;
+
+pub const SERVICE_UUID: u16 = 0x1809;
+pub const TEMPERATURE_MEASUREMENT_UUID: u16 = 0x2A1C;
+pub const TEMPERATURE_TYPE_UUID: u16 = 0x2A1D;
+pub const INTERMEDIATE_TEMPERATURE_UUID: u16 = 0x2A1E;
+pub const MEASUREMENT_INTERVAL_UUID: u16 = 0x2A21;
+
+/// Service declaration plus Temperature Measurement (declaration, value, CCCD).
+pub const ATTRIBUTE_RECORDS: u16 = 4;
+
+/// Flags, value, time stamp and temperature type.
+pub const MAX_MEASUREMENT_LEN: usize = 1 + 4 + DateTime::LEN + 1;
+
+const FLAG_FAHRENHEIT: u8 = 0x01;
+const FLAG_TIME_STAMP: u8 = 0x02;
+const FLAG_TEMPERATURE_TYPE: u8 = 0x04;
+
+/// Temperature Type (0x2A1D): where the temperature was taken.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum TemperatureType {
+    Armpit = 1,
+    Body = 2,
+    Ear = 3,
+    Finger = 4,
+    GastroIntestinalTract = 5,
+    Mouth = 6,
+    Rectum = 7,
+    Toe = 8,
+    Tympanum = 9,
+}
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum TemperatureUnit {
+    #[default]
+    Celsius,
+    Fahrenheit,
+}
+
+/// Temperature Measurement (0x2A1C) / Intermediate Temperature (0x2A1E).
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct TemperatureMeasurement {
+    pub value: Float,
+    pub unit: TemperatureUnit,
+    pub time_stamp: Option<DateTime>,
+    /// Per-measurement location; leave `None` when a fixed Temperature Type
+    /// characteristic is registered.
+    pub temperature_type: Option<TemperatureType>,
+}
+
+impl TemperatureMeasurement {
+    pub fn celsius(value: Float) -> Self {
+        Self {
+            value,
+            unit: TemperatureUnit::Celsius,
+            time_stamp: None,
+            temperature_type: None,
+        }
+    }
+
+    /// Encode into `buf`, returning the length used.
+    pub fn encode(&self, buf: &mut [u8; MAX_MEASUREMENT_LEN]) -> usize {
+        let mut flags = 0;
+        if self.unit == TemperatureUnit::Fahrenheit {
+            flags |= FLAG_FAHRENHEIT;
+        }
+        buf[1..5].copy_from_slice(&self.value.to_le_bytes());
+        let mut n = 5;
+        if let Some(ts) = &self.time_stamp {
+            flags |= FLAG_TIME_STAMP;
+            buf[n..n + DateTime::LEN].copy_from_slice(&ts.encode());
+            n += DateTime::LEN;
+        }
+        if let Some(t) = self.temperature_type {
+            flags |= FLAG_TEMPERATURE_TYPE;
+            buf[n] = t as u8;
+            n += 1;
+        }
+        buf[0] = flags;
+        n
+    }
+}
+
+/// What to register.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct ThermometerConfig {
+    /// Fixed measurement location (Temperature Type characteristic).
+    pub temperature_type: Option<TemperatureType>,
+    /// Register Intermediate Temperature for readings in progress.
+    pub intermediate: bool,
+    /// Register Measurement Interval (read/indicate) with this many seconds.
+    pub measurement_interval: Option<u16>,
+}
+
+impl ThermometerConfig {
+    /// Attribute records the service needs (service declaration included).
+    pub fn attribute_records(&self) -> u16 {
+        let mut n = ATTRIBUTE_RECORDS;
+        if self.temperature_type.is_some() {
+            n += 2;
+        }
+        if self.intermediate {
+            n += 3;
+        }
+        if self.measurement_interval.is_some() {
+            n += 3;
+        }
+        n
+    }
+}
+
+/// A registered Health Thermometer Service.
+pub struct HealthThermometerService {
+    service: Service,
+    measurement: Char,
+    intermediate: Option<Char>,
+    interval: Option<Char>,
+}
+
+impl HealthThermometerService {
+    /// Add the service to the GATT database; call once after stack init.
+    pub fn register(cfg: &ThermometerConfig) -> Result<Self> {
+        let service = gatt::add_primary_service(SERVICE_UUID, cfg.attribute_records() as u8)?;
+        let measurement = gatt::add_char_variable(
+            &service,
+            TEMPERATURE_MEASUREMENT_UUID,
+            CHAR_PROP_INDICATE,
+            gatt::ATTR_PERMISSION_NONE,
+            MAX_MEASUREMENT_LEN as u16,
+            0,
+            0,
+        )?;
+        if let Some(t) = cfg.temperature_type {
+            let ch = gatt::add_char(
+                &service,
+                TEMPERATURE_TYPE_UUID,
+                CHAR_PROP_READ,
+                gatt::ATTR_PERMISSION_NONE,
+                1,
+            )?;
+            gatt::update_char_value(&service, &ch, &[t as u8])?;
+        }
+        let intermediate = if cfg.intermediate {
+            Some(gatt::add_char_variable(
+                &service,
+                INTERMEDIATE_TEMPERATURE_UUID,
+                CHAR_PROP_NOTIFY,
+                gatt::ATTR_PERMISSION_NONE,
+                MAX_MEASUREMENT_LEN as u16,
+                0,
+                0,
+            )?)
+        } else {
+            None
+        };
+        let interval = match cfg.measurement_interval {
+            Some(secs) => {
+                let ch = gatt::add_char(
+                    &service,
+                    MEASUREMENT_INTERVAL_UUID,
+                    CHAR_PROP_READ | CHAR_PROP_INDICATE,
+                    gatt::ATTR_PERMISSION_NONE,
+                    2,
+                )?;
+                gatt::update_char_value(&service, &ch, &secs.to_le_bytes())?;
+                Some(ch)
+            }
+            None => None,
+        };
+        Ok(HealthThermometerService {
+            service,
+            measurement,
+            intermediate,
+            interval,
+        })
+    }
+
+    /// Indicate a final measurement to every subscribed collector.
+    pub fn indicate_measurement(&self, m: &TemperatureMeasurement) -> Result<()> {
+        let mut buf = [0u8; MAX_MEASUREMENT_LEN];
+        let n = m.encode(&mut buf);
+        gatt::update_char_value(&self.service, &self.measurement, &buf[..n])
+    }
+
+    /// Indicate a final measurement on one bearer only.
+    pub fn indicate_measurement_to(
+        &self,
+        bearer: Bearer,
+        m: &TemperatureMeasurement,
+    ) -> Result<()> {
+        let mut buf = [0u8; MAX_MEASUREMENT_LEN];
+        let n = m.encode(&mut buf);
+        gatt::indicate(bearer, &self.service, &self.measurement, &buf[..n])
+    }
+
+    /// Notify an intermediate reading; no-op if it was not registered.
+    pub fn notify_intermediate(&self, m: &TemperatureMeasurement) -> Result<()> {
+        match &self.intermediate {
+            Some(ch) => {
+                let mut buf = [0u8; MAX_MEASUREMENT_LEN];
+                let n = m.encode(&mut buf);
+                gatt::update_char_value(&self.service, ch, &buf[..n])
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Change the Measurement Interval (seconds, 0 = no periodic
+    /// measurements) and indicate it; no-op if it was not registered.
+    pub fn set_measurement_interval(&self, secs: u16) -> Result<()> {
+        match &self.interval {
+            Some(ch) => gatt::update_char_value(&self.service, ch, &secs.to_le_bytes()),
+            None => Ok(()),
+        }
+    }
+
+    pub fn service(&self) -> &Service {
+        &self.service
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(m: &TemperatureMeasurement) -> Vec<u8> {
+        let mut buf = [0u8; MAX_MEASUREMENT_LEN];
+        let n = m.encode(&mut buf);
+        buf[..n].to_vec()
+    }
+
+    #[test]
+    fn measurement_celsius_only() {
+        let m = TemperatureMeasurement::celsius(Float::new(3675, -2).unwrap());
+        assert_eq!(encode(&m), [0x00, 0x5B, 0x0E, 0x00, 0xFE]);
+    }
+
+    #[test]
+    fn measurement_all_fields() {
+        let m = TemperatureMeasurement {
+            value: Float::new(986, -1).unwrap(),
+            unit: TemperatureUnit::Fahrenheit,
+            time_stamp: Some(DateTime {
+                year: 2024,
+                month: 1,
+                day: 2,
+                hours: 3,
+                minutes: 4,
+                seconds: 5,
+            }),
+            temperature_type: Some(TemperatureType::Ear),
+        };
+        assert_eq!(
+            encode(&m),
+            [0x07, 0xDA, 0x03, 0x00, 0xFF, 0xE8, 0x07, 1, 2, 3, 4, 5, 3]
+        );
+    }
+}
@@ -0,0 +1,277 @@
+// st_wba_ble/src/profiles/uart.rs
+//! Serial byte stream over GATT, compatible with the Nordic UART Service
+//! (enabled with the `profile-uart` feature).
+//!
+//! The peer writes to RX (write without response) and subscribes to TX
+//! (notify). [`UartService`] implements `embedded_io_async::Read` and
+//! `Write`, so shells and AT consoles can run on it unchanged. Writes are
+//! cut to the subscriber's ATT MTU and wait for stack buffers when the TX
+//! pool is full; received bytes are kept in a [`RX_BUFFER_LEN`] ring and
+//! dropped if it overflows.
+//!
+//! ```ignore
+//! let mut uart = UartService::register()?;
+//! let (rx, tx) = uart.split();
+//! let mut buf = [0u8; 64];
+//! let n = rx.read(&mut buf).await?;
+//! tx.write_all(&buf[..n]).await?; // echo
+//! ```
+
+use crate::conn::{self, Disconnection};
+use crate::gatt::{
+    self, AttributeWrite, Bearer, CHAR_PROP_NOTIFY, CHAR_PROP_WRITE_WITHOUT_RESP, Char,
+    GATT_NOTIFY_ATTRIBUTE_WRITE, Service,
+};
+use crate::status::{self, BleStatus, Result};
+use core::cell::RefCell;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use embedded_io_async::{ErrorType, Read, Write};
+
+/// 6E400001-B5A3-F393-E0A9-E50E24DCCA9E, little-endian as the ACI expects.
+pub const SERVICE_UUID: [u8; 16] = nus_uuid(0x01);
+/// 6E400002-…: peer → device.
+pub const RX_UUID: [u8; 16] = nus_uuid(0x02);
+/// 6E400003-…: device → peer.
+pub const TX_UUID: [u8; 16] = nus_uuid(0x03);
+
+const fn nus_uuid(n: u8) -> [u8; 16] {
+    [
+        0x9E, 0xCA, 0xDC, 0x24, 0x0E, 0xE5, 0xA9, 0xE0, 0x93, 0xF3, 0xA3, 0xB5, n, 0x00, 0x40, 0x6E,
+    ]
+}
+
+/// Service declaration, RX (declaration, value), TX (declaration, value, CCCD).
+pub const ATTRIBUTE_RECORDS: u16 = 6;
+/// Largest RX write / TX notification (ATT MTU 247).
+pub const MAX_CHUNK: usize = 244;
+/// Received bytes buffered until read.
+pub const RX_BUFFER_LEN: usize = 512;
+
+struct Ring {
+    buf: [u8; RX_BUFFER_LEN],
+    head: usize,
+    len: usize,
+    dropped: usize,
+}
+
+impl Ring {
+    fn push(&mut self, data: &[u8]) {
+        for &b in data {
+            if self.len == RX_BUFFER_LEN {
+                self.dropped += 1;
+                continue;
+            }
+            self.buf[(self.head + self.len) % RX_BUFFER_LEN] = b;
+            self.len += 1;
+        }
+    }
+
+    fn pop(&mut self, out: &mut [u8]) -> usize {
+        let n = out.len().min(self.len);
+        for o in &mut out[..n] {
+            *o = self.buf[self.head];
+            self.head = (self.head + 1) % RX_BUFFER_LEN;
+        }
+        self.len -= n;
+        n
+    }
+}
+
+#[derive(Copy, Clone)]
+struct Handles {
+    rx: u16,
+    tx_cccd: u16,
+}
+
+static HANDLES: Mutex<CriticalSectionRawMutex, RefCell<Option<Handles>>> =
+    Mutex::new(RefCell::new(None));
+static RX: Mutex<CriticalSectionRawMutex, RefCell<Ring>> = Mutex::new(RefCell::new(Ring {
+    buf: [0; RX_BUFFER_LEN],
+    head: 0,
+    len: 0,
+    dropped: 0,
+}));
+static RX_READY: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+/// Bearer that enabled TX notifications and the link it runs on.
+static SUBSCRIBER: Mutex<CriticalSectionRawMutex, RefCell<Option<(Bearer, u16)>>> =
+    Mutex::new(RefCell::new(None));
+
+/// A registered UART service.
+pub struct UartService {
+    rx: UartRx,
+    tx: UartTx,
+}
+
+impl UartService {
+    /// Add the service to the GATT database; call once after stack init.
+    pub fn register() -> Result<Self> {
+        let service = gatt::add_primary_service_uuid128(SERVICE_UUID, ATTRIBUTE_RECORDS as u8)?;
+        let rx = gatt::add_char_uuid128_variable(
+            &service,
+            RX_UUID,
+            CHAR_PROP_WRITE_WITHOUT_RESP,
+            gatt::ATTR_PERMISSION_NONE,
+            MAX_CHUNK as u16,
+            GATT_NOTIFY_ATTRIBUTE_WRITE,
+            0,
+        )?;
+        let tx = gatt::add_char_uuid128_variable(
+            &service,
+            TX_UUID,
+            CHAR_PROP_NOTIFY,
+            gatt::ATTR_PERMISSION_NONE,
+            MAX_CHUNK as u16,
+            GATT_NOTIFY_ATTRIBUTE_WRITE,
+            0,
+        )?;
+        HANDLES.lock(|h| {
+            *h.borrow_mut() = Some(Handles {
+                rx: rx.value_handle(),
+                tx_cccd: tx.handle + 2,
+            })
+        });
+        gatt::add_write_listener(on_write)?;
+        conn::add_disconnect_listener(on_link_closed)?;
+        Ok(UartService {
+            rx: UartRx { _priv: () },
+            tx: UartTx { service, tx },
+        })
+    }
+
+    /// Separate halves for a reader and a writer task.
+    pub fn split(&mut self) -> (&mut UartRx, &mut UartTx) {
+        (&mut self.rx, &mut self.tx)
+    }
+
+    /// Whether a peer has TX notifications enabled.
+    pub fn is_connected(&self) -> bool {
+        SUBSCRIBER.lock(|s| s.borrow().is_some())
+    }
+
+    /// Received bytes lost to a full RX buffer since the last call.
+    pub fn take_dropped(&self) -> usize {
+        RX.lock(|r| core::mem::take(&mut r.borrow_mut().dropped))
+    }
+
+    pub fn service(&self) -> &Service {
+        &self.tx.service
+    }
+}
+
+/// Receive half of [`UartService`].
+pub struct UartRx {
+    _priv: (),
+}
+
+/// Transmit half of [`UartService`].
+pub struct UartTx {
+    service: Service,
+    tx: Char,
+}
+
+impl UartTx {
+    /// Notify one chunk (≤ ATT MTU - 3) to the subscriber; returns bytes sent.
+    async fn send(&self, buf: &[u8]) -> Result<usize> {
+        let Some((bearer, _)) = SUBSCRIBER.lock(|s| *s.borrow()) else {
+            return Err(BleStatus::Other(status::UNKNOWN_CONNECTION));
+        };
+        let max = (gatt::att_mtu(bearer) as usize - 3).min(MAX_CHUNK);
+        let chunk = &buf[..buf.len().min(max)];
+        loop {
+            gatt::tx_pool_reset();
+            match gatt::notify(bearer, &self.service, &self.tx, chunk) {
+                Ok(()) => return Ok(chunk.len()),
+                // Stack buffers full; retry after aci_gatt_tx_pool_available_event.
+                Err(BleStatus::Other(status::INSUFFICIENT_RESOURCES)) => {
+                    gatt::tx_pool_available().await
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl ErrorType for UartRx {
+    type Error = BleStatus;
+}
+
+impl ErrorType for UartTx {
+    type Error = BleStatus;
+}
+
+impl ErrorType for UartService {
+    type Error = BleStatus;
+}
+
+impl Read for UartRx {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            RX_READY.reset();
+            let n = RX.lock(|r| r.borrow_mut().pop(buf));
+            if n > 0 {
+                return Ok(n);
+            }
+            RX_READY.wait().await;
+        }
+    }
+}
+
+impl Write for UartTx {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        self.send(buf).await
+    }
+}
+
+impl Read for UartService {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.rx.read(buf).await
+    }
+}
+
+impl Write for UartService {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.tx.write(buf).await
+    }
+}
+
+fn on_write(w: &AttributeWrite<'_>) {
+    let Some(h) = HANDLES.lock(|h| *h.borrow()) else {
+        return;
+    };
+    if w.handle == h.rx {
+        RX.lock(|r| r.borrow_mut().push(w.data));
+        RX_READY.signal(());
+    } else if w.handle == h.tx_cccd {
+        let notify = w.data.first().is_some_and(|v| v & 0x01 != 0);
+        SUBSCRIBER.lock(|s| {
+            let mut s = s.borrow_mut();
+            if notify {
+                // Enhanced bearers not opened through `l2cap` cannot be
+                // tied to their link, so they are not accepted.
+                if let Some(conn) = gatt::bearer_connection(w.bearer) {
+                    *s = Some((w.bearer, conn));
+                }
+            } else if s.is_some_and(|(b, _)| b == w.bearer) {
+                *s = None;
+            }
+        });
+    }
+}
+
+/// Forget the subscriber when its link goes down.
+fn on_link_closed(d: &Disconnection) {
+    SUBSCRIBER.lock(|s| {
+        let mut s = s.borrow_mut();
+        if s.is_some_and(|(_, conn)| conn == d.handle) {
+            *s = None;
+        }
+    });
+}
@@ -0,0 +1,421 @@
+// st_wba_ble/src/rng.rs
+//! Random numbers for the stack (enabled with the `rust-rng` feature).
+//!
+//! Replaces ST's `hw_rng.c`: the `HW_RNG_*` symbols the link layer and
+//! `BLEPLAT_RngGet` use are exported from here and draw from the installed
+//! [`RngProvider`]. By default that is the STM32WBA RNG peripheral
+//! ([`Stm32WbaRng`]); host tests can install a [`SoftRng`] instead.
+//!
+//! ```ignore
+//! static mut TEST_RNG: SoftRng = SoftRng::new(0x1234_5678_9abc_def0);
+//! st_wba_ble::rng::set_provider(unsafe { &mut *core::ptr::addr_of_mut!(TEST_RNG) });
+//! ```
+
+#![allow(non_snake_case)]
+
+use core::cell::RefCell;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+
+/// The random source could not deliver entropy (seed or clock error).
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum RngError {
+    Seed,
+    Clock,
+}
+
+/// Source of random words for the stack.
+pub trait RngProvider: Send {
+    /// Prepare the source (enable clocks, condition the generator).
+    fn init(&mut self) {}
+
+    /// Bring the source back after an error; defaults to [`init`](Self::init).
+    fn reset(&mut self) {
+        self.init();
+    }
+
+    /// Return the next 32-bit random word.
+    fn next_u32(&mut self) -> Result<u32, RngError>;
+
+    /// Fill `buf` with random bytes.
+    fn fill_bytes(&mut self, buf: &mut [u8]) -> Result<(), RngError> {
+        for chunk in buf.chunks_mut(4) {
+            let w = self.next_u32()?.to_le_bytes();
+            chunk.copy_from_slice(&w[..chunk.len()]);
+        }
+        Ok(())
+    }
+}
+
+static HW: Mutex<CriticalSectionRawMutex, RefCell<Stm32WbaRng>> =
+    Mutex::new(RefCell::new(Stm32WbaRng::new()));
+static PROVIDER: Mutex<CriticalSectionRawMutex, RefCell<Option<&'static mut dyn RngProvider>>> =
+    Mutex::new(RefCell::new(None));
+
+/// Use `provider` instead of the RNG peripheral; call before the stack starts.
+pub fn set_provider(provider: &'static mut dyn RngProvider) {
+    provider.init();
+    PROVIDER.lock(|p| *p.borrow_mut() = Some(provider));
+}
+
+/// Fill `buf` from the installed provider.
+pub fn fill_bytes(buf: &mut [u8]) -> Result<(), RngError> {
+    with_provider(|p| p.fill_bytes(buf))
+}
+
+fn with_provider<R>(f: impl FnOnce(&mut dyn RngProvider) -> R) -> R {
+    PROVIDER.lock(|p| match p.borrow_mut().as_deref_mut() {
+        Some(p) => f(p),
+        None => HW.lock(|hw| f(&mut *hw.borrow_mut())),
+    })
+}
+
+// ===== STM32WBA RNG peripheral (RM0493) =====
+
+const RNG_BASE: usize = 0x420C_0800;
+const RNG_CR: usize = RNG_BASE;
+const RNG_SR: usize = RNG_BASE + 0x04;
+const RNG_DR: usize = RNG_BASE + 0x08;
+
+const CR_RNGEN: u32 = 1 << 2;
+const CR_CED: u32 = 1 << 5;
+const CR_CONDRST: u32 = 1 << 30;
+/// NIST SP 800-90B compliant configuration (CONFIG1=0x0F, NISTC, CONFIG3=0xD).
+const CR_NIST_CONFIG: u32 = 0x00F0_1D00;
+
+const SR_DRDY: u32 = 1 << 0;
+const SR_CECS: u32 = 1 << 1;
+const SR_SECS: u32 = 1 << 2;
+const SR_CEIS: u32 = 1 << 5;
+const SR_SEIS: u32 = 1 << 6;
+
+const RCC_AHB2RSTR: usize = 0x4602_0C00 + 0x064;
+const RCC_AHB2RSTR_RNGRST: u32 = 1 << 18;
+const RCC_AHB2ENR: usize = 0x4602_0C00 + 0x08C;
+const RCC_AHB2ENR_RNGEN: u32 = 1 << 18;
+
+/// Polls before giving up on a data-ready flag.
+const DRDY_TIMEOUT: u32 = 100_000;
+/// Polls before giving up on the end of a conditioning reset.
+const CONDRST_TIMEOUT: u32 = 100_000;
+/// Resets [`HW_RNG_Get`] tries before reporting the source as failed.
+const MAX_RESETS: u32 = 8;
+
+/// Driver for the on-chip true RNG. The RNG kernel clock source must be
+/// configured by the HAL beforehand.
+pub struct Stm32WbaRng {
+    ready: bool,
+}
+
+impl Default for Stm32WbaRng {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Stm32WbaRng {
+    pub const fn new() -> Self {
+        Stm32WbaRng { ready: false }
+    }
+
+    fn read(addr: usize) -> u32 {
+        unsafe { core::ptr::read_volatile(addr as *const u32) }
+    }
+
+    fn write(addr: usize, v: u32) {
+        unsafe { core::ptr::write_volatile(addr as *mut u32, v) }
+    }
+
+    /// Apply the configuration through a conditioning reset and enable the RNG.
+    /// Fails if CONDRST does not clear, which means the RNG kernel clock is off.
+    fn condition(&mut self) -> Result<(), RngError> {
+        self.ready = false;
+        Self::write(RNG_CR, CR_NIST_CONFIG | CR_CONDRST);
+        Self::write(RNG_CR, CR_NIST_CONFIG);
+        let mut polls = 0;
+        while Self::read(RNG_CR) & CR_CONDRST != 0 {
+            polls += 1;
+            if polls > CONDRST_TIMEOUT {
+                return Err(RngError::Clock);
+            }
+        }
+        Self::write(RNG_CR, CR_NIST_CONFIG | CR_RNGEN);
+        self.ready = true;
+        Ok(())
+    }
+
+    fn enable_clock() {
+        let en = Self::read(RCC_AHB2ENR);
+        Self::write(RCC_AHB2ENR, en | RCC_AHB2ENR_RNGEN);
+    }
+
+    /// Stop the generator (the stack calls this before entering Stop mode).
+    fn disable(&mut self) {
+        Self::write(RNG_CR, Self::read(RNG_CR) & !(CR_RNGEN | CR_CED));
+        self.ready = false;
+    }
+}
+
+impl RngProvider for Stm32WbaRng {
+    /// A failed conditioning leaves the driver not ready; the next
+    /// [`next_u32`](RngProvider::next_u32) retries it and reports the error.
+    fn init(&mut self) {
+        Self::enable_clock();
+        let _ = self.condition();
+    }
+
+    /// Pulse the peripheral reset, then enable and condition it again.
+    fn reset(&mut self) {
+        self.disable();
+        let rst = Self::read(RCC_AHB2RSTR);
+        Self::write(RCC_AHB2RSTR, rst | RCC_AHB2RSTR_RNGRST);
+        Self::write(RCC_AHB2RSTR, rst & !RCC_AHB2RSTR_RNGRST);
+        self.init();
+    }
+
+    fn next_u32(&mut self) -> Result<u32, RngError> {
+        if !self.ready {
+            Self::enable_clock();
+            self.condition()?;
+        }
+        let mut polls = 0;
+        loop {
+            let sr = Self::read(RNG_SR);
+            if sr & (SR_SECS | SR_SEIS) != 0 {
+                // Seed error: clear the flag and recondition (RM0493 "Error management").
+                Self::write(RNG_SR, sr & !SR_SEIS);
+                let _ = self.condition();
+                return Err(RngError::Seed);
+            }
+            if sr & (SR_CECS | SR_CEIS) != 0 {
+                Self::write(RNG_SR, sr & !SR_CEIS);
+                return Err(RngError::Clock);
+            }
+            if sr & SR_DRDY != 0 {
+                let v = Self::read(RNG_DR);
+                // A zero word signals a seed error detected after DRDY; discard it.
+                if v != 0 {
+                    return Ok(v);
+                }
+            }
+            polls += 1;
+            if polls > DRDY_TIMEOUT {
+                return Err(RngError::Clock);
+            }
+        }
+    }
+}
+
+/// Deterministic xoshiro128++ generator for host tests. **Not** suitable
+/// for key generation on a device.
+pub struct SoftRng {
+    s: [u32; 4],
+}
+
+impl SoftRng {
+    pub const fn new(seed: u64) -> Self {
+        // splitmix-style expansion so that small seeds still fill the state.
+        let a = seed ^ 0x9E37_79B9_7F4A_7C15;
+        let b = a.wrapping_mul(0xBF58_476D_1CE4_E5B9) ^ 0x94D0_49BB_1331_11EB;
+        let s = [a as u32, (a >> 32) as u32, b as u32, (b >> 32) as u32];
+        let s = if s[0] | s[1] | s[2] | s[3] == 0 {
+            [1, 0, 0, 0]
+        } else {
+            s
+        };
+        SoftRng { s }
+    }
+}
+
+impl RngProvider for SoftRng {
+    fn next_u32(&mut self) -> Result<u32, RngError> {
+        let s = &mut self.s;
+        let r = s[0].wrapping_add(s[3]).rotate_left(7).wrapping_add(s[0]);
+        let t = s[1] << 9;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(11);
+        Ok(r)
+    }
+}
+
+// ===== hw_rng.c API =====
+
+#[unsafe(no_mangle)]
+pub extern "C" fn HW_RNG_Init() {
+    with_provider(|p| p.init());
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn HW_RNG_Start() {
+    with_provider(|p| p.init());
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn HW_RNG_Disable() {
+    let installed = PROVIDER.lock(|p| p.borrow().is_some());
+    if !installed {
+        HW.lock(|hw| hw.borrow_mut().disable());
+    }
+}
+
+/// Words are generated on demand, so there is no background refill to run.
+#[unsafe(no_mangle)]
+pub extern "C" fn HW_RNG_Process() {}
+
+/// Fill `val[..n]` with random words. The source is reset after every
+/// error and the word drawn again: handing the stack predictable key
+/// material is not an option, and the C signature has no error path.
+/// After `MAX_RESETS` failed resets in a row the failure is reported
+/// through the platform assert handler (a panic without `rust-platform`
+/// or a handler); if the handler returns, the retries start over.
+///
+/// # Safety
+/// `val` must point to `n` writable words.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn HW_RNG_Get(n: u8, val: *mut u32) {
+    for i in 0..n as usize {
+        let w = next_word();
+        unsafe { val.add(i).write(w) };
+    }
+}
+
+/// Single-word variant used by some ST utilities; returns 0 on success.
+///
+/// # Safety
+/// `pval` must be null or point to a writable word.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn HW_RNG_GetRandom32(pval: *mut u32) -> i32 {
+    if pval.is_null() {
+        return -1;
+    }
+    match with_provider(|p| p.next_u32()) {
+        Ok(w) => {
+            unsafe { pval.write(w) };
+            0
+        }
+        Err(_) => -1,
+    }
+}
+
+fn next_word() -> u32 {
+    loop {
+        for _ in 0..MAX_RESETS {
+            match with_provider(|p| p.next_u32()) {
+                Ok(w) => return w,
+                Err(_) => with_provider(|p| p.reset()),
+            }
+        }
+        report_failure();
+    }
+}
+
+fn report_failure() {
+    #[cfg(feature = "rust-platform")]
+    crate::platform::assert_failed("RNG failed to recover");
+    #[cfg(not(feature = "rust-platform"))]
+    panic!("RNG failed to recover");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static SERIAL: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    #[test]
+    fn soft_rng_is_deterministic_per_seed() {
+        let words = |seed| {
+            let mut r = SoftRng::new(seed);
+            [(); 4].map(|()| r.next_u32().unwrap())
+        };
+        assert_eq!(words(1), words(1));
+        assert_ne!(words(1), words(2));
+        // The all-zero xoshiro state would only ever produce zeros.
+        assert_ne!(words(0x9E37_79B9_7F4A_7C15), [0; 4]);
+    }
+
+    #[test]
+    fn fill_bytes_uses_little_endian_words() {
+        let mut a = SoftRng::new(7);
+        let mut b = SoftRng::new(7);
+        let mut buf = [0u8; 6];
+        a.fill_bytes(&mut buf).unwrap();
+        assert_eq!(buf[..4], b.next_u32().unwrap().to_le_bytes());
+        assert_eq!(buf[4..], b.next_u32().unwrap().to_le_bytes()[..2]);
+    }
+
+    /// Fails until it has been reset twice.
+    struct Flaky {
+        resets: u32,
+    }
+
+    impl RngProvider for Flaky {
+        fn reset(&mut self) {
+            self.resets += 1;
+        }
+
+        fn next_u32(&mut self) -> Result<u32, RngError> {
+            match self.resets {
+                0 => Err(RngError::Seed),
+                1 => Err(RngError::Clock),
+                n => Ok(n),
+            }
+        }
+    }
+
+    #[test]
+    fn hw_rng_get_resets_the_source_until_it_recovers() {
+        let _g = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        set_provider(Box::leak(Box::new(Flaky { resets: 0 })));
+        let mut out = [0u32; 2];
+        unsafe { HW_RNG_Get(2, out.as_mut_ptr()) };
+        assert_eq!(out, [2, 2]);
+    }
+
+    static RECOVERED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+    /// Fails until the assert handler has run.
+    struct Dead {
+        resets: u32,
+    }
+
+    impl RngProvider for Dead {
+        fn reset(&mut self) {
+            self.resets += 1;
+        }
+
+        fn next_u32(&mut self) -> Result<u32, RngError> {
+            if RECOVERED.load(std::sync::atomic::Ordering::Relaxed) {
+                Ok(self.resets)
+            } else {
+                Err(RngError::Clock)
+            }
+        }
+    }
+
+    #[cfg(feature = "rust-platform")]
+    #[test]
+    fn hw_rng_get_reports_a_source_that_does_not_recover() {
+        let _g = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        crate::platform::set_assert_handler(|| {
+            RECOVERED.store(true, std::sync::atomic::Ordering::Relaxed)
+        });
+        set_provider(Box::leak(Box::new(Dead { resets: 0 })));
+        let mut out = [0u32; 1];
+        unsafe { HW_RNG_Get(1, out.as_mut_ptr()) };
+        assert_eq!(out, [MAX_RESETS]);
+    }
+
+    #[cfg(not(feature = "rust-platform"))]
+    #[test]
+    #[should_panic(expected = "RNG failed to recover")]
+    fn next_word_panics_on_a_source_that_does_not_recover() {
+        let _g = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        set_provider(Box::leak(Box::new(Dead { resets: 0 })));
+        next_word();
+    }
+}
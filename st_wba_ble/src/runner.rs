@@ -0,0 +1,84 @@
+// st_wba_ble/src/runner.rs
+//! Host stack scheduling: runs `BleStack_Process` when the stack asks for it.
+//!
+//! Replaces ST's `host_stack_if.c` and the sequencer task it posts. The stack
+//! calls `BleStackCB_Process` (from thread or interrupt context) whenever it
+//! has work; the runner then drains it and sleeps until the next request, so
+//! the executor can idle (and the MCU enter low-power modes) in between.
+//! With `rust-platform` it also runs the link layer's background process,
+//! with `rust-timer` it wakes up for the stack's timers, and with `rust-mm`
+//! it retries allocations that were waiting for memory.
+//!
+//! ```ignore
+//! #[embassy_executor::task]
+//! async fn ble_task(runner: StackRunner) -> ! {
+//!     runner.run().await
+//! }
+//!
+//! spawner.spawn(ble_task(StackRunner::new())).unwrap();
+//! ```
+
+#[cfg(feature = "rust-timer")]
+use embassy_futures::select::select;
+use embassy_futures::yield_now;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use st_wba_ble_sys::ffi;
+
+/// `BleStack_Process` return value meaning "call me again".
+const BLE_SLEEPMODE_RUNNING: u8 = 0;
+
+static PROCESS: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// Ask the runner to service the host stack (e.g. after feeding it an event).
+pub fn schedule() {
+    PROCESS.signal(());
+}
+
+/// Drives the BLE host stack; spawn [`StackRunner::run`] in its own task.
+pub struct StackRunner {
+    _priv: (),
+}
+
+impl Default for StackRunner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StackRunner {
+    pub const fn new() -> Self {
+        StackRunner { _priv: () }
+    }
+
+    /// Service the stack forever.
+    pub async fn run(self) -> ! {
+        loop {
+            #[cfg(feature = "rust-platform")]
+            crate::platform::process();
+            #[cfg(feature = "rust-mm")]
+            crate::mm::process();
+            // Drain pending work, letting other tasks run between steps.
+            while unsafe { ffi::BleStack_Process() } == BLE_SLEEPMODE_RUNNING {
+                yield_now().await;
+            }
+            #[cfg(feature = "rust-timer")]
+            match crate::timer::next_deadline() {
+                Some(at) => {
+                    select(PROCESS.wait(), embassy_time::Timer::at(at)).await;
+                }
+                None => PROCESS.wait().await,
+            }
+            #[cfg(not(feature = "rust-timer"))]
+            PROCESS.wait().await;
+        }
+    }
+}
+
+// ===== Stack callbacks =====
+
+/// Called by the stack when `BleStack_Process` needs to run.
+#[unsafe(no_mangle)]
+pub extern "C" fn BleStackCB_Process() {
+    schedule();
+}
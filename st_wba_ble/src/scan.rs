@@ -0,0 +1,143 @@
+// st_wba_ble/src/scan.rs
+//! Legacy LE scanning (observer / central discovery).
+
+use crate::addr::{Address, AddressType, OwnAddress};
+use crate::status::{BleStatus, Result};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use st_wba_ble_sys::ffi;
+
+/// Which advertisers are reported while scanning.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(u8)]
+pub enum ScanFilterPolicy {
+    #[default]
+    AcceptAll = 0x00,
+    /// Only advertisers in the Filter Accept List.
+    AcceptListOnly = 0x01,
+    /// Like `AcceptAll`, also reporting directed advertising to our RPA.
+    AcceptAllResolvable = 0x02,
+    /// Like `AcceptListOnly`, also reporting directed advertising to our RPA.
+    AcceptListResolvable = 0x03,
+}
+
+/// Scan parameters for [`start`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ScanParams {
+    /// Send SCAN_REQ to get scan responses.
+    pub active: bool,
+    /// Scan interval, in units of 0.625 ms.
+    pub interval: u16,
+    /// Scan window, in units of 0.625 ms (<= `interval`).
+    pub window: u16,
+    /// Address used in SCAN_REQ; use a `Resolvable*` variant when privacy is enabled.
+    pub own_address: OwnAddress,
+    /// Let the controller drop duplicate reports.
+    pub filter_duplicates: bool,
+    /// Filter Accept List usage (see [`crate::filter::FilterAcceptList`]).
+    pub filter_policy: ScanFilterPolicy,
+}
+
+impl Default for ScanParams {
+    /// Active scan, 100 ms interval, 50 ms window, public address.
+    fn default() -> Self {
+        Self {
+            active: true,
+            interval: 0x00A0,
+            window: 0x0050,
+            own_address: OwnAddress::Public,
+            filter_duplicates: true,
+            filter_policy: ScanFilterPolicy::AcceptAll,
+        }
+    }
+}
+
+/// One advertising report.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ScanReport {
+    /// ADV_IND, ADV_DIRECT_IND, ADV_SCAN_IND, ADV_NONCONN_IND or SCAN_RSP (0..=4).
+    pub event_type: u8,
+    pub address: Address,
+    pub rssi: i8,
+    data: [u8; 31],
+    len: u8,
+}
+
+impl ScanReport {
+    /// Advertising or scan-response payload.
+    pub fn data(&self) -> &[u8] {
+        &self.data[..self.len as usize]
+    }
+}
+
+static REPORTS: Channel<CriticalSectionRawMutex, ScanReport, 8> = Channel::new();
+
+/// Configure and start scanning.
+pub fn start(params: &ScanParams) -> Result<()> {
+    let rc = unsafe {
+        ffi::hci_le_set_scan_parameters(
+            params.active as u8,
+            params.interval,
+            params.window,
+            params.own_address as u8,
+            params.filter_policy as u8,
+        )
+    } as i32;
+    if BleStatus::from(rc) != BleStatus::Ok {
+        return Err(BleStatus::from(rc));
+    }
+
+    let rc = unsafe { ffi::hci_le_set_scan_enable(1, params.filter_duplicates as u8) } as i32;
+    if BleStatus::from(rc) == BleStatus::Ok {
+        Ok(())
+    } else {
+        Err(BleStatus::from(rc))
+    }
+}
+
+/// Stop scanning.
+pub fn stop() -> Result<()> {
+    let rc = unsafe { ffi::hci_le_set_scan_enable(0, 0) } as i32;
+    if BleStatus::from(rc) == BleStatus::Ok {
+        Ok(())
+    } else {
+        Err(BleStatus::from(rc))
+    }
+}
+
+/// Wait for the next advertising report. Reports are dropped while nobody is reading.
+pub async fn next_report() -> ScanReport {
+    REPORTS.receive().await
+}
+
+// ===== Stack event callbacks =====
+
+/// # Safety
+/// Called by the stack with `num_reports` valid entries at `advertising_report`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hci_le_advertising_report_event(
+    num_reports: u8,
+    advertising_report: *const ffi::Advertising_Report_t,
+) {
+    if advertising_report.is_null() {
+        return;
+    }
+    for i in 0..num_reports as usize {
+        let r = unsafe { core::ptr::read_unaligned(advertising_report.add(i)) };
+        let len = core::cmp::min(r.Data_Length, 31);
+        let mut data = [0u8; 31];
+        if !r.Data.is_null() {
+            unsafe { core::ptr::copy_nonoverlapping(r.Data, data.as_mut_ptr(), len as usize) };
+        }
+        let _ = REPORTS.try_send(ScanReport {
+            event_type: r.Event_Type,
+            address: Address {
+                kind: AddressType::from(r.Address_Type),
+                bytes: r.Address,
+            },
+            rssi: r.RSSI,
+            data,
+            len,
+        });
+    }
+}
@@ -0,0 +1,283 @@
+// st_wba_ble/src/security.rs
+//! Pairing and bonding (SMP) configuration and event handling.
+//!
+//! Pick IO capabilities and authentication requirements once after init,
+//! then call [`Security::request`] on a link (or wait for the peer to start
+//! pairing) and answer the events delivered by [`Security::next_event`].
+
+use crate::conn::{Connection, MAX_CONNECTIONS, Role};
+use crate::status::{BleStatus, Result};
+use core::sync::atomic::{AtomicU8, Ordering};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::{Channel, TrySendError};
+use st_wba_ble_sys::ffi;
+
+/// IO capabilities advertised during pairing (`aci_gap_set_io_capability`).
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum IoCapability {
+    DisplayOnly = 0x00,
+    DisplayYesNo = 0x01,
+    KeyboardOnly = 0x02,
+    NoInputNoOutput = 0x03,
+    KeyboardDisplay = 0x04,
+}
+
+/// LE Secure Connections support level.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum SecureConnections {
+    /// Legacy pairing only.
+    NotSupported = 0x00,
+    /// Use LE Secure Connections when the peer supports it.
+    Optional = 0x01,
+    /// Reject peers that cannot do LE Secure Connections.
+    Only = 0x02,
+}
+
+/// Which identity address is distributed during bonding.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum IdentityAddress {
+    Public = 0x00,
+    StaticRandom = 0x01,
+}
+
+/// Authentication requirements (`aci_gap_set_authentication_requirement`).
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct AuthRequirements {
+    /// Store keys so the link can be re-encrypted on reconnection.
+    pub bonding: bool,
+    /// Require MITM protection (passkey entry or numeric comparison).
+    pub mitm: bool,
+    pub secure_connections: SecureConnections,
+    pub keypress_notifications: bool,
+    /// Encryption key size bounds (7..=16 bytes).
+    pub min_key_size: u8,
+    pub max_key_size: u8,
+    /// Use a fixed passkey instead of asking the application for one.
+    pub fixed_pin: Option<u32>,
+    pub identity_address: IdentityAddress,
+}
+
+impl AuthRequirements {
+    /// Just Works: bonding without MITM protection.
+    pub const fn just_works() -> Self {
+        Self {
+            bonding: true,
+            mitm: false,
+            secure_connections: SecureConnections::Optional,
+            keypress_notifications: false,
+            min_key_size: 7,
+            max_key_size: 16,
+            fixed_pin: None,
+            identity_address: IdentityAddress::Public,
+        }
+    }
+
+    /// MITM-protected bonding; the method (passkey entry or numeric
+    /// comparison) follows from both sides' IO capabilities.
+    pub const fn authenticated() -> Self {
+        Self {
+            mitm: true,
+            ..Self::just_works()
+        }
+    }
+
+    /// MITM-protected bonding that refuses legacy pairing.
+    pub const fn secure_connections_only() -> Self {
+        Self {
+            mitm: true,
+            secure_connections: SecureConnections::Only,
+            min_key_size: 16,
+            ..Self::just_works()
+        }
+    }
+}
+
+impl Default for AuthRequirements {
+    fn default() -> Self {
+        Self::just_works()
+    }
+}
+
+/// Identity address type last accepted by the stack (the stack default is public).
+static IDENTITY: AtomicU8 = AtomicU8::new(IdentityAddress::Public as u8);
+
+/// Identity address type configured through [`Security::set_auth_requirements`].
+pub(crate) fn identity_address() -> IdentityAddress {
+    match IDENTITY.load(Ordering::Relaxed) {
+        0x00 => IdentityAddress::Public,
+        _ => IdentityAddress::StaticRandom,
+    }
+}
+
+/// Pairing outcome reported by `aci_gap_pairing_complete_event`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PairingStatus {
+    Success,
+    Timeout,
+    Failed,
+    Other(u8),
+}
+
+impl From<u8> for PairingStatus {
+    fn from(v: u8) -> Self {
+        match v {
+            0x00 => PairingStatus::Success,
+            0x01 => PairingStatus::Timeout,
+            0x02 => PairingStatus::Failed,
+            x => PairingStatus::Other(x),
+        }
+    }
+}
+
+/// Security events the application has to react to.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SecurityEvent {
+    /// Enter the passkey shown on the peer (or display ours) and answer
+    /// with [`Security::pass_key_resp`].
+    PasskeyRequest { handle: u16 },
+    /// Show `value` and answer with [`Security::numeric_comparison_value_confirm`].
+    NumericComparison { handle: u16, value: u32 },
+    /// Pairing finished; `reason` is the SMP failure reason when not successful.
+    PairingComplete {
+        handle: u16,
+        status: PairingStatus,
+        reason: u8,
+    },
+}
+
+/// A pending request and the completion for every link.
+const EVENT_QUEUE_LEN: usize = 2 * MAX_CONNECTIONS;
+
+static EVENTS: Channel<CriticalSectionRawMutex, SecurityEvent, EVENT_QUEUE_LEN> = Channel::new();
+
+/// Queue `ev`, dropping the oldest event if the application fell behind so
+/// the latest state of every link is always delivered.
+fn post(mut ev: SecurityEvent) {
+    while let Err(TrySendError::Full(e)) = EVENTS.try_send(ev) {
+        let _ = EVENTS.try_receive();
+        ev = e;
+    }
+}
+
+/// Namespace for the GAP security procedures.
+pub struct Security;
+
+impl Security {
+    /// Set the local IO capabilities.
+    pub fn set_io_capability(cap: IoCapability) -> Result<()> {
+        let rc = unsafe { ffi::aci_gap_set_io_capability(cap as u8) } as i32;
+        if BleStatus::from(rc) == BleStatus::Ok {
+            Ok(())
+        } else {
+            Err(BleStatus::from(rc))
+        }
+    }
+
+    /// Set the authentication requirements used for subsequent pairings.
+    ///
+    /// `bonding` is ignored while the bond store is not persistent
+    /// (see [`BondStore::set_persistent`](crate::bond::BondStore::set_persistent)).
+    pub fn set_auth_requirements(req: &AuthRequirements) -> Result<()> {
+        let rc = unsafe {
+            ffi::aci_gap_set_authentication_requirement(
+                (req.bonding && crate::bond::is_persistent()) as u8,
+                req.mitm as u8,
+                req.secure_connections as u8,
+                req.keypress_notifications as u8,
+                req.min_key_size,
+                req.max_key_size,
+                // Use_Fixed_Pin: 0x00 = fixed pin, 0x01 = ask the application.
+                req.fixed_pin.is_none() as u8,
+                req.fixed_pin.unwrap_or(0),
+                req.identity_address as u8,
+            )
+        } as i32;
+        if BleStatus::from(rc) == BleStatus::Ok {
+            IDENTITY.store(req.identity_address as u8, Ordering::Relaxed);
+            Ok(())
+        } else {
+            Err(BleStatus::from(rc))
+        }
+    }
+
+    /// Set IO capabilities and authentication requirements in one go.
+    pub fn configure(cap: IoCapability, req: &AuthRequirements) -> Result<()> {
+        Self::set_io_capability(cap)?;
+        Self::set_auth_requirements(req)
+    }
+
+    /// Start security on a link: a Security Request as Peripheral,
+    /// a Pairing Request as Central.
+    pub fn request(conn: &Connection) -> Result<()> {
+        let rc = unsafe {
+            match conn.role() {
+                Role::Peripheral => ffi::aci_gap_slave_security_req(conn.handle()),
+                Role::Central => ffi::aci_gap_send_pairing_req(conn.handle(), 0),
+            }
+        } as i32;
+        if BleStatus::from(rc) == BleStatus::Ok {
+            Ok(())
+        } else {
+            Err(BleStatus::from(rc))
+        }
+    }
+
+    /// Answer a [`SecurityEvent::PasskeyRequest`] with a 6-digit passkey (0..=999999).
+    pub fn pass_key_resp(conn: &Connection, passkey: u32) -> Result<()> {
+        let rc = unsafe { ffi::aci_gap_pass_key_resp(conn.handle(), passkey) } as i32;
+        if BleStatus::from(rc) == BleStatus::Ok {
+            Ok(())
+        } else {
+            Err(BleStatus::from(rc))
+        }
+    }
+
+    /// Answer a [`SecurityEvent::NumericComparison`]: `true` if the values match.
+    pub fn numeric_comparison_value_confirm(conn: &Connection, matches: bool) -> Result<()> {
+        let rc = unsafe {
+            ffi::aci_gap_numeric_comparison_value_confirm_yesno(conn.handle(), matches as u8)
+        } as i32;
+        if BleStatus::from(rc) == BleStatus::Ok {
+            Ok(())
+        } else {
+            Err(BleStatus::from(rc))
+        }
+    }
+
+    /// Wait for the next security event on any link.
+    pub async fn next_event() -> SecurityEvent {
+        EVENTS.receive().await
+    }
+}
+
+// ===== Stack event callbacks =====
+
+#[unsafe(no_mangle)]
+pub extern "C" fn aci_gap_pass_key_req_event(connection_handle: u16) {
+    post(SecurityEvent::PasskeyRequest {
+        handle: connection_handle,
+    });
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn aci_gap_numeric_comparison_value_event(
+    connection_handle: u16,
+    numeric_value: u32,
+) {
+    post(SecurityEvent::NumericComparison {
+        handle: connection_handle,
+        value: numeric_value,
+    });
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn aci_gap_pairing_complete_event(connection_handle: u16, status: u8, reason: u8) {
+    post(SecurityEvent::PairingComplete {
+        handle: connection_handle,
+        status: PairingStatus::from(status),
+        reason,
+    });
+}
@@ -0,0 +1,352 @@
+// st_wba_ble/src/stack.rs
+//! Host stack memory configuration and `BleStack_Init`.
+//!
+//! Replaces the `CFG_BLE_*` knobs of ST's `app_conf.h`: describe the stack's
+//! capacity with a [`StackConfig`], let [`stack_buffers!`](crate::stack_buffers)
+//! reserve correctly-sized static RAM for it, then call [`StackConfig::init`]
+//! before [`BleConfig::init`](crate::BleConfig::init).
+//!
+//! ```ignore
+//! const STACK: StackConfig = StackConfig::new().links(2).att_mtu(247);
+//! let bufs = st_wba_ble::stack_buffers!(STACK);
+//! STACK.init(bufs)?;
+//! ```
+
+use crate::status::{self, BleStatus, Result};
+use core::cell::Cell;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use st_wba_ble_sys::ffi;
+
+/// Configuration the stack was started with by [`StackConfig::init`].
+static ACTIVE: Mutex<CriticalSectionRawMutex, Cell<Option<StackConfig>>> =
+    Mutex::new(Cell::new(None));
+
+/// The [`StackConfig`] passed to a successful [`StackConfig::init`]; `None`
+/// if the stack was started elsewhere (e.g. by ST's C code).
+pub(crate) fn active() -> Option<StackConfig> {
+    ACTIVE.lock(|a| a.get())
+}
+
+// ===== Buffer sizing (mirrors the macros in ST's blestack.h) =====
+
+/// Size of one stack memory block, in bytes.
+pub const BLE_MEM_BLOCK_SIZE: usize = 32;
+/// ATT MTU every link starts with.
+pub const BLE_DEFAULT_ATT_MTU: u16 = 23;
+/// Memory blocks reserved for LE Secure Connections.
+pub const BLE_MBLOCKS_SECURE_CONNECTIONS: u16 = 4;
+
+const fn div_ceil(a: usize, b: usize) -> usize {
+    a.div_ceil(b)
+}
+
+/// `BLE_PREP_WRITE_X_ATT`: prepare-write list entries needed for an attribute of `max_att` bytes.
+pub const fn prep_write_x_att(max_att: u16) -> u16 {
+    (div_ceil(max_att as usize, (BLE_DEFAULT_ATT_MTU - 5) as usize) * 2) as u16
+}
+
+/// `BLE_MEM_BLOCK_X_TX`: memory blocks to send one MTU-sized packet.
+pub const fn mem_block_x_tx(mtu: u16) -> u16 {
+    (div_ceil(mtu as usize + 4, BLE_MEM_BLOCK_SIZE) + 1) as u16
+}
+
+/// `BLE_MEM_BLOCK_X_RX`: memory blocks to receive one MTU-sized packet on `n_link` links.
+pub const fn mem_block_x_rx(mtu: u16, n_link: u8) -> u16 {
+    ((div_ceil(mtu as usize + 4, BLE_MEM_BLOCK_SIZE) + 2) * n_link as usize + 1) as u16
+}
+
+/// `BLE_MEM_BLOCK_X_MTU`: memory blocks to carry one MTU-sized packet on `n_link` links.
+pub const fn mem_block_x_mtu(mtu: u16, n_link: u8) -> u16 {
+    mem_block_x_tx(mtu) + mem_block_x_rx(mtu, n_link)
+}
+
+/// `BLE_MBLOCKS_CALC`: total memory blocks for a prepare-write list, MTU and link count.
+pub const fn mblocks_calc(prep_write_list_size: u16, mtu: u16, n_link: u8) -> u16 {
+    let x = mem_block_x_mtu(mtu, n_link);
+    let x = if x > BLE_MBLOCKS_SECURE_CONNECTIONS {
+        x
+    } else {
+        BLE_MBLOCKS_SECURE_CONNECTIONS
+    };
+    prep_write_list_size + x
+}
+
+/// `BLE_TOTAL_BUFFER_SIZE`: bytes of host RAM for `n_link` links and `mblocks` memory blocks.
+pub const fn total_buffer_size(n_link: u8, mblocks: u16) -> usize {
+    16 + ffi::BLE_FIXED_BUFFER_SIZE_BYTES as usize
+        + ffi::BLE_PER_LINK_SIZE_BYTES as usize * n_link as usize
+        + (BLE_MEM_BLOCK_SIZE + 8) * mblocks as usize
+}
+
+/// `BLE_TOTAL_BUFFER_SIZE_GATT`: bytes of GATT database RAM.
+pub const fn total_buffer_size_gatt(
+    num_gatt_attributes: u16,
+    num_gatt_services: u16,
+    att_value_array_size: u16,
+) -> usize {
+    // `((size - 1) | 3) + 1`: rounded up to 4 bytes, and 0 when empty.
+    (att_value_array_size as usize).next_multiple_of(4)
+        + 40 * num_gatt_attributes as usize
+        + 48 * num_gatt_services as usize
+}
+
+/// `BleStack_init_t.options` flags (blestack.h).
+pub mod options {
+    /// Link-layer only: no host stack.
+    pub const LL_ONLY: u16 = 0x0001;
+    /// Do not create the Service Changed descriptor.
+    pub const NO_SVC_CHANGE_DESC: u16 = 0x0002;
+    /// Device Name characteristic is read-only.
+    pub const DEV_NAME_READ_ONLY: u16 = 0x0004;
+    /// Enable extended advertising.
+    pub const EXTENDED_ADV: u16 = 0x0008;
+    /// Store a reduced GATT database in NVM.
+    pub const REDUCED_DB_IN_NVM: u16 = 0x0020;
+    /// Enable GATT caching.
+    pub const GATT_CACHING: u16 = 0x0040;
+    /// Power Class 1 (high-power PA).
+    pub const POWER_CLASS_1: u16 = 0x0080;
+    /// Appearance characteristic is writable.
+    pub const APPEARANCE_WRITABLE: u16 = 0x0100;
+    /// Enable Enhanced ATT.
+    pub const ENHANCED_ATT: u16 = 0x0200;
+}
+
+/// Stack capacity passed to `BleStack_Init`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct StackConfig {
+    /// Simultaneous connections.
+    pub num_links: u8,
+    /// GATT attribute records (including GAP/GATT services).
+    pub num_gatt_attributes: u16,
+    /// GATT services (including GAP/GATT services).
+    pub num_gatt_services: u16,
+    /// Bytes reserved for characteristic values.
+    pub att_value_array_size: u16,
+    /// Largest ATT MTU the stack negotiates.
+    pub att_mtu: u16,
+    pub prepare_write_list_size: u16,
+    /// Memory blocks; recomputed by the builder methods via [`mblocks_calc`].
+    pub mblock_count: u16,
+    /// L2CAP CoC: maximum MPS, channel count, and channels we may initiate.
+    pub max_coc_mps: u16,
+    pub max_coc_nbr: u8,
+    pub max_coc_initiator_nbr: u8,
+    /// Additional Enhanced ATT bearers per link.
+    pub max_add_eatt_bearers: u8,
+    /// [`options`] flags.
+    pub options: u16,
+    pub debug: u8,
+}
+
+impl Default for StackConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StackConfig {
+    /// Defaults matching ST's example `app_conf.h` (2 links, 68 attributes,
+    /// 8 services, 1344-byte value array, 251-byte MTU).
+    pub const fn new() -> Self {
+        let num_links = 2;
+        let att_mtu = 251;
+        let prepare_write_list_size = prep_write_x_att(512);
+        Self {
+            num_links,
+            num_gatt_attributes: 68,
+            num_gatt_services: 8,
+            att_value_array_size: 1344,
+            att_mtu,
+            prepare_write_list_size,
+            mblock_count: mblocks_calc(prepare_write_list_size, att_mtu, num_links),
+            max_coc_mps: 248,
+            max_coc_nbr: 0,
+            max_coc_initiator_nbr: 0,
+            max_add_eatt_bearers: 0,
+            options: 0,
+            debug: 0,
+        }
+    }
+
+    const fn recompute(mut self) -> Self {
+        self.mblock_count =
+            mblocks_calc(self.prepare_write_list_size, self.att_mtu, self.num_links);
+        self
+    }
+
+    pub const fn links(mut self, n: u8) -> Self {
+        self.num_links = n;
+        self.recompute()
+    }
+
+    pub const fn att_mtu(mut self, mtu: u16) -> Self {
+        self.att_mtu = mtu;
+        self.recompute()
+    }
+
+    /// GATT database capacity.
+    pub const fn gatt(mut self, attributes: u16, services: u16, value_bytes: u16) -> Self {
+        self.num_gatt_attributes = attributes;
+        self.num_gatt_services = services;
+        self.att_value_array_size = value_bytes;
+        self
+    }
+
+    /// GATT attribute records only.
+    pub const fn attributes(mut self, n: u16) -> Self {
+        self.num_gatt_attributes = n;
+        self
+    }
+
+    /// Size the prepare-write list for attributes up to `max_att` bytes.
+    pub const fn max_attribute_size(mut self, max_att: u16) -> Self {
+        self.prepare_write_list_size = prep_write_x_att(max_att);
+        self.recompute()
+    }
+
+    /// L2CAP connection-oriented channels.
+    pub const fn coc(mut self, channels: u8, initiator_channels: u8, mps: u16) -> Self {
+        self.max_coc_nbr = channels;
+        self.max_coc_initiator_nbr = initiator_channels;
+        self.max_coc_mps = mps;
+        self
+    }
+
+    pub const fn eatt_bearers(mut self, n: u8) -> Self {
+        self.max_add_eatt_bearers = n;
+        self
+    }
+
+    pub const fn options(mut self, flags: u16) -> Self {
+        self.options = flags;
+        self
+    }
+
+    /// Host RAM needed, in 32-bit words.
+    pub const fn host_buffer_words(&self) -> usize {
+        div_ceil(total_buffer_size(self.num_links, self.mblock_count), 4)
+    }
+
+    /// GATT database RAM needed, in 32-bit words.
+    pub const fn gatt_buffer_words(&self) -> usize {
+        div_ceil(
+            total_buffer_size_gatt(
+                self.num_gatt_attributes,
+                self.num_gatt_services,
+                self.att_value_array_size,
+            ),
+            4,
+        )
+    }
+
+    /// Initialise the host stack with `bufs` (from [`stack_buffers!`](crate::stack_buffers)).
+    pub fn init(&self, bufs: StackBuffers) -> Result<()> {
+        if bufs.host.len() < self.host_buffer_words() || bufs.gatt.len() < self.gatt_buffer_words()
+        {
+            return Err(BleStatus::Other(status::INVALID_PARAMS));
+        }
+
+        let mut p: ffi::BleStack_init_t = unsafe { core::mem::zeroed() };
+        p.bleStartRamAddress = bufs.host.as_mut_ptr() as *mut u8;
+        p.total_buffer_size = (bufs.host.len() * 4) as u32;
+        p.bleStartRamAddress_GATT = bufs.gatt.as_mut_ptr() as *mut u8;
+        p.total_buffer_size_GATT = (bufs.gatt.len() * 4) as u32;
+        p.numAttrRecord = self.num_gatt_attributes;
+        p.numAttrServ = self.num_gatt_services;
+        p.attrValueArrSize = self.att_value_array_size;
+        p.numOfLinks = self.num_links;
+        p.prWriteListSize = self.prepare_write_list_size;
+        p.mblockCount = self.mblock_count;
+        p.attMtu = self.att_mtu;
+        p.max_coc_mps = self.max_coc_mps;
+        p.max_coc_nbr = self.max_coc_nbr;
+        p.max_coc_initiator_nbr = self.max_coc_initiator_nbr;
+        p.max_add_eatt_bearers = self.max_add_eatt_bearers;
+        p.options = self.options;
+        p.debug = self.debug;
+
+        let rc = unsafe { ffi::BleStack_Init(&p) } as i32;
+        if BleStatus::from(rc) == BleStatus::Ok {
+            ACTIVE.lock(|a| a.set(Some(*self)));
+            Ok(())
+        } else {
+            Err(BleStatus::from(rc))
+        }
+    }
+}
+
+/// Static RAM handed to the stack; create with [`stack_buffers!`](crate::stack_buffers).
+pub struct StackBuffers {
+    host: &'static mut [u32],
+    gatt: &'static mut [u32],
+}
+
+impl StackBuffers {
+    /// Wrap caller-provided buffers (word-aligned, sized per [`StackConfig`]).
+    pub fn new(host: &'static mut [u32], gatt: &'static mut [u32]) -> Self {
+        Self { host, gatt }
+    }
+}
+
+/// Reserve static, correctly-sized stack buffers for a `const` [`StackConfig`].
+///
+/// Evaluates to a [`StackBuffers`](crate::stack::StackBuffers); panics if the
+/// same invocation is evaluated twice.
+#[macro_export]
+macro_rules! stack_buffers {
+    ($cfg:expr) => {{
+        const CFG: $crate::stack::StackConfig = $cfg;
+        const HOST_WORDS: usize = CFG.host_buffer_words();
+        const GATT_WORDS: usize = CFG.gatt_buffer_words();
+        static mut HOST: [u32; HOST_WORDS] = [0; HOST_WORDS];
+        static mut GATT: [u32; GATT_WORDS] = [0; GATT_WORDS];
+        static TAKEN: ::core::sync::atomic::AtomicBool =
+            ::core::sync::atomic::AtomicBool::new(false);
+        assert!(
+            !TAKEN.swap(true, ::core::sync::atomic::Ordering::AcqRel),
+            "stack_buffers! evaluated twice"
+        );
+        // SAFETY: guarded by TAKEN, so each static is borrowed exactly once.
+        unsafe {
+            $crate::stack::StackBuffers::new(
+                &mut *::core::ptr::addr_of_mut!(HOST),
+                &mut *::core::ptr::addr_of_mut!(GATT),
+            )
+        }
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Expected values evaluated from the blestack.h macros.
+
+    #[test]
+    fn mem_blocks_match_blestack_h() {
+        assert_eq!(mem_block_x_tx(23), 2);
+        assert_eq!(mem_block_x_rx(23, 1), 4);
+        assert_eq!(mem_block_x_mtu(23, 1), 6);
+        assert_eq!(mem_block_x_mtu(247, 2), 30);
+        assert_eq!(mem_block_x_mtu(512, 4), 95);
+    }
+
+    #[test]
+    fn mblocks_calc_matches_blestack_h() {
+        assert_eq!(prep_write_x_att(18), 2);
+        assert_eq!(prep_write_x_att(19), 4);
+        assert_eq!(prep_write_x_att(512), 58);
+        assert_eq!(mblocks_calc(prep_write_x_att(512), 247, 2), 88);
+        assert_eq!(mblocks_calc(0, 23, 1), 6);
+    }
+
+    #[test]
+    fn gatt_buffer_size_matches_blestack_h() {
+        assert_eq!(total_buffer_size_gatt(0, 0, 0), 0);
+        assert_eq!(total_buffer_size_gatt(10, 2, 1), 4 + 400 + 96);
+        assert_eq!(total_buffer_size_gatt(10, 2, 4), 4 + 400 + 96);
+        assert_eq!(total_buffer_size_gatt(10, 2, 5), 8 + 400 + 96);
+    }
+}
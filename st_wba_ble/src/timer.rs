@@ -0,0 +1,90 @@
+// st_wba_ble/src/timer.rs
+//! Host stack timers (enabled with the `rust-timer` feature).
+//!
+//! Backs `BLEPLAT_TimerStart`/`TimerStop` (see [`crate::platform`]) with
+//! `embassy-time`, replacing ST's `ble_timer.c`. Running timers are kept in a
+//! small table; [`crate::StackRunner`] sleeps until the earliest deadline and
+//! reports each expiry to the stack with `BLEPLATCB_TimerExpiry`.
+
+use core::cell::RefCell;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_time::{Duration, Instant};
+
+/// Timers the stack can run at once (SMP, GATT and L2CAP timeouts per link).
+pub const MAX_TIMERS: usize = 8;
+
+/// `BLE_STATUS_INSUFFICIENT_RESOURCES`, returned when the table is full.
+pub(crate) const INSUFFICIENT_RESOURCES: u8 = 0x64;
+
+#[derive(Copy, Clone)]
+struct Slot {
+    id: u16,
+    deadline: Instant,
+}
+
+static SLOTS: Mutex<CriticalSectionRawMutex, RefCell<[Option<Slot>; MAX_TIMERS]>> =
+    Mutex::new(RefCell::new([None; MAX_TIMERS]));
+
+unsafe extern "C" {
+    fn BLEPLATCB_TimerExpiry(id: u16);
+}
+
+/// (Re)start timer `id` to expire in `timeout_ms`; false if no slot is free.
+pub(crate) fn start(id: u16, timeout_ms: u32) -> bool {
+    let deadline = Instant::now() + Duration::from_millis(timeout_ms as u64);
+    let started = SLOTS.lock(|s| {
+        let mut s = s.borrow_mut();
+        let idx = s
+            .iter()
+            .position(|t| t.is_some_and(|t| t.id == id))
+            .or_else(|| s.iter().position(Option::is_none));
+        match idx {
+            Some(i) => {
+                s[i] = Some(Slot { id, deadline });
+                true
+            }
+            None => false,
+        }
+    });
+    if started {
+        // Let the runner pick up the new deadline.
+        crate::runner::schedule();
+    }
+    started
+}
+
+/// Stop timer `id`; no-op if it is not running.
+pub(crate) fn stop(id: u16) {
+    SLOTS.lock(|s| {
+        for t in s.borrow_mut().iter_mut() {
+            if t.is_some_and(|t| t.id == id) {
+                *t = None;
+            }
+        }
+    });
+}
+
+/// Earliest running deadline, for the runner to sleep on.
+pub(crate) fn next_deadline() -> Option<Instant> {
+    SLOTS.lock(|s| s.borrow().iter().flatten().map(|t| t.deadline).min())
+}
+
+/// Fire every expired timer; called from [`crate::StackRunner`].
+pub(crate) fn process() {
+    let now = Instant::now();
+    loop {
+        // One at a time, outside the lock: the callback may start or stop timers.
+        let expired = SLOTS.lock(|s| {
+            let mut s = s.borrow_mut();
+            let i = s
+                .iter()
+                .position(|t| t.is_some_and(|t| t.deadline <= now))?;
+            s[i].take().map(|t| t.id)
+        });
+        match expired {
+            Some(id) => unsafe { BLEPLATCB_TimerExpiry(id) },
+            None => break,
+        }
+    }
+}
@@ -285,8 +285,10 @@ fn main() {
         .header(inc_auto.join("ble_events.h").to_string_lossy())
         .allowlist_function("aci_.*")
         .allowlist_function("hci_.*")
+        .allowlist_function("BleStack_.*")
         .allowlist_var("HCI_.*|ACI_.*|GAP_.*|GATT_.*|BLE_.*")
-        .allowlist_type(".*(Handle|Event|Param|Service|Characteristic|Status|Opcode).*");
+        .allowlist_type(".*(Handle|Event|Param|Service|Characteristic|Status|Opcode).*")
+        .allowlist_type("BleStack_init_t");
 
     // ---- feed clang the ARM toolchain headers ----
     let gcc = env::var("ARM_NONE_EABI_GCC").unwrap_or_else(|_| "arm-none-eabi-gcc".into());
@@ -400,26 +402,51 @@ fn build_wba_glue(repo_root: &std::path::Path) -> bool {
 
     // Minimal set used by ST examples (add only if they exist locally)
     let mut files: Vec<PathBuf> = vec![
-        target.join("bleplat.c"),
-        target.join("linklayer_plat.c"),
-        target.join("ll_sys_if.c"),
-        target.join("host_stack_if.c"),
+        // host_stack_if.c is replaced by `st_wba_ble::runner` (BleStackCB_Process).
         target.join("power_table.c"),
-        system.join("Modules/ble_timer.c"),
         system.join("Modules/stm_list.c"),
         system.join("Modules/utilities_common.c"),
-        system.join("Modules/Flash/flash_driver.c"),
-        system.join("Modules/Flash/flash_manager.c"),
-        system.join("Modules/Flash/simple_nvm_arbiter.c"),
         system.join("Modules/Flash/rf_timing_synchro.c"),
-        system.join("Modules/Nvm/nvm.c"),
-        system.join("Modules/MemoryManager/stm32_mm.c"),
-        system.join("Modules/MemoryManager/advanced_memory_manager.c"),
-        system.join("Interfaces/hw_rng.c"),
-        system.join("Interfaces/hw_aes.c"),
-        system.join("Interfaces/hw_pka.c"),
-        system.join("Interfaces/pka_p256.c"),
     ];
+    // bleplat.c / linklayer_plat.c / ll_sys_if.c are replaced by
+    // `st_wba_ble::platform` with the `rust_platform` feature.
+    if !feat("rust_platform") {
+        files.extend([
+            target.join("bleplat.c"),
+            target.join("linklayer_plat.c"),
+            target.join("ll_sys_if.c"),
+        ]);
+    }
+    if !feat("rust_rng") {
+        files.push(system.join("Interfaces/hw_rng.c"));
+    }
+    if !feat("rust_timer") {
+        files.push(system.join("Modules/ble_timer.c"));
+    }
+    if !feat("rust_nvm") {
+        files.extend([
+            system.join("Modules/Flash/flash_driver.c"),
+            system.join("Modules/Flash/flash_manager.c"),
+            system.join("Modules/Flash/simple_nvm_arbiter.c"),
+            system.join("Modules/Nvm/nvm.c"),
+        ]);
+    }
+    if !feat("rust_mm") {
+        files.extend([
+            system.join("Modules/MemoryManager/stm32_mm.c"),
+            system.join("Modules/MemoryManager/advanced_memory_manager.c"),
+        ]);
+    }
+    if !feat("rust_crypto") {
+        files.extend([
+            system.join("Modules/bpka.c"),
+            system.join("Modules/baes/baes_ecb.c"),
+            system.join("Modules/baes/baes_cmac.c"),
+            system.join("Interfaces/hw_aes.c"),
+            system.join("Interfaces/hw_pka.c"),
+            system.join("Interfaces/pka_p256.c"),
+        ]);
+    }
     files.retain(|p| p.exists());
     if files.is_empty() {
         return false;